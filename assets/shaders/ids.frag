@@ -0,0 +1,14 @@
+#version 400 core
+
+in vec2 barycentric;
+
+uniform uint objectId; // 0 is reserved for the background
+uniform uint submesh;
+
+layout(location = 0) out uvec4 ids;
+layout(location = 1) out vec2 weights;
+
+void main() {
+    ids = uvec4(objectId, submesh, uint(gl_PrimitiveID), floatBitsToUint(gl_FragCoord.z));
+    weights = barycentric;
+}
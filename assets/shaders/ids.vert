@@ -0,0 +1,11 @@
+#version 400 core
+
+layout(location = 0) in vec3 OS_position;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 perspective;
+
+void main() {
+    gl_Position = perspective * view * model * vec4(OS_position, 1.0);
+}
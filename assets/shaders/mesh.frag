@@ -2,10 +2,15 @@
 
 in vec3 WS_position; // world-space position
 in vec3 WS_normal;   // world-space normal
+in vec2 uv;
+in vec4 vertexColor;
 
 out vec3 fragColor;
 
 uniform vec3 cDiffuse;
+uniform bool useTexture;
+uniform bool useVertexColor;
+uniform sampler2D diffuseTexture;
 
 const float u_time = 0.0;
 const float light_dist = 10.0;
@@ -29,5 +34,12 @@ void main() {
 
     float diff = max(0.0, dot(normalize(WS_normal), WS_toLight));
     vec3 diffuse = diff * lightColor;
-    fragColor = (ambient + diffuse) * cDiffuse;
+    vec3 albedo = cDiffuse;
+    if (useTexture) {
+        albedo *= texture(diffuseTexture, uv).rgb;
+    }
+    if (useVertexColor) {
+        albedo *= vertexColor.rgb;
+    }
+    fragColor = (ambient + diffuse) * albedo;
 }
@@ -2,6 +2,7 @@
 
 layout(location = 0) in vec3 OS_position;
 layout(location = 1) in vec3 OS_normal;
+layout(location = 2) in vec4 color;
 layout(location = 5) in vec2 texCoord;
 
 uniform mat4 model;
@@ -10,10 +11,14 @@ uniform mat4 perspective;
 
 out vec3 WS_position; // world-space position
 out vec3 WS_normal;   // world-space normal
+out vec2 uv;
+out vec4 vertexColor;
 
 void main() {
     WS_position = (model * vec4(OS_position, 1.0)).xyz;
     WS_normal = (model * vec4(OS_normal, 0.0)).xyz;
+    uv = texCoord;
+    vertexColor = color;
 
     gl_Position = perspective * view * vec4(WS_position, 1.0);
 }
@@ -0,0 +1,12 @@
+#version 400 core
+
+in vec4 vertexColor;
+
+out vec3 fragColor;
+
+uniform vec3 cDiffuse;
+uniform bool useVertexColor;
+
+void main() {
+    fragColor = useVertexColor ? vertexColor.rgb : cDiffuse;
+}
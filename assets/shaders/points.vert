@@ -0,0 +1,15 @@
+#version 400 core
+
+layout(location = 0) in vec3 OS_position;
+layout(location = 2) in vec4 color;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 perspective;
+
+out vec4 vertexColor;
+
+void main() {
+    vertexColor = color;
+    gl_Position = perspective * view * model * vec4(OS_position, 1.0);
+}
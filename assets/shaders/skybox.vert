@@ -4,6 +4,7 @@ layout(location = 0) in vec3 position;
 
 uniform mat4 view;
 uniform mat4 perspective;
+uniform float farDepth; // NDC depth of the far plane: 1, or -1 for reverse-Z
 
 out vec3 texCoords;
 
@@ -12,5 +13,6 @@ void main() {
 
     mat4 view_upper3x3 = mat4(mat3(view));
     vec4 pos = perspective * view_upper3x3 * vec4(position, 1.0);
-    gl_Position = pos.xyww; // set depth value to maximum after perspective division
+    // set depth value to the far plane after perspective division
+    gl_Position = vec4(pos.xy, farDepth * pos.w, pos.w);
 }
@@ -0,0 +1,145 @@
+//! `#[derive(Vertex)]` for the vertex types of the `opengl` crate.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Expr, Field, Fields};
+
+/// Derive `rendergl::Vertex` for a struct with named fields, each marked with the shader
+/// location it is bound to.
+///
+/// ```rust,ignore
+/// #[derive(Copy, Clone, Debug, Vertex)]
+/// #[repr(C, packed)]
+/// pub struct VertexN {
+///     #[location(ShaderAttrib::POSITION)]
+///     pos: glm::Vec3,
+///     #[location(ShaderAttrib::NORMAL)]
+///     n: glm::Vec3,
+/// }
+/// ```
+///
+/// Offsets come from the layout of the struct, and the data type, component count and
+/// normalisation of each attribute from its field type's `rendergl::AttribFormat`, so fields
+/// of other types do not compile. `from_point_color` converts the position, normal or texture
+/// coordinates of the `SurfacePoint`, or the colour, into each field with `From`, according to
+/// its location, and fields at any other location are `Default`. `from_point3d` gives white
+/// colours.
+///
+/// The generated code refers to `crate::rendergl`, so it can only be derived within the
+/// `opengl` crate.
+#[proc_macro_derive(Vertex, attributes(location))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "Vertex cannot be derived for generic types",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) if !fields.named.is_empty() => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    name.span(),
+                    "Vertex can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "Vertex can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut markers = Vec::new();
+    let mut values = Vec::new();
+    let mut used: Vec<String> = Vec::new();
+    for field in fields {
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let location = location(field)?;
+        let location_name = match &location {
+            Expr::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        };
+        if let Some(location_name) = &location_name {
+            if used.contains(location_name) {
+                return Err(Error::new(
+                    location.span(),
+                    format!("location {} is used by more than one field", location_name),
+                ));
+            }
+            used.push(location_name.clone());
+        }
+
+        markers.push(quote_spanned! {ty.span()=>
+            crate::rendergl::VBOAttribMarker::of::<#ty>(
+                #location,
+                ::std::mem::offset_of!(#name, #ident),
+            )
+        });
+        let value = match location_name.as_deref() {
+            Some("POSITION") => quote!(::std::convert::From::from(point.position())),
+            Some("NORMAL") => quote!(::std::convert::From::from(point.normal())),
+            Some("TEXCOORD0") => quote!(::std::convert::From::from(point.texcoord())),
+            Some("COLOR") => quote!(::std::convert::From::from(color)),
+            _ => quote!(::std::default::Default::default()),
+        };
+        values.push(quote_spanned!(ty.span()=> #ident: #value));
+    }
+
+    Ok(quote! {
+        impl crate::rendergl::Vertex for #name {
+            fn vertex_attrib_markers() -> ::std::vec::Vec<crate::rendergl::VBOAttribMarker> {
+                ::std::vec![#(#markers),*]
+            }
+
+            fn from_point3d(point: &crate::util::SurfacePoint) -> #name {
+                <#name as crate::rendergl::Vertex>::from_point_color(
+                    point,
+                    ::glm::vec4(1.0, 1.0, 1.0, 1.0),
+                )
+            }
+
+            #[allow(unused_variables)]
+            fn from_point_color(point: &crate::util::SurfacePoint, color: ::glm::Vec4) -> #name {
+                #name { #(#values),* }
+            }
+        }
+    })
+}
+
+/// Shader location of `field`, from its `#[location(...)]` attribute.
+fn location(field: &Field) -> syn::Result<Expr> {
+    let mut attrs = field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("location"));
+    let attr = attrs.next().ok_or_else(|| {
+        Error::new(
+            field.span(),
+            "missing shader location, such as #[location(ShaderAttrib::POSITION)]",
+        )
+    })?;
+    if let Some(extra) = attrs.next() {
+        return Err(Error::new(
+            extra.span(),
+            "a field can only have one shader location",
+        ));
+    }
+    attr.parse_args()
+}
@@ -0,0 +1,448 @@
+//! Keyframe animation of object transforms and shader uniforms.
+//!
+//! An `AnimationClip` groups typed keyframe tracks: translation, rotation (as quaternions),
+//! scale, and any number of named uniforms. Each `Track` is sampled with its own
+//! `Interpolation`. An `Animator` plays a clip back over time according to its `Playback` mode,
+//! producing a `Pose` which can be applied to a `Drawable`.
+//!
+//! Clips can also be written as text files and loaded with `ResourceLoader::load_clip`; the
+//! scene's ball plays `assets/animations/bounce.clip`.
+//!
+//! # Examples
+//!
+//! Bob an object up and down while fading a colour uniform, ping-ponging forever:
+//! ```rust,ignore
+//! let clip = AnimationClip::new(Playback::PingPong)
+//!     .translation(
+//!         Track::new(Interpolation::Cubic)
+//!             .key(0.0, vec3(0.0, 0.0, 0.0))
+//!             .key(1.0, vec3(0.0, 0.5, 0.0))
+//!             .key(2.0, vec3(0.0, 0.2, 0.0)),
+//!     )
+//!     .uniform(
+//!         "cDiffuse",
+//!         UniformTrack::Vec3(
+//!             Track::new(Interpolation::Linear)
+//!                 .key(0.0, vec3(1.0, 0.0, 0.0))
+//!                 .key(2.0, vec3(0.0, 0.0, 1.0)),
+//!         ),
+//!     );
+//! scene.animate(0, clip);
+//! ```
+
+use glm;
+use num;
+
+use crate::rendergl::{uniform, Program, UniformValue};
+use crate::util::Quat;
+
+/// Method used to compute values between two keyframes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Interpolation {
+    /// Hold the value of the previous keyframe.
+    Step,
+    /// Linear interpolation (spherical linear interpolation for rotations).
+    Linear,
+    /// Catmull-Rom spline through the keyframes, parameterised by keyframe time.
+    Cubic,
+}
+
+/// How an `Animator` maps elapsed time onto a clip.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Playback {
+    /// Play once, then hold the final pose.
+    Once,
+    /// Restart from the beginning after reaching the end.
+    Loop,
+    /// Alternate between playing forwards and backwards.
+    PingPong,
+}
+
+/// Values that can be interpolated between keyframes.
+pub trait Interpolate: Copy {
+    /// Interpolate from `a` to `b` by `t`.
+    ///
+    /// Values of `t` outside of `[0, 1]` must extrapolate, since cubic interpolation is built
+    /// out of repeated calls to `lerp`.
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn lerp(a: &f32, b: &f32, t: f32) -> f32 {
+        a + (b - a) * t
+    }
+}
+
+impl Interpolate for glm::Vec2 {
+    fn lerp(a: &glm::Vec2, b: &glm::Vec2, t: f32) -> glm::Vec2 {
+        *a + (*b - *a) * t
+    }
+}
+
+impl Interpolate for glm::Vec3 {
+    fn lerp(a: &glm::Vec3, b: &glm::Vec3, t: f32) -> glm::Vec3 {
+        *a + (*b - *a) * t
+    }
+}
+
+impl Interpolate for glm::Vec4 {
+    fn lerp(a: &glm::Vec4, b: &glm::Vec4, t: f32) -> glm::Vec4 {
+        *a + (*b - *a) * t
+    }
+}
+
+impl Interpolate for Quat {
+    fn lerp(a: &Quat, b: &Quat, t: f32) -> Quat {
+        a.slerp(b, t)
+    }
+}
+
+/// A value at a point in time.
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+/// A sequence of keyframes for a single animated value.
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+    interpolation: Interpolation,
+}
+
+impl<T: Interpolate> Track<T> {
+    /// Construct an empty track.
+    pub fn new(interpolation: Interpolation) -> Track<T> {
+        Track {
+            keyframes: Vec::new(),
+            interpolation,
+        }
+    }
+
+    /// Add a keyframe at `time` seconds. Keyframes are kept sorted by time, so they may be
+    /// added in any order.
+    pub fn key(mut self, time: f32, value: T) -> Track<T> {
+        let index = self
+            .keyframes
+            .iter()
+            .position(|k| k.time > time)
+            .unwrap_or(self.keyframes.len());
+        self.keyframes.insert(index, Keyframe { time, value });
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    /// Sample the track at `time`. Times outside of the keyframe range hold the first or last
+    /// value. Returns `None` if the track has no keyframes.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let keys = &self.keyframes;
+        let first = keys.first()?;
+        let last = keys.last()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+        if time >= last.time {
+            return Some(last.value);
+        }
+
+        // index of the keyframe at the start of the segment containing `time`
+        let i = keys.iter().rposition(|k| k.time <= time).unwrap_or(0);
+        let (k1, k2) = (&keys[i], &keys[i + 1]);
+
+        let value = match self.interpolation {
+            Interpolation::Step => k1.value,
+            Interpolation::Linear => T::lerp(&k1.value, &k2.value, ratio(time, k1.time, k2.time)),
+            Interpolation::Cubic => {
+                // duplicate the end keyframes, one segment-length beyond the track
+                let k0 = if i > 0 {
+                    keys[i - 1]
+                } else {
+                    Keyframe {
+                        time: k1.time - (k2.time - k1.time),
+                        value: k1.value,
+                    }
+                };
+                let k3 = if i + 2 < keys.len() {
+                    keys[i + 2]
+                } else {
+                    Keyframe {
+                        time: k2.time + (k2.time - k1.time),
+                        value: k2.value,
+                    }
+                };
+                catmull_rom(&k0, k1, k2, &k3, time)
+            }
+        };
+        Some(value)
+    }
+}
+
+/// Normalised position of `t` within `[t0, t1]`.
+fn ratio(t: f32, t0: f32, t1: f32) -> f32 {
+    if t1 - t0 > f32::EPSILON {
+        (t - t0) / (t1 - t0)
+    } else {
+        0.0
+    }
+}
+
+/// Evaluate a Catmull-Rom spline between `k1` and `k2` using the Barry-Goldman pyramidal
+/// formulation.
+///
+/// The formulation only needs (extrapolating) linear interpolation, so it works for any
+/// `Interpolate` type - including quaternions, where `lerp` is a slerp.
+fn catmull_rom<T: Interpolate>(
+    k0: &Keyframe<T>,
+    k1: &Keyframe<T>,
+    k2: &Keyframe<T>,
+    k3: &Keyframe<T>,
+    t: f32,
+) -> T {
+    let (t0, t1, t2, t3) = (k0.time, k1.time, k2.time, k3.time);
+    let a1 = T::lerp(&k0.value, &k1.value, ratio(t, t0, t1));
+    let a2 = T::lerp(&k1.value, &k2.value, ratio(t, t1, t2));
+    let a3 = T::lerp(&k2.value, &k3.value, ratio(t, t2, t3));
+    let b1 = T::lerp(&a1, &a2, ratio(t, t0, t2));
+    let b2 = T::lerp(&a2, &a3, ratio(t, t1, t3));
+    T::lerp(&b1, &b2, ratio(t, t1, t2))
+}
+
+/// Keyframe track for a shader uniform of a given type.
+#[derive(Clone, Debug)]
+pub enum UniformTrack {
+    Float(Track<f32>),
+    Vec2(Track<glm::Vec2>),
+    Vec3(Track<glm::Vec3>),
+    Vec4(Track<glm::Vec4>),
+}
+
+impl UniformTrack {
+    pub fn duration(&self) -> f32 {
+        match self {
+            UniformTrack::Float(track) => track.duration(),
+            UniformTrack::Vec2(track) => track.duration(),
+            UniformTrack::Vec3(track) => track.duration(),
+            UniformTrack::Vec4(track) => track.duration(),
+        }
+    }
+
+    pub fn sample(&self, time: f32) -> Option<UniformValue> {
+        match self {
+            UniformTrack::Float(track) => track.sample(time).map(UniformValue::Float),
+            UniformTrack::Vec2(track) => track.sample(time).map(UniformValue::Vec2),
+            UniformTrack::Vec3(track) => track.sample(time).map(UniformValue::Vec3),
+            UniformTrack::Vec4(track) => track.sample(time).map(UniformValue::Vec4),
+        }
+    }
+}
+
+/// Result of sampling an `AnimationClip`.
+#[derive(Clone, Debug, Default)]
+pub struct Pose {
+    /// Model transform, if the clip animates translation, rotation or scale.
+    pub transform: Option<glm::Mat4>,
+    /// Uniform values to set before drawing.
+    pub uniforms: Vec<(String, UniformValue)>,
+}
+
+impl Pose {
+    /// Set each of the pose's uniforms on `program`. The program must be bound.
+    pub fn apply_uniforms(&self, program: &Program) -> Result<(), uniform::Error> {
+        for (name, value) in &self.uniforms {
+            program.set_uniform(name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of keyframe tracks played back together.
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    translation: Option<Track<glm::Vec3>>,
+    rotation: Option<Track<Quat>>,
+    scale: Option<Track<glm::Vec3>>,
+    uniforms: Vec<(String, UniformTrack)>,
+    playback: Playback,
+}
+
+impl AnimationClip {
+    /// Construct a clip with no tracks.
+    pub fn new(playback: Playback) -> AnimationClip {
+        AnimationClip {
+            translation: None,
+            rotation: None,
+            scale: None,
+            uniforms: Vec::new(),
+            playback,
+        }
+    }
+
+    /// A looping clip that makes one full revolution about `axis` every `period` seconds.
+    pub fn turntable(axis: glm::Vec3, period: f32) -> AnimationClip {
+        // quarter turns, so consecutive keys never sit on opposite sides of the quaternion sphere
+        let quarter = glm::ext::half_pi::<f32, f32>();
+        let track = (0..5).fold(Track::new(Interpolation::Linear), |track, i| {
+            let i = i as f32;
+            track.key(period * i / 4.0, Quat::from_axis_angle(axis, quarter * i))
+        });
+        AnimationClip::new(Playback::Loop).rotation(track)
+    }
+
+    pub fn translation(mut self, track: Track<glm::Vec3>) -> AnimationClip {
+        self.translation = Some(track);
+        self
+    }
+
+    pub fn rotation(mut self, track: Track<Quat>) -> AnimationClip {
+        self.rotation = Some(track);
+        self
+    }
+
+    pub fn scale(mut self, track: Track<glm::Vec3>) -> AnimationClip {
+        self.scale = Some(track);
+        self
+    }
+
+    /// Animate the uniform called `name`.
+    pub fn uniform(mut self, name: &str, track: UniformTrack) -> AnimationClip {
+        self.uniforms.push((name.into(), track));
+        self
+    }
+
+    pub fn playback(&self) -> Playback {
+        self.playback
+    }
+
+    /// Length of the clip, i.e. the time of the latest keyframe across all tracks.
+    pub fn duration(&self) -> f32 {
+        let transform_durations = [
+            self.translation.as_ref().map(Track::duration),
+            self.rotation.as_ref().map(Track::duration),
+            self.scale.as_ref().map(Track::duration),
+        ];
+        transform_durations
+            .iter()
+            .filter_map(|d| *d)
+            .chain(self.uniforms.iter().map(|(_, track)| track.duration()))
+            .fold(0.0, f32::max)
+    }
+
+    /// Map an elapsed playback time onto the clip's timeline according to its `Playback` mode.
+    pub fn local_time(&self, time: f32) -> f32 {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        match self.playback {
+            Playback::Once => time.clamp(0.0, duration),
+            Playback::Loop => time.rem_euclid(duration),
+            Playback::PingPong => {
+                let t = time.rem_euclid(2.0 * duration);
+                if t > duration {
+                    2.0 * duration - t
+                } else {
+                    t
+                }
+            }
+        }
+    }
+
+    /// Sample every track at `time` on the clip's timeline.
+    pub fn sample(&self, time: f32) -> Pose {
+        let translation = self.translation.as_ref().and_then(|t| t.sample(time));
+        let rotation = self.rotation.as_ref().and_then(|t| t.sample(time));
+        let scale = self.scale.as_ref().and_then(|t| t.sample(time));
+
+        let transform = if translation.is_some() || rotation.is_some() || scale.is_some() {
+            let t = glm::ext::translate(&num::one(), translation.unwrap_or(glm::to_vec3(0.0)));
+            let r = rotation.unwrap_or_default().to_mat4();
+            let s = glm::ext::scale(&num::one(), scale.unwrap_or(glm::to_vec3(1.0)));
+            Some(t * r * s)
+        } else {
+            None
+        };
+
+        let uniforms = self
+            .uniforms
+            .iter()
+            .filter_map(|(name, track)| track.sample(time).map(|v| (name.clone(), v)))
+            .collect();
+
+        Pose {
+            transform,
+            uniforms,
+        }
+    }
+}
+
+/// Plays back an `AnimationClip`.
+pub struct Animator {
+    clip: AnimationClip,
+    time: f32,
+    playing: bool,
+    /// Playback rate multiplier. Negative values play in reverse.
+    pub speed: f32,
+}
+
+impl Animator {
+    /// Construct an `Animator` which starts playing `clip` from the beginning.
+    pub fn new(clip: AnimationClip) -> Animator {
+        Animator {
+            clip,
+            time: 0.0,
+            playing: true,
+            speed: 1.0,
+        }
+    }
+
+    pub fn clip(&self) -> &AnimationClip {
+        &self.clip
+    }
+
+    /// Advance playback by `dt` seconds.
+    pub fn advance(&mut self, dt: f32) {
+        if self.playing {
+            self.time += dt * self.speed;
+        }
+    }
+
+    /// Jump to `time` seconds since the start of playback.
+    pub fn seek(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Whether a clip with `Playback::Once` has reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.clip.playback() == Playback::Once && self.time >= self.clip.duration()
+    }
+
+    /// Sample the clip at the current playback time.
+    pub fn pose(&self) -> Pose {
+        self.clip.sample(self.clip.local_time(self.time))
+    }
+}
@@ -1,13 +1,41 @@
 //! Camera implementation(s).
 //!
-//! The current camera implementation is a perspective camera. It supports a few basic
-//! operations. To render with a `Camera`, make sure to pass its perspective and view matrices to
-//! a shader which understands how to apply the homographic transform.
+//! The camera supports a few basic operations, and several kinds of projection (see
+//! `Projection`). To render with a `Camera`, make sure to pass its perspective and view matrices
+//! to a shader which understands how to apply the homographic transform.
+//!
+//! The camera's orientation is stored as a unit quaternion, so that it can be turned
+//! incrementally (`yaw`, `pitch`, `roll`, `orbit`) without accumulating error, and smoothly
+//! interpolated with a `CameraTransition`.
+//!
+//! Controllers translate user input into camera movement: `ArcballController` orbits, pans and
+//! dollies around a target point, while `FlyController` is a first-person free-fly camera.
 
 use glm;
+use glm::GenSquareMat;
 use num;
 
+use crate::picking::Ray;
+use crate::util::{BoundingVolume, Quat};
+
+mod arcball;
+pub mod bookmarks;
+mod fly;
+pub mod path;
+mod projection;
+mod transition;
+
+pub use self::arcball::{ArcballController, DragMode, OrbitStyle};
+pub use self::bookmarks::{Bookmark, Bookmarks};
+pub use self::fly::{FlyController, Movement};
+pub use self::path::{CameraPath, PathCurve, PathKey, PathPlayer};
+pub use self::projection::Projection;
+pub use self::transition::CameraTransition;
+
 /// Builder pattern for constructing a `Camera` out of parameters.
+///
+/// Once the camera is built, `look` and `up` in its parameters are kept up to date from its
+/// orientation, as unit vectors.
 pub struct CameraBuilder {
     pub eye: glm::Vec3,
     pub look: glm::Vec3,
@@ -16,6 +44,7 @@ pub struct CameraBuilder {
     pub far: f32,
     pub ratio: f32,
     pub fov: f32,
+    pub projection: Projection,
 }
 
 impl CameraBuilder {
@@ -32,6 +61,7 @@ impl CameraBuilder {
             far: 50.0,
             ratio: 1.0,
             fov: glm::ext::pi::<f32, f32>() / 3.0,
+            projection: Projection::Perspective,
         }
     }
 
@@ -40,6 +70,7 @@ impl CameraBuilder {
         self
     }
 
+    /// Direction the camera is looking in.
     pub fn look(mut self, look: &glm::Vec3) -> CameraBuilder {
         self.look = look.clone();
         self
@@ -72,41 +103,142 @@ impl CameraBuilder {
         self
     }
 
+    /// Kind of projection to use. Defaults to `Projection::Perspective`.
+    pub fn projection(mut self, projection: Projection) -> CameraBuilder {
+        self.projection = projection;
+        self
+    }
+
     pub fn build(self) -> Camera {
         Camera::new(self)
     }
 }
 
-/// Perspective camera implementation.
+/// Camera implementation.
 pub struct Camera {
+    /// Projection transform. Despite the name, this need not be a perspective projection; it is
+    /// whatever the camera's `Projection` describes.
     pub perspective: glm::Mat4,
     pub view: glm::Mat4,
+    orientation: Quat,
     params: CameraBuilder,
 }
 
 impl Camera {
     /// Construct a new `Camera` by consuming a `CameraBuilder` instance.
     pub fn new(params: CameraBuilder) -> Camera {
-        let view = Camera::create_view(&params);
         let perspective = Camera::create_perspective(&params);
-
-        Camera {
+        let mut camera = Camera {
             perspective,
-            view,
+            view: num::one(),
+            orientation: Quat::look_rotation(params.look, params.up),
             params,
-        }
+        };
+        camera.update_view();
+        camera
     }
 
     pub fn params(&self) -> &CameraBuilder {
         &self.params
     }
 
+    pub fn projection(&self) -> Projection {
+        self.params.projection
+    }
+
     fn create_perspective(params: &CameraBuilder) -> glm::Mat4 {
-        glm::ext::perspective(params.fov, params.ratio, params.near, params.far)
+        params.projection.matrix(params)
+    }
+
+    /// Rebuild the view transform from the position and orientation, and bring `look` and `up`
+    /// in the parameters up to date.
+    fn update_view(&mut self) {
+        self.orientation = self.orientation.normalize();
+        self.params.look = self.forward();
+        self.params.up = self.up();
+        let translate = glm::ext::translate(&num::one(), -self.params.eye);
+        self.view = self.orientation.conjugate().to_mat4() * translate;
+    }
+
+    /// Replace the camera's position, look direction and up vector. This will rebuild the
+    /// camera's view transform.
+    ///
+    /// `up` only needs to be roughly upright; the camera's actual up vector is made orthogonal
+    /// to `look`.
+    pub fn set_view(&mut self, eye: glm::Vec3, look: glm::Vec3, up: glm::Vec3) {
+        self.params.eye = eye;
+        self.orientation = Quat::look_rotation(look, up);
+        self.update_view();
+    }
+
+    pub fn position(&self) -> glm::Vec3 {
+        self.params.eye
+    }
+
+    /// Move the camera without turning it. This will rebuild the camera's view transform.
+    pub fn set_position(&mut self, eye: glm::Vec3) {
+        self.params.eye = eye;
+        self.update_view();
     }
 
-    fn create_view(params: &CameraBuilder) -> glm::Mat4 {
-        glm::ext::look_at(params.eye, params.look, glm::normalize(params.up))
+    /// Rotation from camera space (looking down -z, with +y up) to world space.
+    pub fn orientation(&self) -> Quat {
+        self.orientation
+    }
+
+    /// Turn the camera to `orientation`, without moving it. This will rebuild the camera's view
+    /// transform.
+    pub fn set_orientation(&mut self, orientation: Quat) {
+        self.orientation = orientation;
+        self.update_view();
+    }
+
+    /// Unit vector in the direction the camera is looking.
+    pub fn forward(&self) -> glm::Vec3 {
+        self.orientation.rotate(glm::vec3(0.0, 0.0, -1.0))
+    }
+
+    /// Unit vector pointing to the right of the view.
+    pub fn right(&self) -> glm::Vec3 {
+        self.orientation.rotate(glm::vec3(1.0, 0.0, 0.0))
+    }
+
+    /// Unit vector pointing to the top of the view.
+    pub fn up(&self) -> glm::Vec3 {
+        self.orientation.rotate(glm::vec3(0.0, 1.0, 0.0))
+    }
+
+    /// Turn left (positive `angle`) or right around the camera's own up axis, in radians.
+    pub fn yaw(&mut self, angle: f32) {
+        self.turn_local(glm::vec3(0.0, 1.0, 0.0), angle);
+    }
+
+    /// Tilt up (positive `angle`) or down around the camera's own right axis, in radians.
+    pub fn pitch(&mut self, angle: f32) {
+        self.turn_local(glm::vec3(1.0, 0.0, 0.0), angle);
+    }
+
+    /// Roll counter-clockwise (positive `angle`, as seen by the camera) or clockwise around the
+    /// view direction, in radians.
+    pub fn roll(&mut self, angle: f32) {
+        self.turn_local(glm::vec3(0.0, 0.0, 1.0), angle);
+    }
+
+    fn turn_local(&mut self, axis: glm::Vec3, angle: f32) {
+        self.orientation = self.orientation * Quat::from_axis_angle(axis, angle);
+        self.update_view();
+    }
+
+    /// Apply a world-space `rotation` to the camera's orientation, without moving it.
+    pub fn rotate(&mut self, rotation: Quat) {
+        self.orientation = rotation * self.orientation;
+        self.update_view();
+    }
+
+    /// Turn the camera to look at `target`, keeping `up` as upright as possible.
+    pub fn look_at(&mut self, target: glm::Vec3, up: glm::Vec3) {
+        self.orientation = Quat::look_rotation(target - self.params.eye, up);
+        self.update_view();
     }
 
     /// Set a new aspect ratio. This will rebuild the camera's perspective transform.
@@ -115,37 +247,208 @@ impl Camera {
         self.perspective = Camera::create_perspective(&self.params);
     }
 
+    /// Set a new vertical field-of-view, in radians. This will rebuild the camera's perspective
+    /// transform.
+    pub fn set_fov(&mut self, fov: f32) {
+        self.params.fov = fov;
+        self.perspective = Camera::create_perspective(&self.params);
+    }
+
+    /// Set new near and far clip plane distances. This will rebuild the camera's perspective
+    /// transform.
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.params.near = near;
+        self.params.far = far;
+        self.perspective = Camera::create_perspective(&self.params);
+    }
+
+    /// Replace the camera's projection as-is. This will rebuild the camera's perspective
+    /// transform.
+    ///
+    /// See `switch_projection` to change between kinds of projection without the view jumping.
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.params.projection = projection;
+        self.perspective = Camera::create_perspective(&self.params);
+    }
+
+    /// Change to a different kind of projection, keeping objects `focus_distance` in front of the
+    /// camera the same size on screen.
+    ///
+    /// Changing to an orthographic projection picks its height to match the current view at the
+    /// focus distance (any height given in `projection` is ignored). Changing from orthographic
+    /// to perspective moves the camera along its view direction instead, so that the focus point
+    /// stays put.
+    ///
+    /// Changing the lens shift of an off-axis projection moves the camera across the view by as
+    /// much as the frustum moves at the focus distance, so the view there does not move either.
+    /// Returns that movement, so that a controller orbiting the focus point can move it too.
+    pub fn switch_projection(&mut self, projection: Projection, focus_distance: f32) -> glm::Vec3 {
+        let height = self.view_height_at(focus_distance);
+        let projection = match projection {
+            Projection::Orthographic { .. } => Projection::Orthographic { height },
+            other => other,
+        };
+
+        let forward = glm::normalize(self.params.look);
+        if self.params.projection.is_orthographic() && !projection.is_orthographic() {
+            let focus = self.params.eye + forward * focus_distance;
+            let distance = height / (2.0 * (self.params.fov / 2.0).tan());
+            self.set_position(focus - forward * distance);
+        }
+
+        let (old_x, old_y) = self.params.projection.lens_shift();
+        let (new_x, new_y) = projection.lens_shift();
+        let right = glm::normalize(glm::cross(forward, self.params.up));
+        let up = glm::cross(right, forward);
+        let shift = right * ((old_x - new_x) * height * self.params.ratio)
+            + up * ((old_y - new_y) * height);
+        self.translate(shift);
+        self.set_projection(projection);
+        shift
+    }
+
+    /// Height in world units of the visible region on a plane `distance` in front of the camera.
+    pub fn view_height_at(&self, distance: f32) -> f32 {
+        match self.params.projection {
+            Projection::Orthographic { height } => height,
+            _ => 2.0 * distance * (self.params.fov / 2.0).tan(),
+        }
+    }
+
+    /// Fraction of the view's height covered by a sphere enclosing `volume`, e.g. for choosing a
+    /// level of detail. Under a perspective projection, volumes around or behind the eye cover
+    /// the whole view or more.
+    pub fn screen_size(&self, volume: &BoundingVolume) -> f32 {
+        let distance = glm::dot(volume.center() - self.params.eye, self.forward());
+        let radius = volume.radius();
+        match self.params.projection {
+            Projection::Orthographic { .. } => {}
+            _ if distance <= radius => return f32::INFINITY,
+            _ => {}
+        }
+        2.0 * radius / self.view_height_at(distance)
+    }
+
+    /// Scale the visible height of an orthographic projection by `factor`. This is how an
+    /// orthographic camera zooms, since moving it closer does not change what it sees. Has no
+    /// effect on other projections.
+    pub fn scale_view_size(&mut self, factor: f32) {
+        if let Projection::Orthographic { height } = self.params.projection {
+            self.set_projection(Projection::Orthographic {
+                height: height * factor,
+            });
+        }
+    }
+
+    /// Normalised device z-coordinate of the farthest visible depth; -1 under reverse-Z,
+    /// otherwise 1.
+    pub fn far_plane_ndc(&self) -> f32 {
+        self.params.projection.far_ndc()
+    }
+
+    /// Move the camera back or forth along its view direction, without turning it, so that
+    /// `volume` just fits in view with a small margin, and returns the distance from the eye to
+    /// the volume's centre.
+    ///
+    /// Perspective projections are fitted using the field-of-view (ignoring any off-axis shift),
+    /// while an orthographic projection is resized to fit. The near and far clip planes are also
+    /// moved to enclose the volume comfortably, so very small or large models are not clipped.
+    pub fn frame_bounds(&mut self, volume: &BoundingVolume) -> f32 {
+        const MARGIN: f32 = 1.1;
+
+        let forward = glm::normalize(self.params.look);
+        let right = glm::normalize(glm::cross(forward, self.params.up));
+        let up = glm::cross(right, forward);
+        let center = volume.center();
+        let radius = volume.radius().max(f32::EPSILON);
+
+        let tan_y = (self.params.fov / 2.0).tan();
+        let tan_x = tan_y * self.params.ratio;
+        let distance = if let Projection::Orthographic { .. } = self.params.projection {
+            let (half_x, half_y) = match *volume {
+                BoundingVolume::Sphere { radius, .. } => (radius, radius),
+                BoundingVolume::Box(ref aabb) => {
+                    aabb.corners().iter().fold((0.0f32, 0.0f32), |(x, y), c| {
+                        let offset = *c - center;
+                        (
+                            x.max(glm::dot(offset, right).abs()),
+                            y.max(glm::dot(offset, up).abs()),
+                        )
+                    })
+                }
+            };
+            let height = 2.0 * MARGIN * half_y.max(half_x / self.params.ratio);
+            self.params.projection = Projection::Orthographic { height };
+            2.0 * radius
+        } else {
+            match *volume {
+                BoundingVolume::Sphere { radius, .. } => {
+                    MARGIN * radius / tan_y.min(tan_x).atan().sin()
+                }
+                // each corner needs |x| <= (d + z) tan_x and |y| <= (d + z) tan_y, and staying
+                // a radius away keeps the whole box in front of the camera
+                BoundingVolume::Box(ref aabb) => aabb.corners().iter().fold(radius, |d, c| {
+                    let offset = *c - center;
+                    let z = glm::dot(offset, forward);
+                    let x = MARGIN * glm::dot(offset, right).abs();
+                    let y = MARGIN * glm::dot(offset, up).abs();
+                    d.max(x / tan_x - z).max(y / tan_y - z)
+                }),
+            }
+        };
+
+        self.params.near = ((distance - radius) * 0.5).max(radius * 1e-3);
+        self.params.far = (distance + radius) * 10.0;
+        self.perspective = Camera::create_perspective(&self.params);
+        self.set_position(center - forward * distance);
+        distance
+    }
+
+    /// World-space ray from the camera through pixel `(x, y)` of a `viewport` of the given size,
+    /// with the origin in the top-left corner as reported by mouse events.
+    ///
+    /// The ray starts on the near plane, so for an orthographic camera it starts in front of the
+    /// pixel rather than at the eye. Returns `None` if the camera transform is not invertible,
+    /// such as for a degenerate projection.
+    pub fn unproject(&self, x: i32, y: i32, viewport: (i32, i32)) -> Option<Ray> {
+        let ndc_x = 2.0 * (x as f32 + 0.5) / viewport.0.max(1) as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * (y as f32 + 0.5) / viewport.1.max(1) as f32;
+
+        let inverse = (self.perspective * self.view).inverse()?;
+        let unproject = |z: f32| {
+            let p = inverse * glm::vec4(ndc_x, ndc_y, z, 1.0);
+            p.truncate(3) / p.w
+        };
+        // the mid-depth point is finite even for an infinite far plane
+        let near = unproject(-self.far_plane_ndc());
+        let mid = unproject(0.0);
+        Some(Ray::new(near, mid - near))
+    }
+
     /// Translate the camera along the given vector in world space. This will rebuild the camera's
     /// view transform.
     pub fn translate(&mut self, v: glm::Vec3) {
-        self.params.eye = self.params.eye + v;
-        self.view = Camera::create_view(&self.params);
+        let eye = self.params.eye + v;
+        self.set_position(eye);
     }
 
-    /// Zoom by translating along the camera's `look` vector. This will rebuild the camera's view
-    /// transform.
+    /// Zoom by translating `delta` units along the camera's `look` vector. This will rebuild the
+    /// camera's view transform. An orthographic camera also shrinks its view by the same
+    /// proportion.
     pub fn zoom(&mut self, delta: f32) {
         let v = self.params.look * delta;
         self.translate(v);
+        self.scale_view_size((1.0 - delta).max(0.01));
     }
 
-    /// Rotate the camera around a fixed axis (in world-space), while looking at the origin.
+    /// Rotate the camera around a fixed axis (in world-space) through the origin, turning it with
+    /// the rotation so that a camera looking at the origin keeps looking at it.
     ///
     /// * `angle`: in radians
     /// * `axis`: in world-space
     pub fn orbit(&mut self, angle: f32, axis: &glm::Vec3) {
-        let rotate = glm::ext::rotate(&num::one(), angle, *axis);
-        let eye = {
-            let eye = &self.params.eye;
-            (rotate * glm::vec4(eye.x, eye.y, eye.z, 1.0)).truncate(3)
-        };
-        let up = {
-            let up = &self.params.up;
-            (rotate * glm::vec4(up.x, up.y, up.z, 0.0)).truncate(3)
-        };
-        self.params.eye = eye;
-        self.params.up = up;
-        self.params.look = -eye;
-        self.view = Camera::create_view(&self.params);
+        let rotation = Quat::from_axis_angle(*axis, angle);
+        self.params.eye = rotation.rotate(self.params.eye);
+        self.rotate(rotation);
     }
 }
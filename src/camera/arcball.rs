@@ -0,0 +1,221 @@
+use glm;
+
+use crate::camera::Camera;
+use crate::util::Quat;
+
+/// What a mouse drag does to an `ArcballController`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DragMode {
+    /// Rotate the camera around the target.
+    Rotate,
+    /// Slide the camera and target across the view plane.
+    Pan,
+}
+
+/// How rotation drags are interpreted by an `ArcballController`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum OrbitStyle {
+    /// Free rotation: the drag rolls a virtual trackball centred on the target.
+    Arcball,
+    /// Horizontal drags spin around the world y-axis and vertical drags tilt, keeping the
+    /// horizon level.
+    Turntable,
+}
+
+struct Drag {
+    mode: DragMode,
+    last: (i32, i32),
+    viewport: (i32, i32),
+}
+
+/// Model-viewer camera controller which orbits, pans and dollies around a target point.
+///
+/// The camera always looks at `target`. After every operation the camera's up vector is
+/// re-orthonormalised against the view direction, so it does not drift over time.
+pub struct ArcballController {
+    target: glm::Vec3,
+    drag: Option<Drag>,
+    pub style: OrbitStyle,
+    /// Closest the camera may dolly to the target.
+    pub min_distance: f32,
+    /// Furthest the camera may dolly from the target.
+    pub max_distance: f32,
+    /// Radians turned per pixel dragged, for turntable rotation.
+    pub sensitivity: f32,
+}
+
+impl ArcballController {
+    /// Elevation is kept just short of the poles so that the view never aligns with world up.
+    const MAX_ELEVATION: f32 = 1.55;
+
+    /// Construct a controller orbiting `target`, and aim `camera` at it.
+    pub fn new(camera: &mut Camera, target: glm::Vec3) -> ArcballController {
+        let controller = ArcballController {
+            target,
+            drag: None,
+            style: OrbitStyle::Turntable,
+            min_distance: 0.1,
+            max_distance: 40.0,
+            sensitivity: 0.01,
+        };
+        let eye = camera.params().eye;
+        let up = camera.params().up;
+        controller.apply(camera, eye - target, up);
+        controller
+    }
+
+    pub fn target(&self) -> glm::Vec3 {
+        self.target
+    }
+
+    /// Distance from the camera to the target.
+    pub fn distance(&self, camera: &Camera) -> f32 {
+        glm::length(camera.params().eye - self.target)
+    }
+
+    /// Start dragging from cursor position `(x, y)` within a viewport of `viewport` pixels.
+    pub fn begin_drag(&mut self, mode: DragMode, x: i32, y: i32, viewport: (i32, i32)) {
+        self.drag = Some(Drag {
+            mode,
+            last: (x, y),
+            viewport,
+        });
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Continue the current drag to cursor position `(x, y)`. Does nothing if not dragging.
+    pub fn drag_to(&mut self, camera: &mut Camera, x: i32, y: i32) {
+        let (mode, last, viewport) = match self.drag {
+            Some(ref mut drag) => {
+                let last = drag.last;
+                drag.last = (x, y);
+                (drag.mode, last, drag.viewport)
+            }
+            None => return,
+        };
+        let (dx, dy) = (x - last.0, y - last.1);
+        if dx == 0 && dy == 0 {
+            return;
+        }
+
+        match (mode, self.style) {
+            (DragMode::Rotate, OrbitStyle::Turntable) => {
+                let angle = self.sensitivity;
+                self.orbit_by(camera, -dx as f32 * angle, dy as f32 * angle);
+            }
+            (DragMode::Rotate, OrbitStyle::Arcball) => {
+                let from = ArcballController::sphere_point(last, viewport);
+                let to = ArcballController::sphere_point((x, y), viewport);
+                self.roll_trackball(camera, from, to);
+            }
+            (DragMode::Pan, _) => self.pan(camera, dx, dy, viewport),
+        }
+    }
+
+    /// Orbit the target by changing the camera's azimuth (around world y) and elevation, both
+    /// in radians.
+    pub fn orbit_by(&self, camera: &mut Camera, azimuth: f32, elevation: f32) {
+        let offset = camera.params().eye - self.target;
+        let distance = glm::length(offset);
+        let dir = offset / distance;
+
+        let theta = dir.x.atan2(dir.z) + azimuth;
+        let phi = (dir.y.clamp(-1.0, 1.0).asin() + elevation).clamp(
+            -ArcballController::MAX_ELEVATION,
+            ArcballController::MAX_ELEVATION,
+        );
+        let offset =
+            glm::vec3(phi.cos() * theta.sin(), phi.sin(), phi.cos() * theta.cos()) * distance;
+        self.apply(camera, offset, glm::vec3(0.0, 1.0, 0.0));
+    }
+
+    /// Move towards (`factor < 1`) or away from (`factor > 1`) the target, keeping within
+    /// `min_distance` and `max_distance`. An orthographic view is scaled to match.
+    pub fn dolly(&self, camera: &mut Camera, factor: f32) {
+        let offset = camera.params().eye - self.target;
+        let distance = glm::length(offset);
+        let new_distance = (distance * factor).clamp(self.min_distance, self.max_distance);
+        let up = camera.params().up;
+        self.apply(camera, offset * (new_distance / distance), up);
+        camera.scale_view_size(new_distance / distance);
+    }
+
+    /// Translate camera and target together so that the scene follows a drag of `(dx, dy)`
+    /// pixels.
+    pub fn pan(&mut self, camera: &mut Camera, dx: i32, dy: i32, viewport: (i32, i32)) {
+        let (eye, look, up) = {
+            let params = camera.params();
+            (params.eye, params.look, params.up)
+        };
+        let forward = glm::normalize(look);
+        let right = glm::normalize(glm::cross(forward, up));
+        let up = glm::cross(right, forward);
+
+        // size of a pixel on the plane through the target
+        let distance = glm::length(eye - self.target);
+        let view_height = camera.view_height_at(distance);
+        let pixel = view_height / viewport.1.max(1) as f32;
+
+        let shift = (right * -dx as f32 + up * dy as f32) * pixel;
+        self.target = self.target + shift;
+        self.apply(camera, eye + shift - self.target, up);
+    }
+
+    /// Rotate the camera around the target so that the trackball point under `from` moves to
+    /// `to`. Both are points on the unit trackball in camera space.
+    fn roll_trackball(&self, camera: &mut Camera, from: glm::Vec3, to: glm::Vec3) {
+        let axis = glm::cross(from, to);
+        if glm::length(axis) < 1e-6 {
+            return;
+        }
+        let angle = glm::dot(from, to).clamp(-1.0, 1.0).acos();
+
+        let (eye, look, up) = {
+            let params = camera.params();
+            (params.eye, params.look, params.up)
+        };
+        let back = -glm::normalize(look);
+        let right = glm::normalize(glm::cross(up, back));
+        let up = glm::cross(back, right);
+        let world_axis = right * axis.x + up * axis.y + back * axis.z;
+
+        // the scene appears to rotate with the drag, so the camera rotates the other way
+        let rotation = Quat::from_axis_angle(world_axis, -angle);
+        self.apply(
+            camera,
+            rotation.rotate(eye - self.target),
+            rotation.rotate(up),
+        );
+    }
+
+    /// Project a cursor position onto Shoemake's virtual trackball, a unit sphere filling the
+    /// smaller viewport dimension.
+    fn sphere_point(cursor: (i32, i32), viewport: (i32, i32)) -> glm::Vec3 {
+        let (w, h) = (viewport.0 as f32, viewport.1 as f32);
+        let radius = (w.min(h) / 2.0).max(1.0);
+        let x = (cursor.0 as f32 - w / 2.0) / radius;
+        let y = (h / 2.0 - cursor.1 as f32) / radius;
+        let d2 = x * x + y * y;
+        if d2 <= 1.0 {
+            glm::vec3(x, y, (1.0 - d2).sqrt())
+        } else {
+            glm::normalize(glm::vec3(x, y, 0.0))
+        }
+    }
+
+    /// Place the camera at `target + offset`, looking at the target, with `up` made orthogonal
+    /// to the view direction.
+    fn apply(&self, camera: &mut Camera, offset: glm::Vec3, up: glm::Vec3) {
+        let forward = -glm::normalize(offset);
+        let right = glm::normalize(glm::cross(forward, up));
+        let up = glm::cross(right, forward);
+        camera.set_view(self.target + offset, forward, up);
+    }
+}
@@ -0,0 +1,190 @@
+use glm;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::camera::{Camera, Projection};
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to read or write bookmarks")]
+    Io {
+        #[cause]
+        inner: io::Error,
+    },
+    #[fail(display = "Malformed bookmark on line {}", line)]
+    Parse { line: usize },
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Self {
+        Error::Io { inner: other }
+    }
+}
+
+/// Saved camera placement.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Bookmark {
+    pub eye: glm::Vec3,
+    pub look: glm::Vec3,
+    pub up: glm::Vec3,
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub projection: Projection,
+    /// Distance in front of the camera of the point being looked at, e.g. an orbit target.
+    pub focus: f32,
+}
+
+impl Bookmark {
+    /// Record where `camera` is, looking at a point `focus` units in front of it.
+    pub fn from_camera(camera: &Camera, focus: f32) -> Bookmark {
+        let params = camera.params();
+        Bookmark {
+            eye: params.eye,
+            look: params.look,
+            up: params.up,
+            fov: params.fov,
+            near: params.near,
+            far: params.far,
+            projection: params.projection,
+            focus,
+        }
+    }
+
+    /// Point being looked at when the bookmark was made.
+    pub fn target(&self) -> glm::Vec3 {
+        self.eye + glm::normalize(self.look) * self.focus
+    }
+
+    /// Move `camera` back to the bookmarked placement, clip planes and projection.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.set_fov(self.fov);
+        camera.set_clip_planes(self.near, self.far);
+        camera.set_projection(self.projection);
+        camera.set_view(self.eye, self.look, self.up);
+    }
+
+    /// Format as whitespace-separated fields, see `Bookmarks::save`.
+    fn format_fields(&self) -> String {
+        let v = |v: glm::Vec3| format!("{} {} {}", v.x, v.y, v.z);
+        let projection = match self.projection {
+            Projection::Perspective => "perspective".to_string(),
+            Projection::Orthographic { height } => format!("orthographic {}", height),
+            Projection::OffAxis { shift_x, shift_y } => {
+                format!("off_axis {} {}", shift_x, shift_y)
+            }
+            Projection::InfinitePerspective { reverse_z } => format!("infinite {}", reverse_z),
+        };
+        format!(
+            "{} {} {} {} {} {} {} {}",
+            v(self.eye),
+            v(self.look),
+            v(self.up),
+            self.fov,
+            self.focus,
+            self.near,
+            self.far,
+            projection
+        )
+    }
+
+    /// Parse fields formatted by `format_fields`.
+    fn from_fields(fields: &str) -> Option<Bookmark> {
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let f = |i: usize| fields.get(i)?.parse::<f32>().ok();
+        let v = |i: usize| Some(glm::vec3(f(i)?, f(i + 1)?, f(i + 2)?));
+
+        let projection = match *fields.get(13)? {
+            "perspective" => Projection::Perspective,
+            "orthographic" => Projection::Orthographic { height: f(14)? },
+            "off_axis" => Projection::OffAxis {
+                shift_x: f(14)?,
+                shift_y: f(15)?,
+            },
+            "infinite" => Projection::InfinitePerspective {
+                reverse_z: fields.get(14)?.parse().ok()?,
+            },
+            _ => return None,
+        };
+        Some(Bookmark {
+            eye: v(0)?,
+            look: v(3)?,
+            up: v(6)?,
+            fov: f(9)?,
+            near: f(11)?,
+            far: f(12)?,
+            focus: f(10)?,
+            projection,
+        })
+    }
+}
+
+/// Named collection of camera `Bookmark`s which can be saved to and loaded from a text file.
+#[derive(Default)]
+pub struct Bookmarks {
+    marks: BTreeMap<String, Bookmark>,
+}
+
+impl Bookmarks {
+    pub fn new() -> Bookmarks {
+        Bookmarks::default()
+    }
+
+    /// Load bookmarks saved with `Bookmarks::save`. A file which does not exist yet gives no
+    /// bookmarks rather than an error.
+    pub fn load(path: &Path) -> Result<Bookmarks, Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Bookmarks::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut bookmarks = Bookmarks::new();
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut parts = line.splitn(2, '\t');
+            let name = parts.next().unwrap_or("");
+            let bookmark = parts
+                .next()
+                .and_then(Bookmark::from_fields)
+                .ok_or(Error::Parse { line: i + 1 })?;
+            bookmarks.insert(name, bookmark);
+        }
+        Ok(bookmarks)
+    }
+
+    /// Write all bookmarks to `path`, one per line: the name, a tab, then the eye, look and up
+    /// vectors, field-of-view, focus distance, near and far clip planes, and projection.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents: String = self
+            .marks
+            .iter()
+            .map(|(name, bookmark)| format!("{}\t{}\n", name, bookmark.format_fields()))
+            .collect();
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Add a bookmark, replacing any other of the same name. Names may not contain tabs or line
+    /// breaks, which are replaced by spaces.
+    pub fn insert(&mut self, name: &str, bookmark: Bookmark) {
+        let name = name.replace(['\t', '\n', '\r'], " ");
+        self.marks.insert(name, bookmark);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bookmark> {
+        self.marks.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Bookmark> {
+        self.marks.remove(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.marks.keys()
+    }
+}
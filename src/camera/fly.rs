@@ -0,0 +1,116 @@
+use glm;
+
+use crate::camera::Camera;
+
+/// Directions a `FlyController` can move in, relative to where the camera is looking.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Movement {
+    Forward,
+    Back,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// First-person free-fly camera controller.
+///
+/// Orientation is tracked as yaw (about the world y-axis) and pitch (elevation), driven by
+/// relative mouse motion. Movement keys accelerate the camera smoothly towards `speed` in the
+/// corresponding direction, and it glides to a stop once they are released.
+///
+/// The controller only describes *how* the camera should move; call `update` once per frame to
+/// apply it to a `Camera`.
+pub struct FlyController {
+    yaw: f32,
+    pitch: f32,
+    velocity: glm::Vec3,
+    moving: [bool; 6],
+    /// Top speed in world units per second.
+    pub speed: f32,
+    /// How quickly the camera reaches `speed`, or comes to rest. Higher is snappier.
+    pub acceleration: f32,
+    /// Radians turned per pixel of mouse motion.
+    pub sensitivity: f32,
+}
+
+impl FlyController {
+    /// Pitch is kept just short of straight up/down so that the view never aligns with `up`.
+    const MAX_PITCH: f32 = 1.55;
+    const MIN_SPEED: f32 = 0.05;
+    const MAX_SPEED: f32 = 100.0;
+
+    /// Construct a controller which starts out looking wherever `camera` is looking.
+    pub fn from_camera(camera: &Camera) -> FlyController {
+        let look = glm::normalize(camera.params().look);
+        FlyController {
+            yaw: look.z.atan2(look.x),
+            pitch: look.y.clamp(-1.0, 1.0).asin(),
+            velocity: glm::to_vec3(0.0),
+            moving: [false; 6],
+            speed: 1.0,
+            acceleration: 8.0,
+            sensitivity: 0.003,
+        }
+    }
+
+    /// Unit vector in the direction the controller is looking.
+    pub fn forward(&self) -> glm::Vec3 {
+        glm::vec3(
+            self.pitch.cos() * self.yaw.cos(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.sin(),
+        )
+    }
+
+    /// Start or stop moving in a direction, e.g. on key press and release.
+    pub fn set_moving(&mut self, movement: Movement, moving: bool) {
+        self.moving[movement as usize] = moving;
+    }
+
+    /// Turn by a relative mouse motion, in pixels.
+    pub fn look(&mut self, dx: i32, dy: i32) {
+        self.yaw += dx as f32 * self.sensitivity;
+        self.pitch = (self.pitch - dy as f32 * self.sensitivity)
+            .clamp(-FlyController::MAX_PITCH, FlyController::MAX_PITCH);
+    }
+
+    /// Multiply the top speed by `factor`, within sensible limits.
+    pub fn scale_speed(&mut self, factor: f32) {
+        self.speed =
+            (self.speed * factor).clamp(FlyController::MIN_SPEED, FlyController::MAX_SPEED);
+    }
+
+    /// Advance the controller by `dt` seconds and move `camera` accordingly.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) {
+        let world_up = glm::vec3(0.0, 1.0, 0.0);
+        let forward = self.forward();
+        let right = glm::normalize(glm::cross(forward, world_up));
+
+        let directions = [
+            (Movement::Forward, forward),
+            (Movement::Back, -forward),
+            (Movement::Left, -right),
+            (Movement::Right, right),
+            (Movement::Up, world_up),
+            (Movement::Down, -world_up),
+        ];
+        let heading = directions
+            .iter()
+            .filter(|(m, _)| self.moving[*m as usize])
+            .fold(glm::to_vec3(0.0), |sum, (_, v)| sum + *v);
+
+        let target = if glm::length(heading) > 0.0 {
+            glm::normalize(heading) * self.speed
+        } else {
+            glm::to_vec3(0.0)
+        };
+
+        // exponential approach to the target velocity, independent of frame rate
+        let blend = 1.0 - (-self.acceleration * dt).exp();
+        self.velocity = self.velocity + (target - self.velocity) * blend;
+
+        let eye = camera.params().eye + self.velocity * dt;
+        camera.set_view(eye, forward, world_up);
+    }
+}
@@ -0,0 +1,400 @@
+use glm;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::animation::{Interpolate, Interpolation, Track};
+use crate::camera::Camera;
+use crate::util::Quat;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to read or write camera path")]
+    Io {
+        #[cause]
+        inner: io::Error,
+    },
+    #[fail(display = "Malformed camera path on line {}", line)]
+    Parse { line: usize },
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Self {
+        Error::Io { inner: other }
+    }
+}
+
+/// Curve followed by the camera's position through the keys of a `CameraPath`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PathCurve {
+    /// Catmull-Rom spline passing through every key.
+    CatmullRom,
+    /// A single Bezier curve using the keys as control points. It starts and ends at the first
+    /// and last keys and is pulled towards, but does not pass through, the others.
+    Bezier,
+}
+
+/// Control point of a `CameraPath`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PathKey {
+    pub eye: glm::Vec3,
+    pub orientation: Quat,
+    /// Field of view to pass through at this key, if it should change along the path.
+    pub fov: Option<f32>,
+}
+
+impl PathKey {
+    /// Record where `camera` is and where it is looking, including its field of view.
+    pub fn from_camera(camera: &Camera) -> PathKey {
+        PathKey {
+            eye: camera.position(),
+            orientation: camera.orientation(),
+            fov: Some(camera.params().fov),
+        }
+    }
+
+    /// Format as whitespace-separated fields, see `CameraPath::save`.
+    fn format_fields(&self) -> String {
+        let q = &self.orientation;
+        let fov = self.fov.map_or("-".to_string(), |fov| fov.to_string());
+        format!(
+            "{} {} {} {} {} {} {} {}",
+            self.eye.x, self.eye.y, self.eye.z, q.w, q.x, q.y, q.z, fov
+        )
+    }
+
+    fn from_fields(fields: &str) -> Option<PathKey> {
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        if fields.len() != 8 {
+            return None;
+        }
+        let f = |i: usize| fields[i].parse::<f32>().ok();
+        let fov = match fields[7] {
+            "-" => None,
+            fov => Some(fov.parse().ok()?),
+        };
+        Some(PathKey {
+            eye: glm::vec3(f(0)?, f(1)?, f(2)?),
+            orientation: Quat::new(f(3)?, f(4)?, f(5)?, f(6)?).normalize(),
+            fov,
+        })
+    }
+}
+
+/// Camera fly-through built from `PathKey`s.
+///
+/// Positions follow a `PathCurve`, orientations are interpolated between keys by slerp and the
+/// field of view linearly between the keys which set one. Sampling is parameterised by distance
+/// travelled along the curve, so the camera moves at a constant speed however the keys are
+/// spaced. Turning on the spot between keys at the same position is therefore instant, except
+/// in a path which does not move at all, which spends equal time on each key instead.
+pub struct CameraPath {
+    keys: Vec<PathKey>,
+    curve: PathCurve,
+    positions: Track<glm::Vec3>,
+    orientations: Track<Quat>,
+    fovs: Track<f32>,
+    /// Parameter of each key along the tracks.
+    knots: Vec<f32>,
+    /// Parameters sampled along the curve, and the distance along it at each one.
+    arc_params: Vec<f32>,
+    arc_lengths: Vec<f32>,
+}
+
+impl CameraPath {
+    /// Arc length samples taken between consecutive keys.
+    const SAMPLES_PER_SEGMENT: usize = 32;
+    /// Smallest parameter step between consecutive Catmull-Rom keys, so that keys at the same
+    /// position stay in order.
+    const MIN_KNOT_SPACING: f32 = 1e-3;
+
+    /// Construct an empty path.
+    pub fn new(curve: PathCurve) -> CameraPath {
+        CameraPath {
+            keys: Vec::new(),
+            curve,
+            positions: Track::new(Interpolation::Cubic),
+            orientations: Track::new(Interpolation::Linear),
+            fovs: Track::new(Interpolation::Linear),
+            knots: Vec::new(),
+            arc_params: Vec::new(),
+            arc_lengths: Vec::new(),
+        }
+    }
+
+    pub fn keys(&self) -> &[PathKey] {
+        &self.keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn curve(&self) -> PathCurve {
+        self.curve
+    }
+
+    pub fn set_curve(&mut self, curve: PathCurve) {
+        self.curve = curve;
+        self.rebuild();
+    }
+
+    /// Append a key to the end of the path.
+    pub fn push(&mut self, key: PathKey) {
+        self.keys.push(key);
+        self.rebuild();
+    }
+
+    /// Remove the last key, if any.
+    pub fn pop(&mut self) -> Option<PathKey> {
+        let key = self.keys.pop();
+        self.rebuild();
+        key
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.rebuild();
+    }
+
+    /// Length of the curve followed by the camera's position.
+    pub fn length(&self) -> f32 {
+        self.arc_lengths.last().cloned().unwrap_or(0.0)
+    }
+
+    /// Camera placement `progress` of the way along the path, from 0 at the first key to 1 at
+    /// the last. Returns `None` for an empty path.
+    pub fn sample(&self, progress: f32) -> Option<PathKey> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let u = self.key_param(progress.clamp(0.0, 1.0));
+        Some(PathKey {
+            eye: self.position(u),
+            orientation: self.orientations.sample(u)?,
+            fov: self.fovs.sample(u),
+        })
+    }
+
+    /// Move `camera` to its placement `progress` of the way along the path.
+    pub fn apply(&self, camera: &mut Camera, progress: f32) {
+        if let Some(key) = self.sample(progress) {
+            if let Some(fov) = key.fov {
+                camera.set_fov(fov);
+            }
+            camera.set_position(key.eye);
+            camera.set_orientation(key.orientation);
+        }
+    }
+
+    /// Load a path saved with `CameraPath::save`. A file which does not exist yet gives an empty
+    /// Catmull-Rom path rather than an error.
+    pub fn load(path: &Path) -> Result<CameraPath, Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(CameraPath::new(PathCurve::CatmullRom))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let curve = match lines.next().map(|(_, line)| line.trim()) {
+            Some("catmull_rom") | None => PathCurve::CatmullRom,
+            Some("bezier") => PathCurve::Bezier,
+            Some(_) => return Err(Error::Parse { line: 1 }),
+        };
+        let mut camera_path = CameraPath::new(curve);
+        for (i, line) in lines {
+            let key = PathKey::from_fields(line).ok_or(Error::Parse { line: i + 1 })?;
+            camera_path.keys.push(key);
+        }
+        camera_path.rebuild();
+        Ok(camera_path)
+    }
+
+    /// Write the path to `path`: the curve (`catmull_rom` or `bezier`) on the first line, then
+    /// one key per line as the eye position, orientation quaternion (w, x, y, z) and field of
+    /// view, or `-` for keys which leave it alone.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let curve = match self.curve {
+            PathCurve::CatmullRom => "catmull_rom",
+            PathCurve::Bezier => "bezier",
+        };
+        let keys: String = self
+            .keys
+            .iter()
+            .map(|key| format!("{}\n", key.format_fields()))
+            .collect();
+        fs::write(path, format!("{}\n{}", curve, keys))?;
+        Ok(())
+    }
+
+    /// Rebuild the interpolation tracks and arc length table after the keys have changed.
+    ///
+    /// Catmull-Rom keys are spaced by the square root of the distance between them (the
+    /// centripetal parameterisation), which keeps the curve from overshooting into loops and
+    /// cusps where keys are unevenly spaced. Bezier keys are spaced evenly.
+    fn rebuild(&mut self) {
+        let keys = &self.keys;
+        let mut knot = 0.0;
+        self.knots = Vec::with_capacity(keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            if i > 0 {
+                knot += match self.curve {
+                    PathCurve::CatmullRom => glm::length(key.eye - keys[i - 1].eye)
+                        .sqrt()
+                        .max(CameraPath::MIN_KNOT_SPACING),
+                    PathCurve::Bezier => 1.0,
+                };
+            }
+            self.knots.push(knot);
+        }
+
+        let knots = &self.knots;
+        self.positions = keys
+            .iter()
+            .zip(knots)
+            .fold(Track::new(Interpolation::Cubic), |track, (key, &u)| {
+                track.key(u, key.eye)
+            });
+        self.orientations = keys
+            .iter()
+            .zip(knots)
+            .fold(Track::new(Interpolation::Linear), |track, (key, &u)| {
+                track.key(u, key.orientation)
+            });
+        self.fovs = keys
+            .iter()
+            .zip(knots)
+            .filter_map(|(key, &u)| key.fov.map(|fov| (u, fov)))
+            .fold(Track::new(Interpolation::Linear), |track, (u, fov)| {
+                track.key(u, fov)
+            });
+
+        self.arc_params = Vec::new();
+        self.arc_lengths = Vec::new();
+        if keys.is_empty() {
+            return;
+        }
+        self.arc_params.push(0.0);
+        for segment in self.knots.windows(2) {
+            let (u0, u1) = (segment[0], segment[1]);
+            for i in 1..=CameraPath::SAMPLES_PER_SEGMENT {
+                let t = i as f32 / CameraPath::SAMPLES_PER_SEGMENT as f32;
+                self.arc_params.push(u0 + (u1 - u0) * t);
+            }
+        }
+        let mut length = 0.0;
+        let mut last = self.position(0.0);
+        for &u in &self.arc_params {
+            let p = self.position(u);
+            length += glm::length(p - last);
+            last = p;
+            self.arc_lengths.push(length);
+        }
+    }
+
+    /// Position at parameter `u` along the tracks.
+    fn position(&self, u: f32) -> glm::Vec3 {
+        match self.curve {
+            PathCurve::CatmullRom => self.positions.sample(u).unwrap_or(glm::to_vec3(0.0)),
+            PathCurve::Bezier => {
+                let end = self.knots.last().cloned().unwrap_or(0.0).max(1.0);
+                let points: Vec<glm::Vec3> = self.keys.iter().map(|k| k.eye).collect();
+                de_casteljau(points, u / end)
+            }
+        }
+    }
+
+    /// Track parameter a fraction `progress` of the way along the curve's length.
+    fn key_param(&self, progress: f32) -> f32 {
+        let steps = self.arc_lengths.len().saturating_sub(1);
+        let length = self.length();
+        if steps == 0 || length <= f32::EPSILON {
+            return progress * self.knots.last().cloned().unwrap_or(0.0);
+        }
+
+        let distance = progress * length;
+        let i = self
+            .arc_lengths
+            .partition_point(|&l| l < distance)
+            .clamp(1, steps);
+        let (l0, l1) = (self.arc_lengths[i - 1], self.arc_lengths[i]);
+        let t = if l1 - l0 > f32::EPSILON {
+            (distance - l0) / (l1 - l0)
+        } else {
+            0.0
+        };
+        let (u0, u1) = (self.arc_params[i - 1], self.arc_params[i]);
+        u0 + (u1 - u0) * t
+    }
+}
+
+/// Point `t` of the way along the Bezier curve with control points `points`.
+fn de_casteljau<T: Interpolate>(mut points: Vec<T>, t: f32) -> T {
+    while points.len() > 1 {
+        points = points
+            .windows(2)
+            .map(|w| T::lerp(&w[0], &w[1], t))
+            .collect();
+    }
+    points[0]
+}
+
+/// Plays a `CameraPath` back through a `Camera` at a constant speed.
+pub struct PathPlayer {
+    time: f32,
+    /// Seconds taken to travel the whole path.
+    pub duration: f32,
+    /// Whether to start over after reaching the end.
+    pub looping: bool,
+}
+
+impl PathPlayer {
+    pub fn new(duration: f32) -> PathPlayer {
+        PathPlayer {
+            time: 0.0,
+            duration,
+            looping: false,
+        }
+    }
+
+    /// Player which travels `path` at `speed` world units per second. A path which does not move
+    /// takes a second to turn from each key to the next.
+    pub fn with_speed(path: &CameraPath, speed: f32) -> PathPlayer {
+        let duration = path.length() / speed.max(f32::EPSILON);
+        if duration > 0.0 {
+            PathPlayer::new(duration)
+        } else {
+            PathPlayer::new(path.keys().len().saturating_sub(1) as f32)
+        }
+    }
+
+    /// Fraction of the path travelled so far.
+    pub fn progress(&self) -> f32 {
+        if self.duration > 0.0 {
+            (self.time / self.duration).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time >= self.duration
+    }
+
+    /// Advance playback by `dt` seconds and move `camera` along `path` accordingly. Returns
+    /// `true` once the end of a non-looping path has been reached.
+    pub fn update(&mut self, path: &CameraPath, camera: &mut Camera, dt: f32) -> bool {
+        self.time += dt;
+        if self.looping && self.duration > 0.0 {
+            self.time = self.time.rem_euclid(self.duration);
+        }
+        path.apply(camera, self.progress());
+        self.is_finished()
+    }
+}
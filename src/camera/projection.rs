@@ -0,0 +1,154 @@
+use gl;
+use glm;
+
+use crate::camera::CameraBuilder;
+
+/// Projection transforms supported by `Camera`.
+///
+/// All perspective variants take their vertical field-of-view and aspect ratio from the camera's
+/// parameters.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Projection {
+    /// Symmetric perspective frustum.
+    Perspective,
+    /// Orthographic projection showing `height` world units vertically. The visible width follows
+    /// the aspect ratio.
+    Orthographic { height: f32 },
+    /// Asymmetric perspective frustum, shifted sideways by `shift_x` view widths and vertically
+    /// by `shift_y` view heights, like the lens shift of a view camera. Useful for tiled or
+    /// stereo rendering, and for keeping verticals parallel in architectural views.
+    OffAxis { shift_x: f32, shift_y: f32 },
+    /// Perspective frustum with the far plane at infinity, so that nothing is ever far-clipped.
+    ///
+    /// With `reverse_z`, depth runs from 1 at the near plane to 0 at infinity, so distant
+    /// geometry lands where floating-point depth is most precise. (The full benefit needs a
+    /// `[0, 1]` clip-space depth range, which core GL 4.1 does not offer.) Reverse-Z requires a
+    /// `GEQUAL` depth test and clearing depth to 0; see `depth_func` and `clear_depth`.
+    InfinitePerspective { reverse_z: bool },
+}
+
+impl Projection {
+    /// Build the projection matrix for the given camera parameters.
+    pub fn matrix(&self, params: &CameraBuilder) -> glm::Mat4 {
+        let (near, far, ratio) = (params.near, params.far, params.ratio);
+        let top = near * (params.fov / 2.0).tan();
+        let right = top * ratio;
+
+        match *self {
+            Projection::Perspective => {
+                glm::ext::perspective(params.fov, params.ratio, params.near, params.far)
+            }
+            Projection::Orthographic { height } => {
+                let (t, r) = (height / 2.0, height * ratio / 2.0);
+                glm::Mat4::new(
+                    glm::vec4(1.0 / r, 0.0, 0.0, 0.0),
+                    glm::vec4(0.0, 1.0 / t, 0.0, 0.0),
+                    glm::vec4(0.0, 0.0, -2.0 / (far - near), 0.0),
+                    glm::vec4(0.0, 0.0, -(far + near) / (far - near), 1.0),
+                )
+            }
+            Projection::OffAxis { shift_x, shift_y } => {
+                let dx = shift_x * 2.0 * right;
+                let dy = shift_y * 2.0 * top;
+                frustum(-right + dx, right + dx, -top + dy, top + dy, near, far)
+            }
+            Projection::InfinitePerspective { reverse_z } => {
+                // z_ndc = 1 - 2n/d, or 2n/d - 1 reversed, for a point at distance d
+                let (z, w) = if reverse_z {
+                    (1.0, 2.0 * near)
+                } else {
+                    (-1.0, -2.0 * near)
+                };
+                glm::Mat4::new(
+                    glm::vec4(near / right, 0.0, 0.0, 0.0),
+                    glm::vec4(0.0, near / top, 0.0, 0.0),
+                    glm::vec4(0.0, 0.0, z, -1.0),
+                    glm::vec4(0.0, 0.0, w, 0.0),
+                )
+            }
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        matches!(self, Projection::Orthographic { .. })
+    }
+
+    /// Lens shift of an off-axis projection, in view widths and heights, or zero for the
+    /// others.
+    pub fn lens_shift(&self) -> (f32, f32) {
+        match *self {
+            Projection::OffAxis { shift_x, shift_y } => (shift_x, shift_y),
+            _ => (0.0, 0.0),
+        }
+    }
+
+    /// The next kind of projection after this one, cycling through perspective, off-axis
+    /// (shifted up by a quarter of the view), infinite perspective, infinite perspective with
+    /// reverse-Z, and orthographic. The orthographic height is left for
+    /// `Camera::switch_projection` to choose.
+    pub fn next_kind(&self) -> Projection {
+        match *self {
+            Projection::Perspective => Projection::OffAxis {
+                shift_x: 0.0,
+                shift_y: 0.25,
+            },
+            Projection::OffAxis { .. } => Projection::InfinitePerspective { reverse_z: false },
+            Projection::InfinitePerspective { reverse_z: false } => {
+                Projection::InfinitePerspective { reverse_z: true }
+            }
+            Projection::InfinitePerspective { reverse_z: true } => {
+                Projection::Orthographic { height: 1.0 }
+            }
+            Projection::Orthographic { .. } => Projection::Perspective,
+        }
+    }
+
+    pub fn is_reverse_z(&self) -> bool {
+        match self {
+            Projection::InfinitePerspective { reverse_z } => *reverse_z,
+            _ => false,
+        }
+    }
+
+    /// Depth comparison function (for `glDepthFunc`) that should be used with this projection.
+    pub fn depth_func(&self) -> gl::types::GLenum {
+        if self.is_reverse_z() {
+            gl::GEQUAL
+        } else {
+            gl::LEQUAL
+        }
+    }
+
+    /// Value the depth buffer should be cleared to (for `glClearDepth`) with this projection.
+    pub fn clear_depth(&self) -> f64 {
+        if self.is_reverse_z() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Normalised device z-coordinate of the farthest possible depth.
+    pub fn far_ndc(&self) -> f32 {
+        if self.is_reverse_z() {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// General perspective frustum given its extents on the near plane, as `glFrustum`.
+fn frustum(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> glm::Mat4 {
+    glm::Mat4::new(
+        glm::vec4(2.0 * near / (right - left), 0.0, 0.0, 0.0),
+        glm::vec4(0.0, 2.0 * near / (top - bottom), 0.0, 0.0),
+        glm::vec4(
+            (right + left) / (right - left),
+            (top + bottom) / (top - bottom),
+            -(far + near) / (far - near),
+            -1.0,
+        ),
+        glm::vec4(0.0, 0.0, -2.0 * far * near / (far - near), 0.0),
+    )
+}
@@ -0,0 +1,64 @@
+use glm;
+
+use crate::camera::Camera;
+use crate::util::Quat;
+
+/// Smooth move of a `Camera` from where it is to a new position and orientation.
+///
+/// The position is interpolated linearly and the orientation by spherical linear interpolation,
+/// both eased in and out over `duration` seconds. Call `update` once per frame until it reports
+/// that the transition has finished.
+pub struct CameraTransition {
+    from_eye: glm::Vec3,
+    from_orientation: Quat,
+    to_eye: glm::Vec3,
+    to_orientation: Quat,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl CameraTransition {
+    /// Transition from `camera`'s current view to `eye` and `orientation`.
+    pub fn new(camera: &Camera, eye: glm::Vec3, orientation: Quat, duration: f32) -> Self {
+        CameraTransition {
+            from_eye: camera.position(),
+            from_orientation: camera.orientation(),
+            to_eye: eye,
+            to_orientation: orientation,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Transition from `camera`'s current view to looking along `look` from `eye`.
+    pub fn look(
+        camera: &Camera,
+        eye: glm::Vec3,
+        look: glm::Vec3,
+        up: glm::Vec3,
+        duration: f32,
+    ) -> Self {
+        CameraTransition::new(camera, eye, Quat::look_rotation(look, up), duration)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Advance the transition by `dt` seconds and move `camera` accordingly. Returns `true`
+    /// once the camera has arrived.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) -> bool {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        let t = if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        };
+        // smoothstep: start and stop gently
+        let t = t * t * (3.0 - 2.0 * t);
+
+        camera.set_position(self.from_eye + (self.to_eye - self.from_eye) * t);
+        camera.set_orientation(self.from_orientation.slerp(&self.to_orientation, t));
+        self.is_finished()
+    }
+}
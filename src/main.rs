@@ -1,8 +1,10 @@
 #[macro_use]
 extern crate failure;
 
+pub mod animation;
 pub mod camera;
 pub mod mesh;
+pub mod picking;
 pub mod rendergl;
 pub mod resources;
 pub mod shape;
@@ -22,7 +24,15 @@ fn run() -> Result<(), failure::Error> {
     let mut view = ui::View::new("App", SCREEN_WIDTH, SCREEN_HEIGHT).map_err(err_msg)?;
     let mut scene = ui::Scene::new("assets/")?;
     scene.on_resize(SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32)?;
+    scene.set_click_handler(|pick| match pick {
+        Some(pick) => println!(
+            "Picked object {} (model {}, triangle {}) at {:?}",
+            pick.object, pick.hit.model, pick.hit.triangle, pick.hit.position
+        ),
+        None => println!("Picked nothing"),
+    });
 
+    let mut last_frame = std::time::Instant::now();
     'main: loop {
         for event in view.poll_events() {
             match event {
@@ -38,9 +48,31 @@ fn run() -> Result<(), failure::Error> {
                     scene.on_resize(x, y)?;
                 }
                 Event::KeyDown {
+                    keycode: Some(key),
+                    keymod,
+                    ..
+                } => {
+                    scene.on_keydown(&key, keymod)?;
+                }
+                Event::KeyUp {
                     keycode: Some(key), ..
                 } => {
-                    scene.on_keydown(&key)?;
+                    scene.on_keyup(&key)?;
+                }
+                Event::MouseMotion {
+                    x, y, xrel, yrel, ..
+                } => {
+                    scene.on_mouse_motion(x, y, xrel, yrel)?;
+                }
+                Event::MouseButtonDown {
+                    mouse_btn, x, y, ..
+                } => {
+                    scene.on_mouse_down(mouse_btn, x, y)?;
+                }
+                Event::MouseButtonUp {
+                    mouse_btn, x, y, ..
+                } => {
+                    scene.on_mouse_up(mouse_btn, x, y)?;
                 }
                 Event::MouseWheel { y, direction, .. } => {
                     let dir = match direction {
@@ -53,11 +85,13 @@ fn run() -> Result<(), failure::Error> {
             }
         }
 
-        unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-        }
+        view.set_relative_mouse_mode(scene.captures_mouse());
+
+        scene.clear();
 
-        scene.tick();
+        let now = std::time::Instant::now();
+        scene.tick(now.duration_since(last_frame).as_secs_f32());
+        last_frame = now;
         scene.render()?;
         view.gl_swap_window();
 
@@ -1,38 +1,177 @@
 //! 3D mesh implementation.
 use std::cmp::{max, min};
 use std::path::Path;
+use std::rc::Rc;
 
+use crate::animation::Pose;
 use crate::camera::Camera;
-use crate::rendergl::{types, VertexN};
-use crate::resources::ResourceLoader;
-use crate::shape::{DrawError, Drawable, InitError, ShapeGL};
-use crate::util;
+use crate::picking::{Bvh, Hit, Ray};
+use crate::rendergl::texture::Texture;
+use crate::rendergl::types::{self, ShaderAttrib};
+use crate::rendergl::{Program, VertexN, VertexNC8, VertexNTC};
+use crate::resources::{self, ResourceLoader};
+use crate::shape::{
+    strip_to_triangles, DrawError, Drawable, InitError, LodGroup, MeshData, Shading, ShapeGL,
+};
+use crate::util::{self, Aabb};
 use glm;
+use glm::GenSquareMat;
 use num;
 use tobj::Material;
 
+mod gltf;
 mod model;
-pub use model::{MaterialShader, SceneModel};
+mod points;
+pub use self::gltf::{
+    AlphaMode, GltfCamera, GltfLight, GltfMaterial, GltfNode, GltfPrimitive, GltfScene,
+    GltfTexture, LightKind, TextureRef,
+};
+pub use self::model::{MaterialShader, SceneModel};
+pub use self::points::PointCloud;
 
 /// Implements `Drawable` to render a 3D mesh.
 pub struct MeshObject {
     shader: MaterialShader,
     models: Vec<SceneModel>,
     materials: Vec<Material>,
+    /// Diffuse texture of each material, if any material has one.
+    textures: Vec<Option<Rc<Texture>>>,
+    /// Whether the models' vertex colours modulate their materials.
+    vertex_colors: bool,
     transform: glm::Mat4,
+    pose: Pose,
 }
 
 impl MeshObject {
+    /// Load an OBJ model and its materials. Models without normals are given smooth ones if
+    /// the shader reads normals. Vertex colours (`v x y z r g b`), if the file has any,
+    /// modulate the materials.
     pub fn from_obj(
         loader: &ResourceLoader,
         objfile: &str,
         program_name: &str,
     ) -> Result<MeshObject, InitError> {
-        let (models, materials) = loader.load_obj(Path::new(objfile))?;
+        let resources::ObjFile {
+            mut models,
+            materials,
+            colors,
+            ..
+        } = loader.load_obj_with_colors(Path::new(objfile))?;
+        let shader = MaterialShader::from_res(loader, program_name)?;
+        if shader.program.uses_attrib(ShaderAttrib::NORMAL) {
+            for model in models.iter_mut().filter(|m| m.mesh.normals.is_empty()) {
+                let mut data = MeshData::from(&model.mesh);
+                data.compute_normals(Shading::default());
+                model.mesh = tobj::Mesh {
+                    material_id: model.mesh.material_id,
+                    ..tobj::Mesh::from(&data)
+                };
+            }
+        }
+        if colors.is_empty() {
+            let models: Vec<SceneModel> = models.iter().map(|model| model.into()).collect();
+            return Ok(MeshObject::new(shader, models, materials));
+        }
+
+        let models = models
+            .iter()
+            .map(|model| {
+                let mut data = MeshData::from(&model.mesh);
+                colors.apply(&mut data);
+                let bvh = Bvh::new(
+                    data.positions.clone(),
+                    &data.indices,
+                    types::GlLayout::Triangles,
+                );
+                let shapegl = ShapeGL::from_data::<VertexNTC>(&data);
+                SceneModel::new(shapegl, model.mesh.material_id).with_bvh(bvh)
+            })
+            .collect();
+        let mut mesh = MeshObject::new(shader, models, materials);
+        mesh.vertex_colors = true;
+        Ok(mesh)
+    }
+
+    /// Load a PLY mesh, drawing it with its vertex colours if it has them. Meshes without
+    /// normals are given smooth ones if the shader reads normals.
+    ///
+    /// PLY files without faces cannot be loaded as meshes; load them as a `PointCloud`.
+    pub fn from_ply(
+        loader: &ResourceLoader,
+        plyfile: &str,
+        program_name: &str,
+    ) -> Result<MeshObject, InitError> {
+        let mut data = loader.load_ply(Path::new(plyfile))?;
+        if data.is_point_cloud() {
+            let message = format!("{} has no faces; load it as a PointCloud", plyfile);
+            return Err(resources::Error::PlyLoadError(message).into());
+        }
         let shader = MaterialShader::from_res(loader, program_name)?;
-        let models: Vec<SceneModel> = models.iter().map(|model| model.into()).collect();
+        if data.normals.is_empty() && shader.program.uses_attrib(ShaderAttrib::NORMAL) {
+            data.compute_normals(Shading::default());
+        }
+        Ok(MeshObject::from_mesh_data(shader, &data))
+    }
 
-        Ok(MeshObject::new(shader, models, materials))
+    /// Load an STL mesh, drawing it with its facet colours if it has them.
+    pub fn from_stl(
+        loader: &ResourceLoader,
+        stlfile: &str,
+        program_name: &str,
+    ) -> Result<MeshObject, InitError> {
+        let data = loader.load_stl(Path::new(stlfile))?;
+        let shader = MaterialShader::from_res(loader, program_name)?;
+        Ok(MeshObject::from_mesh_data(shader, &data))
+    }
+
+    /// Simplify `data` into a chain of up to `levels` levels of detail and upload each as a
+    /// `MeshObject` drawn with `program_name`, in a `LodGroup`.
+    ///
+    /// Each level has a quarter of the triangles of the one before, and is drawn below half the
+    /// screen size of the one before, starting from half the view's height. The levels share
+    /// one program.
+    pub fn lod_group(
+        loader: &ResourceLoader,
+        data: &MeshData,
+        program_name: &str,
+        levels: usize,
+    ) -> Result<LodGroup, InitError> {
+        let mut chain = data.lod_chain(levels, 0.25).into_iter();
+        let shader = MaterialShader::from_res(loader, program_name)?;
+        let base = chain.next().unwrap_or_default();
+        let base = MeshObject::from_mesh_data(shader.clone(), &base);
+        let mut group = LodGroup::new(Box::new(base));
+        let mut screen_size = 0.5;
+        for level in chain {
+            let mesh = MeshObject::from_mesh_data(shader.clone(), &level);
+            group = group.with_level(Box::new(mesh), screen_size);
+            screen_size /= 2.0;
+        }
+        Ok(group)
+    }
+
+    /// Upload mesh data, such as from a PLY or STL file, as a single pickable model.
+    fn from_mesh_data(shader: MaterialShader, data: &MeshData) -> MeshObject {
+        let bvh = Bvh::new(
+            data.positions.clone(),
+            &data.indices,
+            types::GlLayout::Triangles,
+        );
+        let model = SceneModel::new(upload_scan(data), None).with_bvh(bvh);
+        if data.colors.is_empty() {
+            return MeshObject::new(shader, vec![model], Vec::new());
+        }
+
+        // vertex colours are shown as they are, rather than tinted by the default grey
+        let mut white = Material::empty();
+        white.diffuse = [1.0; 3];
+        let model = SceneModel {
+            material_id: Some(0),
+            ..model
+        };
+        let mut mesh = MeshObject::new(shader, vec![model], vec![white]);
+        mesh.vertex_colors = true;
+        mesh
     }
 
     pub fn new(
@@ -49,9 +188,37 @@ impl MeshObject {
             shader,
             models,
             materials,
+            textures: Vec::new(),
+            vertex_colors: false,
             transform: num::one(),
+            pose: Pose::default(),
         }
     }
+
+    /// Give materials a diffuse texture, indexed by material ID, which modulates their diffuse
+    /// colour. The shader is then given `useTexture` and a `diffuseTexture` sampler.
+    pub fn with_textures(mut self, textures: Vec<Option<Rc<Texture>>>) -> MeshObject {
+        self.textures = textures;
+        self
+    }
+
+    /// Bind the diffuse texture of material `id`, if the mesh has textures at all.
+    fn apply_texture(&self, id: usize) -> Result<(), DrawError> {
+        if self.textures.is_empty() {
+            return Ok(());
+        }
+        let program = &self.shader.program;
+        match self.textures.get(id).and_then(|t| t.as_ref()) {
+            Some(texture) => {
+                Texture::active_texture(0);
+                texture.bind();
+                program.set_uniform("diffuseTexture", &0)?;
+                program.set_uniform("useTexture", &1)?;
+            }
+            None => program.set_uniform("useTexture", &0)?,
+        }
+        Ok(())
+    }
 }
 
 impl Drawable for MeshObject {
@@ -63,17 +230,72 @@ impl Drawable for MeshObject {
         self.shader
             .program
             .set_uniform("perspective", &camera.perspective)?;
-        self.shader.program.set_uniform("model", &self.transform)?;
+        if self.vertex_colors {
+            self.shader.program.set_uniform("useVertexColor", &1)?;
+        }
         for model in &self.models {
             let id = match model.material_id {
                 Some(id) => id,
                 None => self.materials.len() - 1,
             };
+            self.shader
+                .program
+                .set_uniform("model", &(self.transform * model.transform))?;
             self.shader.apply_material(&self.materials[id])?;
-            model.shapegl.draw_vertices();
+            self.apply_texture(id)?;
+            // animated uniforms take precedence over material values
+            self.pose.apply_uniforms(&self.shader.program)?;
+            model.shapegl.draw_vertices(&self.shader.program)?;
         }
         Ok(())
     }
+
+    fn apply_pose(&mut self, pose: &Pose) {
+        if let Some(transform) = pose.transform {
+            self.transform = transform;
+        }
+        self.pose = pose.clone();
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.models.iter().fold(Aabb::empty(), |aabb, model| {
+            aabb.union(&model.shapegl.bounds().transform(&model.transform))
+        });
+        if bounds.is_empty() {
+            None
+        } else {
+            Some(bounds.transform(&self.transform))
+        }
+    }
+
+    fn draw_ids(&self, program: &Program) -> Result<(), DrawError> {
+        for (i, model) in self.models.iter().enumerate() {
+            program.set_uniform("model", &(self.transform * model.transform))?;
+            program.set_uniform("submesh", &(i as u32))?;
+            model.shapegl.draw_vertices(program)?;
+        }
+        Ok(())
+    }
+
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        let (model, hit) = self
+            .models
+            .iter()
+            .enumerate()
+            .filter_map(|(i, model)| {
+                let inverse = (self.transform * model.transform).inverse()?;
+                // distances along the model-space ray are the same as along the world-space one
+                let local = ray.transform(&inverse);
+                model.bvh.as_ref()?.ray_cast(&local).map(|hit| (i, hit))
+            })
+            .min_by(|a, b| a.1.distance.total_cmp(&b.1.distance))?;
+
+        Some(Hit {
+            model,
+            position: ray.at(hit.distance),
+            ..hit
+        })
+    }
 }
 
 /// 3D point data for a mesh. Consumes itself to construct a `MeshObject`.
@@ -110,44 +332,69 @@ impl DepthMesh {
     /// Constructs vertex data out of this `DepthMesh`'s 3D point cloud and returns a `MeshObject`
     /// for rendering with OpenGL.
     pub fn build_shape(&self, loader: &ResourceLoader) -> Result<MeshObject, InitError> {
-        let shapegl = self.init_buffers();
+        let data = self.vertex_data();
+        let strip = self.strip_indices();
+        let vertex_data: Vec<VertexN> = data
+            .positions
+            .iter()
+            .zip(&data.normals)
+            .map(|(&v, &n)| (v, n).into())
+            .collect();
+        let shapegl = ShapeGL::new(&vertex_data, &strip, types::GlLayout::TriangleStrip);
+        let bvh = Bvh::new(data.positions, &strip, types::GlLayout::TriangleStrip);
         let shader = MaterialShader::from_res(loader, "shaders/mesh")?;
         Ok(MeshObject::new(
             shader,
-            vec![SceneModel::new(shapegl, None)],
+            vec![SceneModel::new(shapegl, None).with_bvh(bvh)],
             Vec::new(),
         ))
     }
 
-    fn push_indices(&self, index_data: &mut Vec<u32>, p1: (i32, i32), p2: (i32, i32)) {
-        let ix1 = util::linear_index(p1.0 as usize, p1.1 as usize, self.num_cols) as u32;
-        let ix2 = util::linear_index(p2.0 as usize, p2.1 as usize, self.num_cols) as u32;
-        index_data.push(ix1);
-        index_data.push(ix2);
+    /// Triangulate the mesh on the CPU, with a vertex and normal per depth sample, e.g. to export
+    /// it. The mesh is drawn as a triangle strip, which this unrolls into a list of triangles.
+    pub fn mesh_data(&self) -> MeshData {
+        // degenerate triangles joining the rows are left out
+        MeshData {
+            indices: strip_to_triangles(&self.strip_indices()),
+            ..self.vertex_data()
+        }
     }
 
-    fn init_buffers(&self) -> ShapeGL {
-        let mut vertex_data: Vec<VertexN> = Vec::with_capacity(self.mesh_data.len());
-        let mut index_data: Vec<u32> = Vec::new();
+    /// A vertex and normal per depth sample, without indices.
+    fn vertex_data(&self) -> MeshData {
+        let mut data = MeshData::default();
 
         let num_rows = self.num_rows as i32;
         let num_cols = self.num_cols as i32;
         for i in 0..num_rows {
             for j in 0..num_cols {
-                let v = self.get_position(i, j);
-                let n = self.get_normal(i, j);
-                vertex_data.push((*v, n).into());
+                data.positions.push(*self.get_position(i, j));
+                data.normals.push(self.get_normal(i, j));
             }
         }
 
+        data
+    }
+
+    /// Indices of a triangle strip over the samples, with rows joined by degenerate triangles.
+    fn strip_indices(&self) -> Vec<u32> {
+        let num_rows = self.num_rows as i32;
+        let num_cols = self.num_cols as i32;
+        let mut strip: Vec<u32> = Vec::new();
         for i in 0..(num_rows - 1) {
             for j in (0..num_cols).rev() {
-                self.push_indices(&mut index_data, (i, j), (i + 1, j));
+                self.push_indices(&mut strip, (i, j), (i + 1, j));
             }
-            self.push_indices(&mut index_data, (i + 1, 0), (i + 1, num_cols - 1));
+            self.push_indices(&mut strip, (i + 1, 0), (i + 1, num_cols - 1));
         }
+        strip
+    }
 
-        ShapeGL::new(&vertex_data, &index_data, types::GlLayout::TriangleStrip)
+    fn push_indices(&self, index_data: &mut Vec<u32>, p1: (i32, i32), p2: (i32, i32)) {
+        let ix1 = util::linear_index(p1.0 as usize, p1.1 as usize, self.num_cols) as u32;
+        let ix2 = util::linear_index(p2.0 as usize, p2.1 as usize, self.num_cols) as u32;
+        index_data.push(ix1);
+        index_data.push(ix2);
     }
 
     fn get_position(&self, row: i32, col: i32) -> &glm::Vec3 {
@@ -183,3 +430,13 @@ impl DepthMesh {
         glm::normalize(n)
     }
 }
+
+/// Upload scanned vertex data, such as from a PLY or STL file, with its colours if it has any.
+fn upload_scan(data: &MeshData) -> ShapeGL {
+    if data.colors.is_empty() {
+        ShapeGL::from_data::<VertexN>(data)
+    } else {
+        // scans rarely have more than 8 bits per channel, so colours are stored as bytes
+        ShapeGL::from_data::<VertexNC8>(data)
+    }
+}
@@ -0,0 +1,646 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use glm;
+use gltf;
+use gltf::khr_lights_punctual::Kind;
+use gltf::mesh::Mode;
+use gltf::texture::{MagFilter, WrappingMode};
+use image::{self, DynamicImage};
+use num;
+use tobj;
+
+use crate::camera::{CameraBuilder, Projection};
+use crate::picking::Bvh;
+use crate::rendergl::texture::{FilterMethod, Texture, TextureParameters, WrapMethod};
+use crate::rendergl::types::{GlLayout, TextureParam};
+use crate::rendergl::VertexNT;
+use crate::resources::{self, ResourceLoader};
+use crate::shape::{InitError, ShapeGL};
+
+use super::{MaterialShader, MeshObject, SceneModel};
+
+/// A glTF 2.0 model decoded into CPU-side data: the node hierarchy of its default scene, with
+/// the mesh primitives, cameras and lights attached to it, and the materials and textures the
+/// primitives use.
+///
+/// Use `mesh_object` to upload it for rendering, or `MeshObject::from_gltf` to load and upload
+/// in one go.
+pub struct GltfScene {
+    /// Every node of the document, in document order.
+    pub nodes: Vec<GltfNode>,
+    /// Nodes at the root of the scene.
+    pub roots: Vec<usize>,
+    /// Primitives of every mesh instance in the scene.
+    pub primitives: Vec<GltfPrimitive>,
+    pub materials: Vec<GltfMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub images: Vec<DynamicImage>,
+    pub cameras: Vec<GltfCamera>,
+    pub lights: Vec<GltfLight>,
+}
+
+/// A node of the scene hierarchy.
+pub struct GltfNode {
+    pub name: Option<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Transform relative to the parent node.
+    pub transform: glm::Mat4,
+    /// Transform relative to the scene root. Identity for nodes outside the scene.
+    pub world: glm::Mat4,
+}
+
+/// Vertex attributes and triangles of one primitive of a mesh, in the space of its node.
+///
+/// Attributes which the primitive does not have are empty. Strips and fans are unrolled into a
+/// triangle list. Primitives without normals are given flat normals, as the glTF spec requires,
+/// which splits each of their triangles into vertices of its own.
+pub struct GltfPrimitive {
+    /// Node instancing the primitive's mesh.
+    pub node: usize,
+    pub positions: Vec<glm::Vec3>,
+    pub normals: Vec<glm::Vec3>,
+    /// Tangents, with the handedness of the bitangent in `w`.
+    pub tangents: Vec<glm::Vec4>,
+    /// Texture coordinates, one list per set (`TEXCOORD_0`, `TEXCOORD_1`, ...).
+    pub texcoords: Vec<Vec<glm::Vec2>>,
+    /// Vertex colours of set 0, as RGBA.
+    pub colors: Vec<glm::Vec4>,
+    /// Skinning joints and weights of set 0.
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<glm::Vec4>,
+    pub indices: Vec<u32>,
+    pub material_id: Option<usize>,
+}
+
+/// How a material's alpha is interpreted.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fragments with alpha below `cutoff` are discarded; the rest are opaque.
+    Mask {
+        cutoff: f32,
+    },
+    Blend,
+}
+
+/// A material's use of one of the scene's textures.
+#[derive(Copy, Clone, Debug)]
+pub struct TextureRef {
+    /// Index into `GltfScene::textures`.
+    pub texture: usize,
+    /// Texture coordinate set to sample with.
+    pub texcoord: u32,
+}
+
+/// A metallic-roughness PBR material.
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color: glm::Vec4,
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive: glm::Vec3,
+    pub emissive_texture: Option<TextureRef>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+/// An image together with the sampler parameters to apply to it.
+pub struct GltfTexture {
+    /// Index into `GltfScene::images`.
+    pub image: usize,
+    pub filter: FilterMethod,
+    pub wrap_s: WrapMethod,
+    pub wrap_t: WrapMethod,
+}
+
+/// A camera placed in the scene. Use `builder` to construct a `Camera` viewing through it.
+pub struct GltfCamera {
+    pub name: Option<String>,
+    pub node: usize,
+    pub eye: glm::Vec3,
+    pub look: glm::Vec3,
+    pub up: glm::Vec3,
+    pub projection: Projection,
+    /// Vertical field-of-view in radians, for perspective cameras.
+    pub fov: Option<f32>,
+    pub aspect_ratio: Option<f32>,
+    pub near: f32,
+    /// Far clip distance; `None` for an infinite perspective projection.
+    pub far: Option<f32>,
+}
+
+/// Type of a `GltfLight`, from `KHR_lights_punctual`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LightKind {
+    Directional,
+    Point,
+    /// Spot light, with the angles in radians from its direction at which the cone starts and
+    /// finishes falling off.
+    Spot {
+        inner_cone: f32,
+        outer_cone: f32,
+    },
+}
+
+/// A punctual light placed in the scene.
+pub struct GltfLight {
+    pub name: Option<String>,
+    pub node: usize,
+    pub kind: LightKind,
+    pub color: glm::Vec3,
+    /// Intensity in candela for point and spot lights, or lux for directional lights.
+    pub intensity: f32,
+    /// Distance at which the light's attenuation reaches zero, or `None` for no cutoff.
+    pub range: Option<f32>,
+    pub position: glm::Vec3,
+    pub direction: glm::Vec3,
+}
+
+impl GltfScene {
+    /// Load and decode a `.gltf` or `.glb` file through `loader`.
+    pub fn load(loader: &ResourceLoader, file: &Path) -> Result<GltfScene, resources::Error> {
+        let (document, buffers, images) = loader.load_gltf(file)?;
+
+        let mut nodes: Vec<GltfNode> = document
+            .nodes()
+            .map(|node| GltfNode {
+                name: node.name().map(String::from),
+                parent: None,
+                children: node.children().map(|child| child.index()).collect(),
+                transform: to_mat4(node.transform().matrix()),
+                world: num::one(),
+            })
+            .collect();
+        for i in 0..nodes.len() {
+            for child in nodes[i].children.clone() {
+                nodes[child].parent = Some(i);
+            }
+        }
+
+        let mut scene = GltfScene {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            primitives: Vec::new(),
+            materials: document.materials().map(GltfMaterial::new).collect(),
+            textures: document.textures().map(GltfTexture::new).collect(),
+            images: images.into_iter().map(to_image).collect(),
+            cameras: Vec::new(),
+            lights: Vec::new(),
+        };
+
+        let root = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(root) => root,
+            None => {
+                scene.nodes = nodes;
+                return Ok(scene);
+            }
+        };
+        scene.roots = root.nodes().map(|node| node.index()).collect();
+
+        // visit the hierarchy depth-first, parents before children
+        let mut stack: Vec<(gltf::Node, glm::Mat4)> =
+            root.nodes().map(|node| (node, num::one())).collect();
+        while let Some((node, parent)) = stack.pop() {
+            let i = node.index();
+            let world = parent * nodes[i].transform;
+            nodes[i].world = world;
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    if let Some(p) = GltfPrimitive::new(&primitive, i, &buffers) {
+                        scene.primitives.push(p);
+                    }
+                }
+            }
+            if let Some(camera) = node.camera() {
+                scene.cameras.push(GltfCamera::new(&camera, i, &world));
+            }
+            if let Some(light) = node.light() {
+                scene.lights.push(GltfLight::new(&light, i, &world));
+            }
+            stack.extend(node.children().map(|child| (child, world)));
+        }
+        scene.nodes = nodes;
+        Ok(scene)
+    }
+
+    /// Upload the scene's primitives and textures, to draw with `shader`.
+    ///
+    /// Each primitive becomes a pickable `SceneModel` transformed by its node. Materials are
+    /// drawn with their base colour, and base colour texture if they have one.
+    pub fn mesh_object(&self, shader: MaterialShader) -> MeshObject {
+        let models = self
+            .primitives
+            .iter()
+            .map(|p| p.scene_model().with_transform(self.nodes[p.node].world))
+            .collect();
+        let materials = self.materials.iter().map(|m| m.into()).collect();
+
+        let mut uploaded: Vec<Option<Rc<Texture>>> = vec![None; self.textures.len()];
+        let textures = self
+            .materials
+            .iter()
+            .map(|material| {
+                let i = material.base_color_texture?.texture;
+                let texture = self.textures.get(i)?;
+                let image = self.images.get(texture.image)?;
+                Some(
+                    uploaded[i]
+                        .get_or_insert_with(|| Rc::new(texture.upload(image)))
+                        .clone(),
+                )
+            })
+            .collect();
+
+        MeshObject::new(shader, models, materials).with_textures(textures)
+    }
+}
+
+impl MeshObject {
+    /// Load a glTF 2.0 model (`.gltf` or `.glb`) to draw with the program `program_name`.
+    ///
+    /// Cameras and lights in the model are not used; load a `GltfScene` to get at them.
+    pub fn from_gltf(
+        loader: &ResourceLoader,
+        file: &str,
+        program_name: &str,
+    ) -> Result<MeshObject, InitError> {
+        let scene = GltfScene::load(loader, Path::new(file))?;
+        let shader = MaterialShader::from_res(loader, program_name)?;
+        Ok(scene.mesh_object(shader))
+    }
+}
+
+impl GltfPrimitive {
+    /// Read the attributes of `primitive`. Returns `None` for primitives without positions, and
+    /// for points and lines, which are not drawn.
+    fn new(
+        primitive: &gltf::Primitive,
+        node: usize,
+        buffers: &[gltf::buffer::Data],
+    ) -> Option<GltfPrimitive> {
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|b| &b[..]));
+        let positions: Vec<glm::Vec3> = reader.read_positions()?.map(to_vec3).collect();
+
+        let texcoords = (0..)
+            .map(|set| reader.read_tex_coords(set))
+            .take_while(Option::is_some)
+            .map(|t| {
+                t.unwrap()
+                    .into_f32()
+                    .map(|[u, v]| glm::vec2(u, v))
+                    .collect()
+            })
+            .collect();
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        let indices = match primitive.mode() {
+            Mode::Triangles => indices,
+            Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+                .flat_map(|i| {
+                    // every other triangle in a strip is flipped to keep a consistent winding
+                    let odd = i % 2;
+                    vec![indices[i], indices[i + 1 + odd], indices[i + 2 - odd]]
+                })
+                .collect(),
+            Mode::TriangleFan => (0..indices.len().saturating_sub(2))
+                .flat_map(|i| vec![indices[i + 1], indices[i + 2], indices[0]])
+                .collect(),
+            _ => return None,
+        };
+
+        let mut p = GltfPrimitive {
+            node,
+            positions,
+            normals: read_all(reader.read_normals(), to_vec3),
+            tangents: read_all(reader.read_tangents(), to_vec4),
+            texcoords,
+            colors: read_all(reader.read_colors(0).map(|c| c.into_rgba_f32()), to_vec4),
+            joints: read_all(reader.read_joints(0).map(|j| j.into_u16()), |j| j),
+            weights: read_all(reader.read_weights(0).map(|w| w.into_f32()), to_vec4),
+            indices,
+            material_id: primitive.material().index(),
+        };
+        if p.normals.is_empty() {
+            p.flatten();
+        }
+        Some(p)
+    }
+
+    /// Give every triangle vertices of its own, with the triangle's normal.
+    fn flatten(&mut self) {
+        fn split<T: Copy>(attribute: &mut Vec<T>, indices: &[u32]) {
+            if !attribute.is_empty() {
+                *attribute = indices.iter().map(|&i| attribute[i as usize]).collect();
+            }
+        }
+        let indices = std::mem::take(&mut self.indices);
+        split(&mut self.positions, &indices);
+        split(&mut self.tangents, &indices);
+        for texcoords in &mut self.texcoords {
+            split(texcoords, &indices);
+        }
+        split(&mut self.colors, &indices);
+        split(&mut self.joints, &indices);
+        split(&mut self.weights, &indices);
+
+        self.normals = self
+            .positions
+            .chunks(3)
+            .flat_map(|t| {
+                let n = glm::cross(t[1] - t[0], t[2] - t[0]);
+                let n = if glm::length(n) > 0.0 {
+                    glm::normalize(n)
+                } else {
+                    n
+                };
+                vec![n; t.len()]
+            })
+            .collect();
+        self.indices = (0..self.positions.len() as u32).collect();
+    }
+
+    /// Upload the primitive as `VertexNT` data, using texture coordinate set 0.
+    pub fn shapegl(&self) -> ShapeGL {
+        let texcoords = self.texcoords.first();
+        let vertex_data: Vec<VertexNT> = (0..self.positions.len())
+            .map(|i| {
+                let uv = texcoords
+                    .and_then(|t| t.get(i).cloned())
+                    .unwrap_or_else(|| glm::vec2(0.0, 0.0));
+                VertexNT::new(self.positions[i], self.normals[i], uv)
+            })
+            .collect();
+        ShapeGL::new(&vertex_data, &self.indices, GlLayout::Triangles)
+    }
+
+    /// Upload the primitive as a pickable `SceneModel`.
+    pub fn scene_model(&self) -> SceneModel {
+        let bvh = Bvh::new(self.positions.clone(), &self.indices, GlLayout::Triangles);
+        SceneModel::new(self.shapegl(), self.material_id).with_bvh(bvh)
+    }
+}
+
+impl GltfMaterial {
+    fn new(material: gltf::Material) -> GltfMaterial {
+        let texture_ref = |info: Option<gltf::texture::Info>| {
+            info.map(|info| TextureRef {
+                texture: info.texture().index(),
+                texcoord: info.tex_coord(),
+            })
+        };
+        let pbr = material.pbr_metallic_roughness();
+        let normal = material.normal_texture();
+        let occlusion = material.occlusion_texture();
+        GltfMaterial {
+            name: material.name().map(String::from),
+            base_color: to_vec4(pbr.base_color_factor()),
+            base_color_texture: texture_ref(pbr.base_color_texture()),
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            metallic_roughness_texture: texture_ref(pbr.metallic_roughness_texture()),
+            normal_texture: normal.as_ref().map(|t| TextureRef {
+                texture: t.texture().index(),
+                texcoord: t.tex_coord(),
+            }),
+            normal_scale: normal.as_ref().map_or(1.0, |t| t.scale()),
+            occlusion_texture: occlusion.as_ref().map(|t| TextureRef {
+                texture: t.texture().index(),
+                texcoord: t.tex_coord(),
+            }),
+            occlusion_strength: occlusion.as_ref().map_or(1.0, |t| t.strength()),
+            emissive: to_vec3(material.emissive_factor()),
+            emissive_texture: texture_ref(material.emissive_texture()),
+            alpha_mode: match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                gltf::material::AlphaMode::Mask => AlphaMode::Mask {
+                    cutoff: material.alpha_cutoff().unwrap_or(0.5),
+                },
+                gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+            },
+            double_sided: material.double_sided(),
+        }
+    }
+}
+
+impl From<&GltfMaterial> for tobj::Material {
+    fn from(other: &GltfMaterial) -> Self {
+        let mut material = tobj::Material::empty();
+        material.name = other.name.clone().unwrap_or_default();
+        material.diffuse = [other.base_color.x, other.base_color.y, other.base_color.z];
+        material.dissolve = other.base_color.w;
+        material
+    }
+}
+
+impl GltfTexture {
+    fn new(texture: gltf::Texture) -> GltfTexture {
+        let sampler = texture.sampler();
+        let wrap = |mode| match mode {
+            WrappingMode::ClampToEdge => WrapMethod::ClampToEdge,
+            WrappingMode::MirroredRepeat => WrapMethod::MirroredRepeat,
+            WrappingMode::Repeat => WrapMethod::Repeat,
+        };
+        GltfTexture {
+            image: texture.source().index(),
+            filter: match sampler.mag_filter() {
+                Some(MagFilter::Nearest) => FilterMethod::Nearest,
+                _ => FilterMethod::Linear,
+            },
+            wrap_s: wrap(sampler.wrap_s()),
+            wrap_t: wrap(sampler.wrap_t()),
+        }
+    }
+
+    /// Upload `image` with this texture's sampler parameters.
+    ///
+    /// glTF texture coordinates start at the top-left of an image, which is also where its
+    /// first row is uploaded, so images are not flipped.
+    pub fn upload(&self, image: &DynamicImage) -> Texture {
+        let texture = Texture::from_image(image);
+        TextureParameters::new()
+            .filter_method(self.filter)
+            .set_param(TextureParam::WrapS, self.wrap_s.into())
+            .set_param(TextureParam::WrapT, self.wrap_t.into())
+            .apply_to(&texture);
+        texture
+    }
+}
+
+impl GltfCamera {
+    fn new(camera: &gltf::Camera, node: usize, world: &glm::Mat4) -> GltfCamera {
+        // cameras look down their node's -z axis, with +y up
+        let eye = (*world * glm::vec4(0.0, 0.0, 0.0, 1.0)).truncate(3);
+        let look = glm::normalize((*world * glm::vec4(0.0, 0.0, -1.0, 0.0)).truncate(3));
+        let up = glm::normalize((*world * glm::vec4(0.0, 1.0, 0.0, 0.0)).truncate(3));
+        let name = camera.name().map(String::from);
+        match camera.projection() {
+            gltf::camera::Projection::Perspective(p) => GltfCamera {
+                name,
+                node,
+                eye,
+                look,
+                up,
+                projection: match p.zfar() {
+                    Some(_) => Projection::Perspective,
+                    None => Projection::InfinitePerspective { reverse_z: false },
+                },
+                fov: Some(p.yfov()),
+                aspect_ratio: p.aspect_ratio(),
+                near: p.znear(),
+                far: p.zfar(),
+            },
+            gltf::camera::Projection::Orthographic(o) => GltfCamera {
+                name,
+                node,
+                eye,
+                look,
+                up,
+                projection: Projection::Orthographic {
+                    height: 2.0 * o.ymag(),
+                },
+                fov: None,
+                aspect_ratio: Some(o.xmag() / o.ymag()),
+                near: o.znear(),
+                far: Some(o.zfar()),
+            },
+        }
+    }
+
+    /// Parameters for a `Camera` viewing through this one. Parameters the glTF camera leaves
+    /// out, such as the aspect ratio of a perspective camera, keep their defaults.
+    pub fn builder(&self) -> CameraBuilder {
+        let mut builder = CameraBuilder::new()
+            .eye(&self.eye)
+            .look(&self.look)
+            .up(&self.up)
+            .near_clip(self.near)
+            .projection(self.projection);
+        if let Some(fov) = self.fov {
+            builder = builder.fov(fov);
+        }
+        if let Some(ratio) = self.aspect_ratio {
+            builder = builder.aspect_ratio(ratio);
+        }
+        if let Some(far) = self.far {
+            builder = builder.far_clip(far);
+        }
+        builder
+    }
+}
+
+impl GltfLight {
+    fn new(light: &gltf::khr_lights_punctual::Light, node: usize, world: &glm::Mat4) -> GltfLight {
+        GltfLight {
+            name: light.name().map(String::from),
+            node,
+            kind: match light.kind() {
+                Kind::Directional => LightKind::Directional,
+                Kind::Point => LightKind::Point,
+                Kind::Spot {
+                    inner_cone_angle,
+                    outer_cone_angle,
+                } => LightKind::Spot {
+                    inner_cone: inner_cone_angle,
+                    outer_cone: outer_cone_angle,
+                },
+            },
+            color: to_vec3(light.color()),
+            intensity: light.intensity(),
+            range: light.range(),
+            // lights shine down their node's -z axis
+            position: (*world * glm::vec4(0.0, 0.0, 0.0, 1.0)).truncate(3),
+            direction: glm::normalize((*world * glm::vec4(0.0, 0.0, -1.0, 0.0)).truncate(3)),
+        }
+    }
+}
+
+fn read_all<I, T, U, F>(values: Option<I>, f: F) -> Vec<U>
+where
+    I: Iterator<Item = T>,
+    F: Fn(T) -> U,
+{
+    values.map_or_else(Vec::new, |values| values.map(f).collect())
+}
+
+fn to_vec3(v: [f32; 3]) -> glm::Vec3 {
+    glm::vec3(v[0], v[1], v[2])
+}
+
+fn to_vec4(v: [f32; 4]) -> glm::Vec4 {
+    glm::vec4(v[0], v[1], v[2], v[3])
+}
+
+/// glTF matrices are column-major, as are `glm`'s.
+fn to_mat4(m: [[f32; 4]; 4]) -> glm::Mat4 {
+    glm::Mat4::new(to_vec4(m[0]), to_vec4(m[1]), to_vec4(m[2]), to_vec4(m[3]))
+}
+
+/// Convert a decoded glTF image into a `DynamicImage`. Images with channels of more than 8 bits
+/// are reduced to 8-bit RGBA.
+fn to_image(data: gltf::image::Data) -> DynamicImage {
+    use gltf::image::Format;
+
+    let (width, height) = (data.width, data.height);
+    let rgba = |pixels: Vec<u8>| {
+        image::ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+    };
+    let image =
+        match data.format {
+            Format::R8 => image::ImageBuffer::from_raw(width, height, data.pixels)
+                .map(DynamicImage::ImageLuma8),
+            Format::R8G8 => image::ImageBuffer::from_raw(width, height, data.pixels)
+                .map(DynamicImage::ImageLumaA8),
+            Format::R8G8B8 => image::ImageBuffer::from_raw(width, height, data.pixels)
+                .map(DynamicImage::ImageRgb8),
+            Format::R8G8B8A8 => rgba(data.pixels),
+            format => {
+                let channels: Vec<f32> = match format {
+                    Format::R32G32B32FLOAT | Format::R32G32B32A32FLOAT => data
+                        .pixels
+                        .chunks(4)
+                        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect(),
+                    _ => data
+                        .pixels
+                        .chunks(2)
+                        .map(|b| f32::from(u16::from_le_bytes([b[0], b[1]])) / 65535.0)
+                        .collect(),
+                };
+                let count = channels.len() / (width * height).max(1) as usize;
+                let pixels = channels
+                    .chunks(count.max(1))
+                    .flat_map(|c| {
+                        let at = |i: usize| c.get(i).cloned();
+                        // greyscale spreads over RGB; missing alpha is opaque
+                        let rgb = match count {
+                            1 | 2 => [c[0]; 3],
+                            _ => [c[0], c[1], c[2]],
+                        };
+                        let alpha = match count {
+                            2 => at(1),
+                            4 => at(3),
+                            _ => None,
+                        };
+                        vec![rgb[0], rgb[1], rgb[2], alpha.unwrap_or(1.0)]
+                    })
+                    .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .collect();
+                rgba(pixels)
+            }
+        };
+    image.unwrap_or_else(|| DynamicImage::new_rgba8(1, 1))
+}
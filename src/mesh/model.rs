@@ -1,3 +1,10 @@
+use std::rc::Rc;
+
+use glm;
+use num;
+
+use crate::picking::Bvh;
+use crate::rendergl::types::GlLayout;
 use crate::rendergl::{self, Program};
 use crate::resources::ResourceLoader;
 use crate::shape::ShapeGL;
@@ -6,6 +13,10 @@ use tobj;
 pub struct SceneModel {
     pub shapegl: ShapeGL,
     pub material_id: Option<usize>,
+    /// Transform from the model's space to that of the `MeshObject` it belongs to.
+    pub transform: glm::Mat4,
+    /// CPU-side triangles for picking, if the model can be picked.
+    pub bvh: Option<Bvh>,
 }
 
 impl SceneModel {
@@ -13,21 +24,41 @@ impl SceneModel {
         SceneModel {
             shapegl,
             material_id,
+            transform: num::one(),
+            bvh: None,
         }
     }
+
+    /// Make the model pickable, using `bvh` built over the same triangles as `shapegl`.
+    pub fn with_bvh(mut self, bvh: Bvh) -> SceneModel {
+        self.bvh = Some(bvh);
+        self
+    }
+
+    /// Place the model within its `MeshObject`, e.g. at a node of a scene hierarchy.
+    pub fn with_transform(mut self, transform: glm::Mat4) -> SceneModel {
+        self.transform = transform;
+        self
+    }
 }
 
 impl From<&tobj::Model> for SceneModel {
     fn from(other: &tobj::Model) -> Self {
-        SceneModel {
-            shapegl: ShapeGL::from_mesh(&other.mesh),
-            material_id: other.mesh.material_id,
-        }
+        let positions = other
+            .mesh
+            .positions
+            .chunks(3)
+            .map(|p| glm::vec3(p[0], p[1], p[2]))
+            .collect();
+        let bvh = Bvh::new(positions, &other.mesh.indices, GlLayout::Triangles);
+        SceneModel::new(ShapeGL::from_mesh(&other.mesh), other.mesh.material_id).with_bvh(bvh)
     }
 }
 
+/// Program to draw `MeshObject`s with, which clones share rather than compiling again.
+#[derive(Clone)]
 pub struct MaterialShader {
-    pub program: Program,
+    pub program: Rc<Program>,
 }
 
 impl MaterialShader {
@@ -36,7 +67,9 @@ impl MaterialShader {
         name: &str,
     ) -> Result<MaterialShader, rendergl::shader::Error> {
         let program = Program::from_res(res, name)?;
-        Ok(MaterialShader { program })
+        Ok(MaterialShader {
+            program: Rc::new(program),
+        })
     }
 
     pub fn apply_material(
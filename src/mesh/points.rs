@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use gl;
+use glm;
+use num;
+
+use crate::animation::Pose;
+use crate::camera::Camera;
+use crate::rendergl::Program;
+use crate::resources::ResourceLoader;
+use crate::shape::{DrawError, Drawable, InitError, MeshData, ShapeGL};
+use crate::util::Aabb;
+
+use super::upload_scan;
+
+/// Implements `Drawable` to render a point cloud, such as a scan loaded from a PLY file.
+///
+/// Points are drawn with their vertex colours if the cloud has them, or in a single colour
+/// otherwise.
+pub struct PointCloud {
+    program: Program,
+    shapegl: ShapeGL,
+    vertex_colors: bool,
+    transform: glm::Mat4,
+    /// Size of each point in pixels.
+    pub point_size: f32,
+    /// Colour of the points if the cloud has no vertex colours.
+    pub color: [f32; 3],
+}
+
+impl PointCloud {
+    /// Load the vertices of a PLY file as a point cloud, drawn with the program
+    /// `program_name` (e.g. `"shaders/points"`). Any faces are ignored.
+    pub fn from_ply(
+        loader: &ResourceLoader,
+        plyfile: &str,
+        program_name: &str,
+    ) -> Result<PointCloud, InitError> {
+        let data = loader.load_ply(Path::new(plyfile))?;
+        let program = Program::from_res(loader, program_name)?;
+        Ok(PointCloud::new(program, &data))
+    }
+
+    /// Upload the vertices of `data` as a point cloud, drawn with `program`.
+    pub fn new(program: Program, data: &MeshData) -> PointCloud {
+        let points = MeshData {
+            indices: Vec::new(),
+            ..data.clone()
+        };
+        PointCloud {
+            program,
+            shapegl: upload_scan(&points),
+            vertex_colors: !data.colors.is_empty(),
+            transform: num::one(),
+            point_size: 2.0,
+            color: [0.8; 3],
+        }
+    }
+}
+
+impl Drawable for PointCloud {
+    fn draw(&self, camera: &Camera) -> Result<(), DrawError> {
+        self.program.bind();
+        self.program.set_uniform("view", &camera.view)?;
+        self.program
+            .set_uniform("perspective", &camera.perspective)?;
+        self.program.set_uniform("model", &self.transform)?;
+        self.program.set_uniform("cDiffuse", &self.color)?;
+        self.program
+            .set_uniform("useVertexColor", &(self.vertex_colors as i32))?;
+        unsafe {
+            gl::PointSize(self.point_size);
+        }
+        self.shapegl.draw_vertices(&self.program)
+    }
+
+    fn apply_pose(&mut self, pose: &Pose) {
+        if let Some(transform) = pose.transform {
+            self.transform = transform;
+        }
+    }
+
+    /// Points have no triangles to report, and a single pixel is hard to click anyway, so point
+    /// clouds are not picked.
+    fn draw_ids(&self, _program: &Program) -> Result<(), DrawError> {
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.shapegl.bounds();
+        if bounds.is_empty() {
+            None
+        } else {
+            Some(bounds.transform(&self.transform))
+        }
+    }
+}
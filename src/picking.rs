@@ -0,0 +1,103 @@
+//! Finding what is under the cursor.
+//!
+//! `Camera::unproject` turns a pixel into a world-space `Ray`, which can then be cast against
+//! anything implementing `Drawable::ray_cast`. Meshes answer this on the CPU, using a bounding
+//! volume hierarchy (`Bvh`) over their triangles so that large models stay interactive.
+//!
+//! Alternatively, `IdPicker` renders object and primitive IDs on the GPU and reads back the pixel
+//! under the cursor. Both give the same `Pick`.
+
+use glm;
+use std::f32;
+
+use crate::util::Aabb;
+
+mod bvh;
+mod idbuffer;
+
+pub use self::bvh::Bvh;
+pub use self::idbuffer::IdPicker;
+
+/// Half-line starting at `origin` and extending along `direction`.
+///
+/// Points on the ray are `origin + direction * t` for `t >= 0`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Ray {
+    pub origin: glm::Vec3,
+    pub direction: glm::Vec3,
+}
+
+impl Ray {
+    /// Construct a ray, normalising `direction` so that `t` measures distance.
+    pub fn new(origin: glm::Vec3, direction: glm::Vec3) -> Ray {
+        Ray {
+            origin,
+            direction: glm::normalize(direction),
+        }
+    }
+
+    /// Point at parameter `t` along the ray.
+    pub fn at(&self, t: f32) -> glm::Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// The ray transformed by an affine `transform`.
+    ///
+    /// The direction is deliberately left unnormalised, so that a point found at `t` along the
+    /// transformed ray is at the same `t` along the original one.
+    pub fn transform(&self, transform: &glm::Mat4) -> Ray {
+        Ray {
+            origin: (*transform * self.origin.extend(1.0)).truncate(3),
+            direction: (*transform * self.direction.extend(0.0)).truncate(3),
+        }
+    }
+
+    /// Range of `t` over which the ray is inside `aabb`, if it hits it at all.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<(f32, f32)> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            if direction == 0.0 {
+                // parallel to the slab: either always inside it or never
+                if origin < aabb.min[axis] || origin > aabb.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (aabb.min[axis] - origin) / direction;
+            let t1 = (aabb.max[axis] - origin) / direction;
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+        }
+        if t_min <= t_max {
+            Some((t_min, t_max))
+        } else {
+            None
+        }
+    }
+}
+
+/// Where a ray hit a mesh.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Hit {
+    /// Distance along the ray.
+    pub distance: f32,
+    /// Index of the sub-mesh (e.g. the `SceneModel` of a `MeshObject`) that was hit.
+    pub model: usize,
+    /// Index of the triangle within the sub-mesh, counted as OpenGL counts primitives (so for
+    /// a triangle strip, the `n`th triangle starts at the `n`th index).
+    pub triangle: usize,
+    /// Barycentric coordinates of the hit with respect to the triangle's three vertices.
+    pub barycentric: glm::Vec3,
+    /// World-space position of the hit.
+    pub position: glm::Vec3,
+}
+
+/// The closest `Hit` among all objects in a scene.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Pick {
+    /// Index of the object that was hit.
+    pub object: usize,
+    pub hit: Hit,
+}
@@ -0,0 +1,240 @@
+use glm;
+use std::f32;
+
+use crate::picking::{Hit, Ray};
+use crate::rendergl::types::GlLayout;
+use crate::util::Aabb;
+
+#[derive(Copy, Clone)]
+struct Triangle {
+    vertices: [u32; 3],
+    /// Index of the triangle as OpenGL would number it.
+    primitive: usize,
+}
+
+/// Node of the hierarchy. Interior nodes have `count == 0`, their left child directly after
+/// them and their right child at `first`. Leaves hold `triangles[first..first + count]`.
+struct Node {
+    bounds: Aabb,
+    first: usize,
+    count: usize,
+}
+
+/// Bounding volume hierarchy over a triangle mesh, for fast ray casting on the CPU.
+///
+/// The hierarchy is built once, by recursively splitting triangles at the median of their
+/// centroids along the longest axis, and is stored as a flat array of nodes.
+pub struct Bvh {
+    positions: Vec<glm::Vec3>,
+    triangles: Vec<Triangle>,
+    nodes: Vec<Node>,
+}
+
+impl Bvh {
+    const LEAF_SIZE: usize = 4;
+
+    /// Build a hierarchy over the triangles described by `indices` into `positions`, assembled
+    /// according to `layout`. Degenerate triangles (such as those joining the rows of a triangle
+    /// strip) are left out; layouts which are not made of triangles give an empty hierarchy.
+    pub fn new(positions: Vec<glm::Vec3>, indices: &[u32], layout: GlLayout) -> Bvh {
+        let mut triangles = Bvh::assemble(indices, layout);
+        triangles.retain(|t| {
+            let [a, b, c] = t.vertices;
+            a != b && b != c && a != c
+        });
+
+        let mut bvh = Bvh {
+            positions,
+            triangles,
+            nodes: Vec::new(),
+        };
+        let mut centroids: Vec<glm::Vec3> = bvh
+            .triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = bvh.corners(t);
+                (a + b + c) / 3.0
+            })
+            .collect();
+        let count = bvh.triangles.len();
+        bvh.build(&mut centroids, 0, count);
+        bvh
+    }
+
+    /// Bounds of all of the mesh's triangles.
+    pub fn bounds(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(root) => root.bounds,
+            None => Aabb::empty(),
+        }
+    }
+
+    /// Closest triangle hit by `ray`, if any. Both faces of each triangle count.
+    ///
+    /// The returned `Hit` is in the same space as the ray, and its `model` is always 0.
+    pub fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+        let mut stack = match self.nodes.first() {
+            Some(_) => vec![0],
+            None => Vec::new(),
+        };
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let limit = closest.map_or(f32::INFINITY, |hit| hit.distance);
+            match ray.intersect_aabb(&node.bounds) {
+                Some((t_near, _)) if t_near <= limit => {}
+                _ => continue,
+            }
+
+            if node.count > 0 {
+                for triangle in &self.triangles[node.first..node.first + node.count] {
+                    if let Some(hit) = self.intersect(ray, triangle) {
+                        if closest.is_none_or(|c| hit.distance < c.distance) {
+                            closest = Some(hit);
+                        }
+                    }
+                }
+            } else {
+                // visit the nearer child first, so that further boxes can be culled
+                let (left, right) = (index + 1, node.first);
+                let t_left = ray.intersect_aabb(&self.nodes[left].bounds).map(|t| t.0);
+                let t_right = ray.intersect_aabb(&self.nodes[right].bounds).map(|t| t.0);
+                if t_left.unwrap_or(f32::INFINITY) <= t_right.unwrap_or(f32::INFINITY) {
+                    stack.push(right);
+                    stack.push(left);
+                } else {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        closest
+    }
+
+    /// Split an index buffer into triangles, numbered the way OpenGL numbers primitives.
+    fn assemble(indices: &[u32], layout: GlLayout) -> Vec<Triangle> {
+        let count = indices.len();
+        match layout {
+            GlLayout::Triangles => indices
+                .chunks(3)
+                .filter(|c| c.len() == 3)
+                .enumerate()
+                .map(|(primitive, c)| Triangle {
+                    vertices: [c[0], c[1], c[2]],
+                    primitive,
+                })
+                .collect(),
+            GlLayout::TriangleStrip => (0..count.saturating_sub(2))
+                .map(|i| {
+                    // every other triangle in a strip is flipped to keep a consistent winding
+                    let vertices = if i % 2 == 0 {
+                        [indices[i], indices[i + 1], indices[i + 2]]
+                    } else {
+                        [indices[i + 1], indices[i], indices[i + 2]]
+                    };
+                    Triangle {
+                        vertices,
+                        primitive: i,
+                    }
+                })
+                .collect(),
+            GlLayout::TriangleFan => (0..count.saturating_sub(2))
+                .map(|i| Triangle {
+                    vertices: [indices[0], indices[i + 1], indices[i + 2]],
+                    primitive: i,
+                })
+                .collect(),
+            GlLayout::LineStrip | GlLayout::Points => Vec::new(),
+        }
+    }
+
+    fn corners(&self, triangle: &Triangle) -> [glm::Vec3; 3] {
+        let [a, b, c] = triangle.vertices;
+        [
+            self.positions[a as usize],
+            self.positions[b as usize],
+            self.positions[c as usize],
+        ]
+    }
+
+    /// Recursively build nodes over `triangles[start..end]`.
+    fn build(&mut self, centroids: &mut [glm::Vec3], start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        let bounds = self.triangles[start..end]
+            .iter()
+            .fold(Aabb::empty(), |aabb, t| {
+                let [a, b, c] = self.corners(t);
+                aabb.grow(a).grow(b).grow(c)
+            });
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            first: start,
+            count: end - start,
+        });
+
+        let centre_bounds = Aabb::from_points(&centroids[start..end]);
+        let axis = centre_bounds.longest_axis();
+        if end - start <= Bvh::LEAF_SIZE || centre_bounds.size()[axis] <= 0.0 {
+            return;
+        }
+
+        // partially sort triangles and their centroids together around the median
+        let mid = (start + end) / 2;
+        let mut order: Vec<usize> = (start..end).collect();
+        order.select_nth_unstable_by(mid - start, |&a, &b| {
+            centroids[a][axis]
+                .partial_cmp(&centroids[b][axis])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let triangles: Vec<Triangle> = order.iter().map(|&i| self.triangles[i]).collect();
+        let sorted: Vec<glm::Vec3> = order.iter().map(|&i| centroids[i]).collect();
+        self.triangles[start..end].copy_from_slice(&triangles);
+        centroids[start..end].copy_from_slice(&sorted);
+
+        self.build(centroids, start, mid);
+        let right = self.nodes.len();
+        self.build(centroids, mid, end);
+        self.nodes[index].first = right;
+        self.nodes[index].count = 0;
+    }
+
+    /// Möller-Trumbore ray/triangle intersection.
+    fn intersect(&self, ray: &Ray, triangle: &Triangle) -> Option<Hit> {
+        let [a, b, c] = self.corners(triangle);
+        let e1 = b - a;
+        let e2 = c - a;
+        let p = glm::cross(ray.direction, e2);
+        let det = glm::dot(e1, p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = ray.origin - a;
+        let u = glm::dot(s, p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = glm::cross(s, e1);
+        let v = glm::dot(ray.direction, q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = glm::dot(e2, q) * inv_det;
+        if t < 0.0 {
+            return None;
+        }
+
+        Some(Hit {
+            distance: t,
+            model: 0,
+            triangle: triangle.primitive,
+            barycentric: glm::vec3(1.0 - u - v, u, v),
+            position: ray.at(t),
+        })
+    }
+}
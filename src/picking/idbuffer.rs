@@ -0,0 +1,238 @@
+use gl;
+use glm;
+use glm::GenSquareMat;
+use std::f32;
+
+use crate::camera::Camera;
+use crate::picking::{Hit, Pick, Ray};
+use crate::rendergl::texture::Texture;
+use crate::rendergl::{Fence, Framebuffer, PixelBuffer, Program, Renderbuffer};
+use crate::resources::ResourceLoader;
+use crate::shape::{DrawError, Drawable, InitError};
+
+/// Off-screen render targets for the ID pass, matching the viewport size.
+struct Targets {
+    framebuffer: Framebuffer,
+    _ids: Texture,
+    _weights: Texture,
+    _depth: Renderbuffer,
+    size: (i32, i32),
+}
+
+/// A readback in flight, with what is needed to turn its result into a `Pick`.
+struct Pending {
+    fence: Fence,
+    ray: Ray,
+    unproject: glm::Mat4,
+    ndc: (f32, f32),
+}
+
+/// Picks objects by rendering IDs on the GPU.
+///
+/// Each pickable `Drawable` is drawn (with `Drawable::draw_ids`) into an integer framebuffer
+/// attachment recording, per pixel, the object index, sub-mesh, primitive ID and depth; a second
+/// attachment records barycentric coordinates. Only the pixel under the cursor is rendered and it
+/// is copied into a pixel buffer object, so requesting a pick does not wait for the GPU. Poll for
+/// the result on later frames.
+///
+/// Unlike ray casting this needs no CPU-side geometry, and triangle numbers match OpenGL's
+/// primitive IDs for any layout, including triangle strips.
+pub struct IdPicker {
+    program: Program,
+    targets: Option<Targets>,
+    readback: PixelBuffer,
+    pending: Option<Pending>,
+    /// Whether the last request could not be rendered, and so is reported as a miss.
+    missed: bool,
+}
+
+impl IdPicker {
+    /// Bytes read back per pick: a `uvec4` of IDs and a `vec2` of barycentric weights.
+    const READBACK_SIZE: usize = 24;
+
+    pub fn new(loader: &ResourceLoader) -> Result<IdPicker, InitError> {
+        let program = Program::from_res(loader, "shaders/ids")?;
+        Ok(IdPicker {
+            program,
+            targets: None,
+            readback: PixelBuffer::new(IdPicker::READBACK_SIZE),
+            pending: None,
+            missed: false,
+        })
+    }
+
+    /// Whether a pick has been requested but its result is not yet available.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Start picking whichever of `shapes` is under pixel `(x, y)` of a `viewport` of the given
+    /// size, with the origin in the top-left corner. Replaces any pick still pending.
+    ///
+    /// Binds the default framebuffer and program afterwards.
+    pub fn request(
+        &mut self,
+        shapes: &[Box<dyn Drawable>],
+        camera: &Camera,
+        x: i32,
+        y: i32,
+        viewport: (i32, i32),
+    ) -> Result<(), DrawError> {
+        let (width, height) = (viewport.0.max(1), viewport.1.max(1));
+        self.pending = None;
+        self.missed = false;
+        if !(0..width).contains(&x) || !(0..height).contains(&y) {
+            self.missed = true;
+            return Ok(());
+        }
+        self.bind_targets((width, height))?;
+        let row = height - 1 - y; // window coordinates start at the bottom
+
+        let mut polygon_mode = [gl::FILL as gl::types::GLint; 2];
+        let clear_depth = camera.projection().clear_depth() as f32;
+        unsafe {
+            gl::GetIntegerv(gl::POLYGON_MODE, polygon_mode.as_mut_ptr());
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(x, row, 1, 1);
+            gl::ClearBufferuiv(gl::COLOR, 0, [0u32; 4].as_ptr());
+            gl::ClearBufferfv(gl::COLOR, 1, [0.0f32; 4].as_ptr());
+            gl::ClearBufferfv(gl::DEPTH, 0, &clear_depth);
+        }
+
+        self.program.bind();
+        self.program.set_uniform("view", &camera.view)?;
+        self.program
+            .set_uniform("perspective", &camera.perspective)?;
+        for (i, shape) in shapes.iter().enumerate() {
+            self.program.set_uniform("objectId", &(i as u32 + 1))?;
+            self.program.set_uniform("submesh", &0u32)?;
+            shape.draw_ids(&self.program)?;
+        }
+
+        self.readback.bind();
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::PolygonMode(gl::FRONT_AND_BACK, polygon_mode[0] as gl::types::GLenum);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::ReadPixels(
+                x,
+                row,
+                1,
+                1,
+                gl::RGBA_INTEGER,
+                gl::UNSIGNED_INT,
+                std::ptr::null_mut(),
+            );
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT1);
+            gl::ReadPixels(
+                x,
+                row,
+                1,
+                1,
+                gl::RG,
+                gl::FLOAT,
+                16 as *mut gl::types::GLvoid,
+            );
+        }
+        self.readback.unbind();
+        Framebuffer::bind_default();
+        Program::bind_default();
+
+        let ray = camera.unproject(x, y, viewport);
+        self.pending =
+            (camera.perspective * camera.view)
+                .inverse()
+                .zip(ray)
+                .map(|(unproject, ray)| Pending {
+                    fence: Fence::new(),
+                    ray,
+                    unproject,
+                    ndc: (
+                        2.0 * (x as f32 + 0.5) / width as f32 - 1.0,
+                        2.0 * (row as f32 + 0.5) / height as f32 - 1.0,
+                    ),
+                });
+        // without an invertible camera there is no ray to pick along
+        self.missed = self.pending.is_none();
+        Ok(())
+    }
+
+    /// Result of the pending pick, once the GPU has finished with it.
+    ///
+    /// Returns `None` while there is no result to collect, and `Some(None)` if the pick hit
+    /// nothing. `Pick::object` is the index of the hit object in the slice given to `request`.
+    pub fn poll(&mut self) -> Option<Option<Pick>> {
+        if self.missed {
+            self.missed = false;
+            return Some(None);
+        }
+        if !self.pending.as_ref()?.fence.is_signaled() {
+            return None;
+        }
+        let pending = self.pending.take()?;
+        let data: Vec<u32> = self.readback.read();
+        if data.len() < 6 || data[0] == 0 {
+            return Some(None);
+        }
+
+        // window depth back to a world-space point
+        let depth = f32::from_bits(data[3]);
+        let p = pending.unproject * glm::vec4(pending.ndc.0, pending.ndc.1, 2.0 * depth - 1.0, 1.0);
+        let position = p.truncate(3) / p.w;
+        let (u, v) = (f32::from_bits(data[4]), f32::from_bits(data[5]));
+
+        Some(Some(Pick {
+            object: data[0] as usize - 1,
+            hit: Hit {
+                distance: glm::dot(position - pending.ray.origin, pending.ray.direction),
+                model: data[1] as usize,
+                triangle: data[2] as usize,
+                barycentric: glm::vec3(1.0 - u - v, u, v),
+                position,
+            },
+        }))
+    }
+
+    /// Bind the framebuffer, (re)creating targets of the given size if needed.
+    fn bind_targets(&mut self, size: (i32, i32)) -> Result<(), DrawError> {
+        if let Some(ref targets) = self.targets {
+            if targets.size == size {
+                targets.framebuffer.bind();
+                return Ok(());
+            }
+        }
+
+        let (width, height) = size;
+        let ids = Texture::empty(
+            gl::RGBA32UI,
+            gl::RGBA_INTEGER,
+            gl::UNSIGNED_INT,
+            width,
+            height,
+        );
+        let weights = Texture::empty(gl::RG32F, gl::RG, gl::FLOAT, width, height);
+        let depth = Renderbuffer::new(gl::DEPTH_COMPONENT24, width, height);
+
+        let framebuffer = Framebuffer::new();
+        framebuffer.bind();
+        framebuffer.attach_texture(gl::COLOR_ATTACHMENT0, &ids);
+        framebuffer.attach_texture(gl::COLOR_ATTACHMENT1, &weights);
+        framebuffer.attach_renderbuffer(gl::DEPTH_ATTACHMENT, &depth);
+        framebuffer.draw_buffers(&[gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1]);
+        if !framebuffer.is_complete() {
+            Framebuffer::bind_default();
+            self.targets = None;
+            return Err(DrawError::FramebufferError);
+        }
+
+        self.targets = Some(Targets {
+            framebuffer,
+            _ids: ids,
+            _weights: weights,
+            _depth: depth,
+            size,
+        });
+        Ok(())
+    }
+}
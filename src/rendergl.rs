@@ -4,12 +4,18 @@
 
 mod buffer;
 mod data;
+mod framebuffer;
+mod packing;
 pub mod shader;
+mod sync;
 pub mod texture;
 pub mod types;
 pub mod uniform;
 
 pub use self::buffer::*;
 pub use self::data::*;
-pub use self::shader::{Program, Shader};
-pub use self::uniform::UniformSet;
+pub use self::framebuffer::{Framebuffer, Renderbuffer};
+pub use self::packing::*;
+pub use self::shader::{ActiveAttrib, AttribMismatch, Program, Shader};
+pub use self::sync::Fence;
+pub use self::uniform::{UniformSet, UniformValue};
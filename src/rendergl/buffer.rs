@@ -2,15 +2,27 @@ use crate::rendergl::{self, types};
 use gl;
 use std;
 
+/// Error building a `VAO` from several vertex streams.
+#[derive(Debug, Fail)]
+pub enum StreamError {
+    #[fail(display = "Attribute {:?} is in more than one vertex stream", attrib)]
+    OverlappingAttribute { attrib: types::ShaderAttrib },
+}
+
 /// Vertex Buffer Object.
 ///
 /// Passes an array of vertex data to GPU and wraps binding and cleanup.
+///
+/// A VBO is one stream of a `VAO`, holding the attributes of its vertex type `T`; other
+/// attributes can come from other streams (see `VAO::with_streams`). A stream with a divisor
+/// holds per-instance data instead (see `with_divisor`).
 pub struct VBO {
     id: gl::types::GLuint,
     markers: Vec<rendergl::VBOAttribMarker>,
     buffer_size: usize, // number of vertices
     //    f32_per_vert: usize, // elements per vertex
     stride: gl::types::GLint,
+    divisor: gl::types::GLuint, // instances per element, or 0 for per-vertex data
 }
 
 impl VBO {
@@ -35,7 +47,71 @@ impl VBO {
             markers,
             buffer_size,
             stride: stride as i32,
+            divisor: 0,
+        }
+    }
+
+    /// Advance through the buffer once every `divisor` instances rather than once per vertex,
+    /// for per-instance data such as `InstanceOffset`. A divisor of 0 is per-vertex data.
+    ///
+    /// Takes effect in VAOs built after it is set.
+    pub fn with_divisor(mut self, divisor: u32) -> VBO {
+        self.divisor = divisor;
+        self
+    }
+
+    /// Replace the contents of the buffer, which must hold the same vertex type `T`. VAOs
+    /// using the buffer read the new data without being rebuilt.
+    ///
+    /// Data of the same length is written in place; otherwise the buffer is reallocated, and
+    /// VAOs drawing its vertices without an index buffer, or its instances, should be given
+    /// the new count.
+    pub fn update<T: rendergl::Vertex>(&mut self, data: &[T]) {
+        debug_assert_eq!(
+            std::mem::size_of::<T>(),
+            self.stride as usize,
+            "VBO updated with a different vertex type"
+        );
+        let size = std::mem::size_of_val(data) as gl::types::GLsizeiptr;
+        self.bind();
+        unsafe {
+            if data.len() == self.buffer_size {
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    0,
+                    size,
+                    data.as_ptr() as *const gl::types::GLvoid,
+                );
+            } else {
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    size,
+                    data.as_ptr() as *const gl::types::GLvoid,
+                    gl::DYNAMIC_DRAW,
+                );
+            }
         }
+        self.unbind();
+        self.buffer_size = data.len();
+    }
+
+    /// Number of vertices, or instance elements, in the buffer.
+    pub fn len(&self) -> usize {
+        self.buffer_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer_size == 0
+    }
+
+    /// Instances per element, or 0 for per-vertex data.
+    pub fn divisor(&self) -> u32 {
+        self.divisor
+    }
+
+    /// Layout of each vertex in the buffer.
+    pub fn markers(&self) -> &[rendergl::VBOAttribMarker] {
+        &self.markers
     }
 
     // FIXME this bind-unbind semantics feels unsafe/not rusty
@@ -50,14 +126,25 @@ impl VBO {
         for m in &self.markers {
             unsafe {
                 gl::EnableVertexAttribArray(m.name.into());
-                gl::VertexAttribPointer(
-                    m.name.into(),
-                    m.elements_per_vertex,
-                    m.data_type.into(),
-                    m.normalize,
-                    self.stride,
-                    m.offset as *const gl::types::GLvoid,
-                );
+                if m.integer {
+                    gl::VertexAttribIPointer(
+                        m.name.into(),
+                        m.elements_per_vertex,
+                        m.data_type.into(),
+                        self.stride,
+                        m.offset as *const gl::types::GLvoid,
+                    );
+                } else {
+                    gl::VertexAttribPointer(
+                        m.name.into(),
+                        m.elements_per_vertex,
+                        m.data_type.into(),
+                        m.normalize,
+                        self.stride,
+                        m.offset as *const gl::types::GLvoid,
+                    );
+                }
+                gl::VertexAttribDivisor(m.name.into(), self.divisor);
             }
         }
     }
@@ -81,16 +168,55 @@ impl Drop for VBO {
 ///
 /// Defines a permutation of indices over an associated VBO, and can be used
 /// for indexed drawing.
+///
+/// Indices are stored in the smallest type that holds the largest of them, so meshes with
+/// fewer than 65536 vertices take half the memory. Storing them as `u8` for meshes with fewer
+/// than 256 vertices is opt-in, with `from_data_compact`.
 pub struct IBO {
     id: gl::types::GLuint,
     buffer_size: usize,
+    index_type: types::IndexType,
 }
 
 impl IBO {
     pub fn from_data(data: &[u32]) -> IBO {
+        IBO::with_index_type(data, false)
+    }
+
+    /// Like `from_data`, but indices which all fit in a byte are stored as `u8`. This takes the
+    /// least memory, but many drivers convert byte indices on the CPU every time they are drawn.
+    pub fn from_data_compact(data: &[u32]) -> IBO {
+        IBO::with_index_type(data, true)
+    }
+
+    fn with_index_type(data: &[u32], allow_bytes: bool) -> IBO {
+        let max_index = data.iter().cloned().max().unwrap_or(0);
+        let index_type = types::IndexType::for_max(max_index, allow_bytes);
+        let id = match index_type {
+            types::IndexType::UBYTE => {
+                IBO::upload(&data.iter().map(|&i| i as u8).collect::<Vec<_>>())
+            }
+            types::IndexType::USHORT => {
+                IBO::upload(&data.iter().map(|&i| i as u16).collect::<Vec<_>>())
+            }
+            types::IndexType::UINT => IBO::upload(data),
+        };
+        IBO {
+            id,
+            buffer_size: data.len(),
+            index_type,
+        }
+    }
+
+    /// Type the indices are stored as.
+    pub fn index_type(&self) -> types::IndexType {
+        self.index_type
+    }
+
+    fn upload<T>(data: &[T]) -> gl::types::GLuint {
         let mut id: gl::types::GLuint = 0;
         let buffer_size = data.len();
-        let stride = std::mem::size_of::<u32>();
+        let stride = std::mem::size_of::<T>();
         unsafe {
             gl::GenBuffers(1, &mut id);
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, id); // bind handle
@@ -102,7 +228,7 @@ impl IBO {
             );
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0); // unbind
         }
-        IBO { id, buffer_size }
+        id
     }
 
     pub fn bind(&self) {
@@ -126,44 +252,166 @@ impl Drop for IBO {
     }
 }
 
+/// Pixel Buffer Object for reading pixels back from the GPU.
+///
+/// While bound, `glReadPixels` writes into the buffer instead of client memory and returns
+/// immediately; the data can be mapped once the transfer has completed (see `Fence`), so reading
+/// pixels does not stall the pipeline.
+pub struct PixelBuffer {
+    id: gl::types::GLuint,
+    size: usize, // in bytes
+}
+
+impl PixelBuffer {
+    /// Allocate a buffer of `size` bytes.
+    pub fn new(size: usize) -> PixelBuffer {
+        let mut id: gl::types::GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, id);
+            gl::BufferData(
+                gl::PIXEL_PACK_BUFFER,
+                size as gl::types::GLsizeiptr,
+                std::ptr::null(),
+                gl::STREAM_READ,
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        PixelBuffer { id, size }
+    }
+
+    /// Bind as the target for `glReadPixels`, whose data pointer then becomes a byte offset into
+    /// this buffer.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+    }
+
+    /// Copy the buffer's contents out as `T`s. Blocks if the GPU has not finished writing them.
+    pub fn read<T: Copy>(&self) -> Vec<T> {
+        let count = self.size / std::mem::size_of::<T>();
+        let mut data = Vec::with_capacity(count);
+        self.bind();
+        unsafe {
+            let ptr = gl::MapBufferRange(
+                gl::PIXEL_PACK_BUFFER,
+                0,
+                self.size as gl::types::GLsizeiptr,
+                gl::MAP_READ_BIT,
+            ) as *const T;
+            if !ptr.is_null() {
+                data.extend_from_slice(std::slice::from_raw_parts(ptr, count));
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            }
+        }
+        self.unbind();
+        data
+    }
+}
+
+impl Drop for PixelBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
 /// Enumerate draw methods for VAOs
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 enum DrawMethod {
     ARRAYS,
-    INDEXED,
+    INDEXED(types::IndexType),
 }
 
 /// Vertex Array Object.
 ///
-/// Associates attributes with a VBO and is responsible
-/// for drawing the vertex buffer.
+/// Associates attributes with one or more VBOs and is responsible
+/// for drawing the vertex buffers.
 pub struct VAO {
     id: gl::types::GLuint,
     num_vertices: gl::types::GLsizei, // number of vertices to render
+    instances: Option<gl::types::GLsizei>, // number of instances, if any stream is per-instance
     layout: types::GlLayout,
     draw_method: DrawMethod,
 }
 
 impl VAO {
     pub fn new(vbo: &VBO, ibo: Option<&IBO>, layout: types::GlLayout) -> VAO {
+        // a vertex type cannot bind a location twice, so a single stream never overlaps
+        VAO::build(&[vbo], ibo, layout)
+    }
+
+    /// Construct a VAO reading attributes from several streams, such as positions in one VBO
+    /// and normals and texture coordinates in another, so that each can be updated alone.
+    ///
+    /// Each attribute location must come from one stream only, or `StreamError` is returned.
+    /// Per-vertex streams should have the same number of vertices; streams with a divisor (see
+    /// `VBO::with_divisor`) make the VAO draw as many instances as they all hold.
+    pub fn with_streams(
+        vbos: &[&VBO],
+        ibo: Option<&IBO>,
+        layout: types::GlLayout,
+    ) -> Result<VAO, StreamError> {
+        let layouts: Vec<&[rendergl::VBOAttribMarker]> =
+            vbos.iter().map(|vbo| vbo.markers()).collect();
+        VAO::check_streams(&layouts)?;
+        Ok(VAO::build(vbos, ibo, layout))
+    }
+
+    /// Check that streams with the given layouts can be drawn together, i.e. that no attribute
+    /// location is in more than one of them, such as before uploading them.
+    pub fn check_streams(layouts: &[&[rendergl::VBOAttribMarker]]) -> Result<(), StreamError> {
+        let mut attribs: Vec<types::ShaderAttrib> = Vec::new();
+        for m in layouts.iter().flat_map(|markers| markers.iter()) {
+            if attribs.contains(&m.name) {
+                return Err(StreamError::OverlappingAttribute { attrib: m.name });
+            }
+            attribs.push(m.name);
+        }
+        Ok(())
+    }
+
+    fn build(vbos: &[&VBO], ibo: Option<&IBO>, layout: types::GlLayout) -> VAO {
         let mut id: gl::types::GLuint = 0;
-        // number of vertices to render is either the vertices in the VBO, or the indices
+        // number of vertices to render is either the vertices in the VBOs, or the indices
         //  in the IBO
-        let num_vertices = ibo.map_or(vbo.buffer_size, |i| i.buffer_size) as gl::types::GLsizei;
+        let vertices = vbos
+            .iter()
+            .filter(|vbo| vbo.divisor == 0)
+            .map(|vbo| vbo.buffer_size)
+            .min()
+            .unwrap_or(0);
+        let num_vertices = ibo.map_or(vertices, |i| i.buffer_size) as gl::types::GLsizei;
+        let instances = vbos
+            .iter()
+            .filter(|vbo| vbo.divisor != 0)
+            .map(|vbo| vbo.buffer_size * vbo.divisor as usize)
+            .min()
+            .map(|n| n as gl::types::GLsizei);
         unsafe {
             gl::GenVertexArrays(1, &mut id);
             gl::BindVertexArray(id);
         }
-        vbo.bind();
         let draw_method = match ibo {
             Some(i) => {
                 i.bind(); // associate IBO with this VAO
-                DrawMethod::INDEXED
+                DrawMethod::INDEXED(i.index_type)
             }
             None => DrawMethod::ARRAYS,
         };
-        vbo.enable();
-        vbo.unbind();
+        for vbo in vbos {
+            vbo.bind();
+            vbo.enable();
+            vbo.unbind();
+        }
         ibo.map_or((), |i| i.unbind());
         unsafe {
             gl::BindVertexArray(0);
@@ -171,11 +419,30 @@ impl VAO {
         VAO {
             id,
             num_vertices,
+            instances,
             layout,
             draw_method,
         }
     }
 
+    /// Set the number of instances to draw, such as after reallocating a per-instance stream
+    /// with `VBO::update`. With `None`, the vertices are drawn once, without instancing.
+    pub fn set_instances(&mut self, instances: Option<usize>) {
+        self.instances = instances.map(|n| n as gl::types::GLsizei);
+    }
+
+    /// Set the number of vertices to draw from VBOs without an index buffer, such as after
+    /// reallocating them with `VBO::update`. Indexed VAOs draw every index regardless.
+    pub fn set_vertices(&mut self, vertices: usize) {
+        if self.draw_method == DrawMethod::ARRAYS {
+            self.num_vertices = vertices as gl::types::GLsizei;
+        }
+    }
+
+    pub fn layout(&self) -> types::GlLayout {
+        self.layout
+    }
+
     pub fn bind(&self) {
         unsafe {
             gl::BindVertexArray(self.id);
@@ -183,18 +450,30 @@ impl VAO {
     }
 
     pub fn draw(&self) {
-        match self.draw_method {
-            DrawMethod::ARRAYS => unsafe {
+        match (self.draw_method, self.instances) {
+            (DrawMethod::ARRAYS, None) => unsafe {
                 gl::DrawArrays(self.layout.into(), 0, self.num_vertices);
             },
-            DrawMethod::INDEXED => unsafe {
+            (DrawMethod::ARRAYS, Some(instances)) => unsafe {
+                gl::DrawArraysInstanced(self.layout.into(), 0, self.num_vertices, instances);
+            },
+            (DrawMethod::INDEXED(index_type), None) => unsafe {
                 gl::DrawElements(
                     self.layout.into(),
                     self.num_vertices,
-                    gl::UNSIGNED_INT,
+                    index_type.into(),
                     0 as *const gl::types::GLvoid,
                 );
             },
+            (DrawMethod::INDEXED(index_type), Some(instances)) => unsafe {
+                gl::DrawElementsInstanced(
+                    self.layout.into(),
+                    self.num_vertices,
+                    index_type.into(),
+                    std::ptr::null(),
+                    instances,
+                );
+            },
         }
     }
 
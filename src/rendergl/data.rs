@@ -1,12 +1,14 @@
-use crate::rendergl::types;
+use crate::rendergl::packing;
+use crate::rendergl::types::{self, ShaderAttrib};
 use crate::util::SurfacePoint;
 use gl;
 use glm;
+use vertex_derive::Vertex;
 
 /// Defines an interface for generic vertex data representations.
 ///
-/// See the implementations of `VertexUV` or `VertexN` for working examples
-/// of structs that implement this trait.
+/// Vertex types usually derive it with `#[derive(Vertex)]`, marking each field with its
+/// shader location; see `VertexUV` or `VertexN` for working examples.
 pub trait Vertex {
     /// Query attributes for each vertex component.
     ///
@@ -20,12 +22,54 @@ pub trait Vertex {
     /// `SurfacePoint::position()` or `SurfacePoint::normal()` to construct
     /// a `VertexN`.
     fn from_point3d(point: &SurfacePoint) -> Self;
+
+    /// Construct an implementing type from a `SurfacePoint` and an RGBA colour, which surface
+    /// points do not have. Types without a colour ignore it, which is the default.
+    fn from_point_color(point: &SurfacePoint, _color: glm::Vec4) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from_point3d(point)
+    }
+}
+
+/// Read back the position of every vertex in `data`, as described by the `POSITION` marker of
+/// `T`.
+///
+/// Returns an empty list if `T` has no 3-component float or half-float position attribute.
+pub fn vertex_positions<T: Vertex>(data: &[T]) -> Vec<glm::Vec3> {
+    let marker = T::vertex_attrib_markers()
+        .into_iter()
+        .find(|m| m.name == types::ShaderAttrib::POSITION && m.elements_per_vertex >= 3);
+    let (offset, half) = match marker {
+        Some(ref m) if m.data_type == types::VertexAttrib::FLOAT => (m.offset, false),
+        Some(ref m) if m.data_type == types::VertexAttrib::HALF_FLOAT => (m.offset, true),
+        _ => return Vec::new(),
+    };
+
+    let stride = std::mem::size_of::<T>();
+    let bytes = data.as_ptr() as *const u8;
+    (0..data.len())
+        .map(|i| {
+            // vertex types are packed, so the position may not be aligned
+            let p = unsafe {
+                let start = bytes.add(i * stride + offset);
+                if half {
+                    std::ptr::read_unaligned(start as *const [u16; 3]).map(packing::half_to_f32)
+                } else {
+                    std::ptr::read_unaligned(start as *const [f32; 3])
+                }
+            };
+            glm::vec3(p[0], p[1], p[2])
+        })
+        .collect()
 }
 
 /// Representation of a vertex with position.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Vertex)]
 #[repr(C, packed)]
 pub struct VertexP {
+    #[location(ShaderAttrib::POSITION)]
     pos: glm::Vec3,
 }
 impl VertexP {
@@ -33,22 +77,6 @@ impl VertexP {
         VertexP { pos }
     }
 }
-impl Vertex for VertexP {
-    fn vertex_attrib_markers() -> Vec<VBOAttribMarker> {
-        let markers: Vec<VBOAttribMarker> = vec![VBOAttribMarker::new(
-            types::ShaderAttrib::POSITION,
-            types::VertexAttrib::FLOAT,
-            3,
-            gl::FALSE,
-            0,
-        )];
-        markers
-    }
-
-    fn from_point3d(point: &SurfacePoint) -> VertexP {
-        VertexP::new(point.position())
-    }
-}
 impl From<glm::Vec3> for VertexP {
     fn from(other: glm::Vec3) -> VertexP {
         VertexP::new(other)
@@ -56,10 +84,12 @@ impl From<glm::Vec3> for VertexP {
 }
 
 /// Representation of a vertex with position and uv coordinates.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Vertex)]
 #[repr(C, packed)]
 pub struct VertexUV {
+    #[location(ShaderAttrib::POSITION)]
     pos: glm::Vec3,
+    #[location(ShaderAttrib::TEXCOORD0)]
     uv: glm::Vec2,
 }
 impl VertexUV {
@@ -67,31 +97,6 @@ impl VertexUV {
         VertexUV { pos, uv }
     }
 }
-impl Vertex for VertexUV {
-    fn vertex_attrib_markers() -> Vec<VBOAttribMarker> {
-        let markers: Vec<VBOAttribMarker> = vec![
-            VBOAttribMarker::new(
-                types::ShaderAttrib::POSITION,
-                types::VertexAttrib::FLOAT,
-                3,
-                gl::FALSE,
-                0,
-            ),
-            VBOAttribMarker::new(
-                types::ShaderAttrib::TEXCOORD0,
-                types::VertexAttrib::FLOAT,
-                2,
-                gl::FALSE,
-                ::std::mem::size_of::<glm::Vec3>(),
-            ),
-        ];
-        markers
-    }
-
-    fn from_point3d(point: &SurfacePoint) -> VertexUV {
-        VertexUV::new(point.position(), point.texcoord())
-    }
-}
 impl From<(glm::Vec3, glm::Vec2)> for VertexUV {
     fn from(other: (glm::Vec3, glm::Vec2)) -> VertexUV {
         VertexUV::new(other.0, other.1)
@@ -99,11 +104,14 @@ impl From<(glm::Vec3, glm::Vec2)> for VertexUV {
 }
 
 /// Representation of a vertex with position, normal, and texture coordinates.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Vertex)]
 #[repr(C, packed)]
 pub struct VertexNT {
+    #[location(ShaderAttrib::POSITION)]
     pos: glm::Vec3,
+    #[location(ShaderAttrib::NORMAL)]
     n: glm::Vec3,
+    #[location(ShaderAttrib::TEXCOORD0)]
     uv: glm::Vec2,
 }
 impl VertexNT {
@@ -111,38 +119,6 @@ impl VertexNT {
         VertexNT { pos, n: normal, uv }
     }
 }
-impl Vertex for VertexNT {
-    fn vertex_attrib_markers() -> Vec<VBOAttribMarker> {
-        let markers: Vec<VBOAttribMarker> = vec![
-            VBOAttribMarker::new(
-                types::ShaderAttrib::POSITION,
-                types::VertexAttrib::FLOAT,
-                3,
-                gl::FALSE,
-                0,
-            ),
-            VBOAttribMarker::new(
-                types::ShaderAttrib::NORMAL,
-                types::VertexAttrib::FLOAT,
-                3,
-                gl::FALSE,
-                ::std::mem::size_of::<glm::Vec3>(),
-            ),
-            VBOAttribMarker::new(
-                types::ShaderAttrib::TEXCOORD0,
-                types::VertexAttrib::FLOAT,
-                2,
-                gl::FALSE,
-                ::std::mem::size_of::<glm::Vec3>() * 2,
-            ),
-        ];
-        markers
-    }
-
-    fn from_point3d(point: &SurfacePoint) -> VertexNT {
-        VertexNT::new(point.position(), point.normal(), point.texcoord())
-    }
-}
 impl From<(glm::Vec3, glm::Vec3, glm::Vec2)> for VertexNT {
     fn from(other: (glm::Vec3, glm::Vec3, glm::Vec2)) -> VertexNT {
         VertexNT::new(other.0, other.1, other.2)
@@ -150,10 +126,12 @@ impl From<(glm::Vec3, glm::Vec3, glm::Vec2)> for VertexNT {
 }
 
 /// Representation of a vertex with position and normal.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Vertex)]
 #[repr(C, packed)]
 pub struct VertexN {
+    #[location(ShaderAttrib::POSITION)]
     pos: glm::Vec3,
+    #[location(ShaderAttrib::NORMAL)]
     n: glm::Vec3,
 }
 impl VertexN {
@@ -161,46 +139,239 @@ impl VertexN {
         VertexN { pos, n: normal }
     }
 }
-impl Vertex for VertexN {
-    fn vertex_attrib_markers() -> Vec<VBOAttribMarker> {
-        let markers: Vec<VBOAttribMarker> = vec![
-            VBOAttribMarker::new(
-                types::ShaderAttrib::POSITION,
-                types::VertexAttrib::FLOAT,
-                3,
-                gl::FALSE,
-                0,
-            ),
-            VBOAttribMarker::new(
-                types::ShaderAttrib::NORMAL,
-                types::VertexAttrib::FLOAT,
-                3,
-                gl::FALSE,
-                ::std::mem::size_of::<glm::Vec3>(),
-            ),
-        ];
-        markers
+impl From<(glm::Vec3, glm::Vec3)> for VertexN {
+    fn from(other: (glm::Vec3, glm::Vec3)) -> VertexN {
+        VertexN::new(other.0, other.1)
+    }
+}
+
+/// Representation of a vertex with position, normal and RGBA colour. Vertices built from a
+/// `SurfacePoint` alone are white.
+#[derive(Copy, Clone, Debug, Vertex)]
+#[repr(C, packed)]
+pub struct VertexNC {
+    #[location(ShaderAttrib::POSITION)]
+    pos: glm::Vec3,
+    #[location(ShaderAttrib::NORMAL)]
+    n: glm::Vec3,
+    #[location(ShaderAttrib::COLOR)]
+    color: glm::Vec4,
+}
+impl VertexNC {
+    pub fn new(pos: glm::Vec3, normal: glm::Vec3, color: glm::Vec4) -> VertexNC {
+        VertexNC {
+            pos,
+            n: normal,
+            color,
+        }
+    }
+}
+impl From<(glm::Vec3, glm::Vec3, glm::Vec4)> for VertexNC {
+    fn from(other: (glm::Vec3, glm::Vec3, glm::Vec4)) -> VertexNC {
+        VertexNC::new(other.0, other.1, other.2)
     }
+}
 
-    fn from_point3d(point: &SurfacePoint) -> VertexN {
-        VertexN::new(point.position(), point.normal())
+/// Representation of a vertex with position, normal and RGBA colour, with 8 bits per colour
+/// channel. Channels outside `[0, 1]` are clamped.
+#[derive(Copy, Clone, Debug, Vertex)]
+#[repr(C, packed)]
+pub struct VertexNC8 {
+    #[location(ShaderAttrib::POSITION)]
+    pos: glm::Vec3,
+    #[location(ShaderAttrib::NORMAL)]
+    n: glm::Vec3,
+    #[location(ShaderAttrib::COLOR)]
+    color: packing::Unorm8Vec4,
+}
+impl VertexNC8 {
+    pub fn new(pos: glm::Vec3, normal: glm::Vec3, color: glm::Vec4) -> VertexNC8 {
+        VertexNC8 {
+            pos,
+            n: normal,
+            color: color.into(),
+        }
     }
 }
-impl From<(glm::Vec3, glm::Vec3)> for VertexN {
-    fn from(other: (glm::Vec3, glm::Vec3)) -> VertexN {
-        VertexN::new(other.0, other.1)
+impl From<(glm::Vec3, glm::Vec3, glm::Vec4)> for VertexNC8 {
+    fn from(other: (glm::Vec3, glm::Vec3, glm::Vec4)) -> VertexNC8 {
+        VertexNC8::new(other.0, other.1, other.2)
+    }
+}
+
+/// Representation of a vertex with position, normal, texture coordinates and RGBA colour.
+#[derive(Copy, Clone, Debug, Vertex)]
+#[repr(C, packed)]
+pub struct VertexNTC {
+    #[location(ShaderAttrib::POSITION)]
+    pos: glm::Vec3,
+    #[location(ShaderAttrib::NORMAL)]
+    n: glm::Vec3,
+    #[location(ShaderAttrib::TEXCOORD0)]
+    uv: glm::Vec2,
+    #[location(ShaderAttrib::COLOR)]
+    color: glm::Vec4,
+}
+impl VertexNTC {
+    pub fn new(pos: glm::Vec3, normal: glm::Vec3, uv: glm::Vec2, color: glm::Vec4) -> VertexNTC {
+        VertexNTC {
+            pos,
+            n: normal,
+            uv,
+            color,
+        }
+    }
+}
+
+/// Representation of a vertex with position, normal and texture coordinates, in half the
+/// memory of a `VertexNT`.
+///
+/// The position is in half floats, which keep about three significant digits, so large
+/// meshes should be modelled close to their origin. The normal is packed into 10 bits per
+/// component, and the texture coordinates are quantised to 16 bits in `[0, 1]`, so tiling
+/// coordinates outside that range are clamped. Shaders read the attributes as the usual
+/// floating point vectors.
+#[derive(Copy, Clone, Debug, Vertex)]
+#[repr(C, packed)]
+pub struct VertexNTCompact {
+    #[location(ShaderAttrib::POSITION)]
+    pos: packing::HalfVec4,
+    #[location(ShaderAttrib::NORMAL)]
+    n: packing::PackedNormal,
+    #[location(ShaderAttrib::TEXCOORD0)]
+    uv: packing::Unorm16Vec2,
+}
+impl VertexNTCompact {
+    pub fn new(pos: glm::Vec3, normal: glm::Vec3, uv: glm::Vec2) -> VertexNTCompact {
+        VertexNTCompact {
+            pos: pos.into(),
+            n: normal.into(),
+            uv: uv.into(),
+        }
+    }
+}
+impl From<(glm::Vec3, glm::Vec3, glm::Vec2)> for VertexNTCompact {
+    fn from(other: (glm::Vec3, glm::Vec3, glm::Vec2)) -> VertexNTCompact {
+        VertexNTCompact::new(other.0, other.1, other.2)
+    }
+}
+
+/// Normal and texture coordinates without a position, for a stream beside a `VertexP`
+/// stream (see `VAO::with_streams`), so that positions can be updated alone.
+#[derive(Copy, Clone, Debug, Vertex)]
+#[repr(C, packed)]
+pub struct StreamNT {
+    #[location(ShaderAttrib::NORMAL)]
+    n: glm::Vec3,
+    #[location(ShaderAttrib::TEXCOORD0)]
+    uv: glm::Vec2,
+}
+impl StreamNT {
+    pub fn new(normal: glm::Vec3, uv: glm::Vec2) -> StreamNT {
+        StreamNT { n: normal, uv }
+    }
+}
+impl From<(glm::Vec3, glm::Vec2)> for StreamNT {
+    fn from(other: (glm::Vec3, glm::Vec2)) -> StreamNT {
+        StreamNT::new(other.0, other.1)
     }
 }
 
+/// Per-instance translation and uniform scale, in `xyz` and `w`, read from `SPECIAL0` in a
+/// stream with a divisor (see `VBO::with_divisor`).
+#[derive(Copy, Clone, Debug, Vertex)]
+#[repr(C, packed)]
+pub struct InstanceOffset {
+    #[location(ShaderAttrib::SPECIAL0)]
+    offset: [f32; 4],
+}
+impl InstanceOffset {
+    pub fn new(translation: glm::Vec3, scale: f32) -> InstanceOffset {
+        InstanceOffset {
+            offset: [translation.x, translation.y, translation.z, scale],
+        }
+    }
+}
+
+/// Format of vertex attributes stored as `Self`, from which `#[derive(Vertex)]` builds the
+/// attribute markers of each field.
+///
+/// Floats and `glm` vectors are read as they are. Integers and arrays of them are read by
+/// shaders as integers, while the types in `rendergl::packing` are unpacked to floats.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be stored in a vertex attribute",
+    note = "vertex attributes are floats, integers, `glm` vectors, arrays of up to 4 of \
+            them, or packed types such as `HalfVec4`"
+)]
+pub trait AttribFormat {
+    const DATA_TYPE: types::VertexAttrib;
+    const COMPONENTS: gl::types::GLint;
+    /// Whether integer data is mapped to `[0, 1]`, or `[-1, 1]` if signed, when read as floats.
+    const NORMALIZE: bool = false;
+    /// Whether shaders read the data as integers.
+    const INTEGER: bool = false;
+}
+
+macro_rules! float_formats {
+    ($($ty:ty => $components:expr),*) => {
+        $(impl AttribFormat for $ty {
+            const DATA_TYPE: types::VertexAttrib = types::VertexAttrib::FLOAT;
+            const COMPONENTS: gl::types::GLint = $components;
+        })*
+    };
+}
+float_formats!(f32 => 1, [f32; 2] => 2, [f32; 3] => 3, [f32; 4] => 4);
+float_formats!(glm::Vec2 => 2, glm::Vec3 => 3, glm::Vec4 => 4);
+
+macro_rules! integer_formats {
+    ($($scalar:ty => $data_type:ident),*) => {
+        $(integer_formats!(@impl $scalar, $data_type, 1);
+        integer_formats!(@impl [$scalar; 2], $data_type, 2);
+        integer_formats!(@impl [$scalar; 3], $data_type, 3);
+        integer_formats!(@impl [$scalar; 4], $data_type, 4);)*
+    };
+    (@impl $ty:ty, $data_type:ident, $components:expr) => {
+        impl AttribFormat for $ty {
+            const DATA_TYPE: types::VertexAttrib = types::VertexAttrib::$data_type;
+            const COMPONENTS: gl::types::GLint = $components;
+            const INTEGER: bool = true;
+        }
+    };
+}
+integer_formats!(i8 => BYTE, u8 => UBYTE, i16 => SHORT, u16 => USHORT, i32 => INT, u32 => UINT);
+
+impl AttribFormat for packing::HalfVec4 {
+    const DATA_TYPE: types::VertexAttrib = types::VertexAttrib::HALF_FLOAT;
+    const COMPONENTS: gl::types::GLint = 4;
+}
+impl AttribFormat for packing::PackedNormal {
+    const DATA_TYPE: types::VertexAttrib = types::VertexAttrib::INT_2_10_10_10_REV;
+    const COMPONENTS: gl::types::GLint = 4;
+    const NORMALIZE: bool = true;
+}
+impl AttribFormat for packing::Unorm8Vec4 {
+    const DATA_TYPE: types::VertexAttrib = types::VertexAttrib::UBYTE;
+    const COMPONENTS: gl::types::GLint = 4;
+    const NORMALIZE: bool = true;
+}
+impl AttribFormat for packing::Unorm16Vec2 {
+    const DATA_TYPE: types::VertexAttrib = types::VertexAttrib::USHORT;
+    const COMPONENTS: gl::types::GLint = 2;
+    const NORMALIZE: bool = true;
+}
+
 /// Mark a specific VBO attribute (such as position, color, etc)
 /// for passing to
-/// [`glVertexAttribPointer`](https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glVertexAttribPointer.xhtml).
+/// [`glVertexAttribPointer`](https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glVertexAttribPointer.xhtml),
+/// or `glVertexAttribIPointer` for integer attributes.
+#[derive(Copy, Clone, Debug)]
 pub struct VBOAttribMarker {
     pub name: types::ShaderAttrib,      // attribute location
     pub data_type: types::VertexAttrib, // primitive type in VBO
     pub elements_per_vertex: gl::types::GLint,
     pub normalize: gl::types::GLboolean, // normalise data
     pub offset: usize,                   // offset in bytes from start of array to first element
+    pub integer: bool,                   // read as integers (`ivec`/`uvec`) rather than floats
 }
 
 impl VBOAttribMarker {
@@ -217,6 +388,33 @@ impl VBOAttribMarker {
             elements_per_vertex,
             normalize,
             offset,
+            integer: false,
+        }
+    }
+
+    /// Mark an attribute stored as `A`, `offset` bytes into each vertex.
+    pub fn of<A: AttribFormat>(name: types::ShaderAttrib, offset: usize) -> VBOAttribMarker {
+        VBOAttribMarker {
+            name,
+            data_type: A::DATA_TYPE,
+            elements_per_vertex: A::COMPONENTS,
+            normalize: if A::NORMALIZE { gl::TRUE } else { gl::FALSE },
+            offset,
+            integer: A::INTEGER,
+        }
+    }
+
+    /// Mark an integer attribute, which shaders read as an `ivec` or `uvec` of the values
+    /// as they are, rather than converted to floats.
+    pub fn integer(
+        name: types::ShaderAttrib,
+        data_type: types::VertexAttrib,
+        elements_per_vertex: gl::types::GLint,
+        offset: usize,
+    ) -> VBOAttribMarker {
+        VBOAttribMarker {
+            integer: true,
+            ..VBOAttribMarker::new(name, data_type, elements_per_vertex, gl::FALSE, offset)
         }
     }
 }
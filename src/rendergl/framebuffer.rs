@@ -0,0 +1,124 @@
+use gl;
+
+use crate::rendergl::texture::Texture;
+
+/// Renderbuffer object.
+///
+/// Image storage which can be rendered into but not sampled, such as a depth buffer for an
+/// off-screen pass.
+pub struct Renderbuffer {
+    id: gl::types::GLuint,
+}
+
+impl Renderbuffer {
+    /// Allocate `width` by `height` pixels of storage in `internal_format`, e.g.
+    /// `gl::DEPTH_COMPONENT24`.
+    pub fn new(internal_format: gl::types::GLenum, width: i32, height: i32) -> Renderbuffer {
+        let mut id: gl::types::GLuint = 0;
+        unsafe {
+            gl::GenRenderbuffers(1, &mut id);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, internal_format, width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+        Renderbuffer { id }
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.id);
+        }
+    }
+}
+
+/// Framebuffer object.
+///
+/// Collects textures and renderbuffers as attachments to render into instead of the window.
+/// The framebuffer does not own its attachments, which must outlive any rendering into it.
+pub struct Framebuffer {
+    id: gl::types::GLuint,
+}
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        let mut id: gl::types::GLuint = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut id);
+        }
+        Framebuffer { id }
+    }
+
+    /// Render into (and read from) this framebuffer.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+    }
+
+    /// Go back to rendering into the window.
+    pub fn bind_default() {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Attach a 2D texture at `attachment`, e.g. `gl::COLOR_ATTACHMENT0`. Framebuffer **must be
+    /// bound**.
+    pub fn attach_texture(&self, attachment: gl::types::GLenum, texture: &Texture) {
+        unsafe {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment,
+                texture.target.into(),
+                texture.id,
+                0,
+            );
+        }
+    }
+
+    /// Attach a renderbuffer at `attachment`, e.g. `gl::DEPTH_ATTACHMENT`. Framebuffer **must be
+    /// bound**.
+    pub fn attach_renderbuffer(&self, attachment: gl::types::GLenum, renderbuffer: &Renderbuffer) {
+        unsafe {
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::RENDERBUFFER,
+                renderbuffer.id,
+            );
+        }
+    }
+
+    /// Select which colour attachments fragment shader outputs 0, 1, ... are written to.
+    /// Framebuffer **must be bound**.
+    pub fn draw_buffers(&self, attachments: &[gl::types::GLenum]) {
+        unsafe {
+            gl::DrawBuffers(
+                attachments.len() as gl::types::GLsizei,
+                attachments.as_ptr(),
+            );
+        }
+    }
+
+    /// Whether the attachments make up a framebuffer which can be rendered into. Framebuffer
+    /// **must be bound**.
+    pub fn is_complete(&self) -> bool {
+        unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) == gl::FRAMEBUFFER_COMPLETE }
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Framebuffer {
+        Framebuffer::new()
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
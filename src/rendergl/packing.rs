@@ -0,0 +1,127 @@
+//! Conversions to the compact attribute formats OpenGL can unpack in hardware.
+
+use glm;
+
+/// Convert to a half-precision float, for `VertexAttrib::HALF_FLOAT` attributes, rounding to
+/// the nearest. Values too large for a half become infinite.
+pub fn f32_to_half(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        // infinity stays infinite, and NaN stays NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    let (half, rest, shift) = if exponent <= 0 {
+        // too small for a normal half, so denormalise with the implicit bit
+        if exponent < -10 {
+            return sign;
+        }
+        let full = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        (full >> shift, full & ((1 << shift) - 1), shift)
+    } else {
+        (
+            ((exponent as u32) << 10) | (mantissa >> 13),
+            mantissa & 0x1fff,
+            13,
+        )
+    };
+    // round to nearest, ties to even; a carry into the exponent is still correct
+    let halfway = 1 << (shift - 1);
+    let round = rest > halfway || (rest == halfway && half & 1 == 1);
+    sign | (half + round as u32) as u16
+}
+
+/// Convert a half-precision float back to a float.
+pub fn half_to_f32(h: u16) -> f32 {
+    let sign = u32::from(h & 0x8000) << 16;
+    let exponent = u32::from((h >> 10) & 0x1f);
+    let mantissa = u32::from(h & 0x3ff);
+    match exponent {
+        0 => {
+            // zero or denormal, at 2^-24 per step
+            let magnitude = mantissa as f32 / (1 << 24) as f32;
+            if sign == 0 {
+                magnitude
+            } else {
+                -magnitude
+            }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 127 - 15) << 23) | (mantissa << 13)),
+    }
+}
+
+/// Pack a vector with components in `[-1, 1]` into `VertexAttrib::INT_2_10_10_10_REV` format,
+/// with 10 bits each for x, y and z, and 2 for w. Components outside the range are clamped.
+///
+/// The attribute should be normalised, so that shaders read it back in `[-1, 1]`.
+pub fn pack_snorm_2_10_10_10(v: glm::Vec4) -> u32 {
+    let snorm = |x: f32, max: f32, bits: u32| {
+        ((x.clamp(-1.0, 1.0) * max).round() as i32 as u32) & ((1 << bits) - 1)
+    };
+    snorm(v.x, 511.0, 10)
+        | snorm(v.y, 511.0, 10) << 10
+        | snorm(v.z, 511.0, 10) << 20
+        | snorm(v.w, 1.0, 2) << 30
+}
+
+/// Quantise a value in `[0, 1]` to 16 bits, for normalised `VertexAttrib::USHORT` attributes.
+/// Values outside the range are clamped.
+pub fn quantize_unorm16(x: f32) -> u16 {
+    (x.clamp(0.0, 1.0) * f32::from(u16::MAX)).round() as u16
+}
+
+/// Vector of half floats, for `VertexAttrib::HALF_FLOAT` attributes. Three-component vectors
+/// are padded to a multiple of 4 bytes with a fourth component of 1.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(transparent)]
+pub struct HalfVec4(pub [u16; 4]);
+impl From<glm::Vec3> for HalfVec4 {
+    fn from(v: glm::Vec3) -> HalfVec4 {
+        HalfVec4([v.x, v.y, v.z, 1.0].map(f32_to_half))
+    }
+}
+impl From<glm::Vec4> for HalfVec4 {
+    fn from(v: glm::Vec4) -> HalfVec4 {
+        HalfVec4([v.x, v.y, v.z, v.w].map(f32_to_half))
+    }
+}
+
+/// Unit vector packed with `pack_snorm_2_10_10_10`, with a w component of 0.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(transparent)]
+pub struct PackedNormal(pub u32);
+impl From<glm::Vec3> for PackedNormal {
+    fn from(v: glm::Vec3) -> PackedNormal {
+        PackedNormal(pack_snorm_2_10_10_10(v.extend(0.0)))
+    }
+}
+
+/// Pair of values quantised with `quantize_unorm16`, such as texture coordinates.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(transparent)]
+pub struct Unorm16Vec2(pub [u16; 2]);
+impl From<glm::Vec2> for Unorm16Vec2 {
+    fn from(v: glm::Vec2) -> Unorm16Vec2 {
+        Unorm16Vec2([v.x, v.y].map(quantize_unorm16))
+    }
+}
+
+/// Four values quantised to 8 bits each in `[0, 1]`, such as an RGBA colour. Values outside
+/// the range are clamped.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(transparent)]
+pub struct Unorm8Vec4(pub [u8; 4]);
+impl From<glm::Vec4> for Unorm8Vec4 {
+    fn from(v: glm::Vec4) -> Unorm8Vec4 {
+        Unorm8Vec4([v.x, v.y, v.z, v.w].map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8))
+    }
+}
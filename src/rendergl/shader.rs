@@ -16,9 +16,11 @@
 use gl;
 use std::collections::HashMap;
 use std::ffi::{CStr, CString, OsStr};
+use std::fmt;
 use std::path::{Path, PathBuf};
 
-use crate::rendergl::{uniform, UniformSet};
+use crate::rendergl::types::ShaderAttrib;
+use crate::rendergl::{uniform, UniformSet, VBOAttribMarker};
 use crate::resources::{self, ResourceLoader};
 
 /// Error enum for shaders
@@ -43,6 +45,122 @@ pub enum Error {
 /// Type alias for mapping uniform names to `GLint` identifiers.
 type UniformMap = HashMap<String, Vec<gl::types::GLint>>;
 
+/// Vertex shader input of a linked program, as reported by `glGetActiveAttrib`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveAttrib {
+    pub name: String,
+    /// Location set with `layout(location = ...)`, or -1 for built-ins such as `gl_VertexID`.
+    pub location: gl::types::GLint,
+    /// GLSL type, such as `gl::FLOAT_VEC3`.
+    pub data_type: gl::types::GLenum,
+    /// Number of array elements, 1 for attributes which are not arrays.
+    pub size: gl::types::GLint,
+}
+
+impl ActiveAttrib {
+    /// GLSL name of the type, the number of components in each location it takes up, the
+    /// number of locations (columns, for matrices), and whether it is an integer type.
+    /// Returns `None` for types with no vertex attribute equivalent here, such as doubles.
+    pub fn glsl_type(&self) -> Option<(&'static str, gl::types::GLint, gl::types::GLint, bool)> {
+        let glsl_type = match self.data_type {
+            gl::FLOAT => ("float", 1, 1, false),
+            gl::FLOAT_VEC2 => ("vec2", 2, 1, false),
+            gl::FLOAT_VEC3 => ("vec3", 3, 1, false),
+            gl::FLOAT_VEC4 => ("vec4", 4, 1, false),
+            gl::INT => ("int", 1, 1, true),
+            gl::INT_VEC2 => ("ivec2", 2, 1, true),
+            gl::INT_VEC3 => ("ivec3", 3, 1, true),
+            gl::INT_VEC4 => ("ivec4", 4, 1, true),
+            gl::UNSIGNED_INT => ("uint", 1, 1, true),
+            gl::UNSIGNED_INT_VEC2 => ("uvec2", 2, 1, true),
+            gl::UNSIGNED_INT_VEC3 => ("uvec3", 3, 1, true),
+            gl::UNSIGNED_INT_VEC4 => ("uvec4", 4, 1, true),
+            gl::FLOAT_MAT2 => ("mat2", 2, 2, false),
+            gl::FLOAT_MAT3 => ("mat3", 3, 3, false),
+            gl::FLOAT_MAT4 => ("mat4", 4, 4, false),
+            gl::FLOAT_MAT2x3 => ("mat2x3", 3, 2, false),
+            gl::FLOAT_MAT2x4 => ("mat2x4", 4, 2, false),
+            gl::FLOAT_MAT3x2 => ("mat3x2", 2, 3, false),
+            gl::FLOAT_MAT3x4 => ("mat3x4", 4, 3, false),
+            gl::FLOAT_MAT4x2 => ("mat4x2", 2, 4, false),
+            gl::FLOAT_MAT4x3 => ("mat4x3", 3, 4, false),
+            _ => return None,
+        };
+        Some(glsl_type)
+    }
+}
+
+/// Difference between the attributes a program reads and the vertex data it draws, from
+/// `Program::check_layout`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttribMismatch {
+    /// No vertex data at the attribute's location, so every vertex reads the current generic
+    /// attribute value, `(0, 0, 0, 1)` unless set otherwise.
+    Missing {
+        name: String,
+        location: gl::types::GLint,
+    },
+    /// The vertex data has fewer components than the shader reads. A missing fourth component
+    /// is 1 and is not a mismatch.
+    Components {
+        name: String,
+        location: gl::types::GLint,
+        glsl_type: &'static str,
+        components: gl::types::GLint,
+    },
+    /// The vertex data is read as integers for a float attribute, or the other way around,
+    /// which leaves the values undefined.
+    Integer {
+        name: String,
+        location: gl::types::GLint,
+        glsl_type: &'static str,
+        /// Whether the vertex data is read as integers.
+        integer: bool,
+    },
+}
+
+impl AttribMismatch {
+    /// Whether the attribute reads undefined or misplaced values, rather than a constant.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, AttribMismatch::Missing { .. })
+    }
+}
+
+impl fmt::Display for AttribMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttribMismatch::Missing { name, location } => write!(
+                f,
+                "`{}` (location {}) is not in the vertex data",
+                name, location
+            ),
+            AttribMismatch::Components {
+                name,
+                location,
+                glsl_type,
+                components,
+            } => write!(
+                f,
+                "`{}` (location {}) is a {}, but the vertex data has {} components",
+                name, location, glsl_type, components
+            ),
+            AttribMismatch::Integer {
+                name,
+                location,
+                glsl_type,
+                integer,
+            } => write!(
+                f,
+                "`{}` (location {}) is a {}, but the vertex data is read as {}",
+                name,
+                location,
+                glsl_type,
+                if *integer { "integers" } else { "floats" }
+            ),
+        }
+    }
+}
+
 /// Wraps OpenGL shader program object.
 ///
 /// Stores a handle to the openGL object for a shader program,
@@ -50,22 +168,25 @@ type UniformMap = HashMap<String, Vec<gl::types::GLint>>;
 pub struct Program {
     id: gl::types::GLuint,
     uniforms: UniformMap,
+    attributes: Vec<ActiveAttrib>,
 }
 
 impl Program {
     /// Construct a shader program from resource.
     ///
     /// Here, `name` assumes there exist vertex and fragment shaders within the resource system
-    /// called `name.vert` and `name.frag`.
+    /// called `name.vert` and `name.frag`. A geometry shader `name.geom` is also linked in if it
+    /// exists.
     ///
     /// `name` should be a relative path from the resource root.
     pub fn from_res(res: &ResourceLoader, name: &str) -> Result<Program, Error> {
-        const EXTENSIONS: [&str; 2] = ["vert", "frag"];
+        const EXTENSIONS: [&str; 3] = ["vert", "geom", "frag"];
 
         let mut resource_path = PathBuf::from(name);
 
         let shaders = EXTENSIONS
             .iter()
+            .filter(|&&ext| ext != "geom" || res.exists(&Path::new(name).with_extension(ext)))
             .map(|ext| {
                 resource_path.set_extension(ext);
                 Shader::from_res(&res, &resource_path.as_path())
@@ -121,8 +242,10 @@ impl Program {
         let mut program = Program {
             id: program_id,
             uniforms: HashMap::new(),
+            attributes: Vec::new(),
         };
         program.discover_uniforms()?;
+        program.discover_attributes();
         Ok(program)
     }
 
@@ -178,6 +301,101 @@ impl Program {
         self.set_uniform_by_index(name, data, 0)
     }
 
+    /// OpenGL handle of the program.
+    pub fn id(&self) -> gl::types::GLuint {
+        self.id
+    }
+
+    /// Attributes the vertex shader reads. Attributes which are declared but unused are
+    /// optimised away, and are not listed.
+    pub fn attributes(&self) -> &[ActiveAttrib] {
+        &self.attributes
+    }
+
+    /// Whether the vertex shader reads the attribute at `attrib`'s location, e.g. whether it
+    /// needs normals.
+    pub fn uses_attrib(&self, attrib: ShaderAttrib) -> bool {
+        let location = attrib as gl::types::GLint;
+        self.attributes.iter().any(|a| {
+            let locations = a.glsl_type().map_or(1, |(_, _, columns, _)| columns) * a.size;
+            a.location <= location && location < a.location + locations
+        })
+    }
+
+    /// Compare the attributes the program reads with vertex data laid out as `markers`.
+    pub fn check_layout(&self, markers: &[VBOAttribMarker]) -> Vec<AttribMismatch> {
+        let mut mismatches = Vec::new();
+        for attrib in self.attributes.iter().filter(|a| a.location >= 0) {
+            let (glsl_type, components, columns, integer) = match attrib.glsl_type() {
+                Some(glsl_type) => glsl_type,
+                None => continue,
+            };
+            // matrices and arrays take up consecutive locations
+            for location in attrib.location..attrib.location + columns * attrib.size {
+                let name = attrib.name.clone();
+                let marker = markers
+                    .iter()
+                    .find(|m| m.name as gl::types::GLint == location);
+                let mismatch = match marker {
+                    None => AttribMismatch::Missing { name, location },
+                    Some(m) if m.integer != integer => AttribMismatch::Integer {
+                        name,
+                        location,
+                        glsl_type,
+                        integer: m.integer,
+                    },
+                    Some(m)
+                        if m.elements_per_vertex < components
+                            && !(m.elements_per_vertex == 3 && components == 4) =>
+                    {
+                        AttribMismatch::Components {
+                            name,
+                            location,
+                            glsl_type,
+                            components: m.elements_per_vertex,
+                        }
+                    }
+                    Some(_) => continue,
+                };
+                mismatches.push(mismatch);
+            }
+        }
+        mismatches
+    }
+
+    fn discover_attributes(&mut self) {
+        let mut attrib_count: gl::types::GLint = 0;
+        unsafe {
+            gl::GetProgramiv(self.id, gl::ACTIVE_ATTRIBUTES, &mut attrib_count);
+        }
+        for i in 0..attrib_count {
+            let buffer_size: gl::types::GLsizei = 256;
+            let mut name_length: gl::types::GLsizei = 0;
+            let mut size: gl::types::GLint = 0;
+            let mut data_type: gl::types::GLenum = 0;
+            let name = alloc_nul_cstring(buffer_size as usize);
+            let location = unsafe {
+                gl::GetActiveAttrib(
+                    self.id,
+                    i as u32,
+                    buffer_size,
+                    &mut name_length,
+                    &mut size,
+                    &mut data_type,
+                    name.as_ptr() as *mut gl::types::GLchar,
+                );
+                gl::GetAttribLocation(self.id, name.as_ptr())
+            };
+            let name = String::from_utf8_lossy(&name.as_bytes()[..name_length as usize]);
+            self.attributes.push(ActiveAttrib {
+                name: name.into_owned(),
+                location,
+                data_type,
+                size,
+            });
+        }
+    }
+
     fn discover_uniforms(&mut self) -> Result<(), String> {
         let mut uniform_count: gl::types::GLint = 0;
         self.bind();
@@ -258,8 +476,11 @@ pub struct Shader {
 impl Shader {
     /// Load shader source from resource.
     pub fn from_res(res: &ResourceLoader, path: &Path) -> Result<Shader, Error> {
-        const EXT_TYPES: [(&str, gl::types::GLenum); 2] =
-            [("vert", gl::VERTEX_SHADER), ("frag", gl::FRAGMENT_SHADER)];
+        const EXT_TYPES: [(&str, gl::types::GLenum); 3] = [
+            ("vert", gl::VERTEX_SHADER),
+            ("geom", gl::GEOMETRY_SHADER),
+            ("frag", gl::FRAGMENT_SHADER),
+        ];
 
         let name = path.to_str().ok_or(Error::EncodingError)?;
         let path_ext = path.extension().unwrap_or(OsStr::new(""));
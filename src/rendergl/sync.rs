@@ -0,0 +1,37 @@
+use gl;
+
+/// Fence sync object.
+///
+/// Marks a point in the GL command stream, so that the CPU can find out when the GPU has
+/// finished everything issued before it without stalling.
+pub struct Fence {
+    sync: gl::types::GLsync,
+}
+
+impl Fence {
+    /// Insert a fence after all commands issued so far.
+    pub fn new() -> Fence {
+        let sync = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        Fence { sync }
+    }
+
+    /// Whether the GPU has passed the fence. Never blocks.
+    pub fn is_signaled(&self) -> bool {
+        let status = unsafe { gl::ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, 0) };
+        status == gl::ALREADY_SIGNALED || status == gl::CONDITION_SATISFIED
+    }
+}
+
+impl Default for Fence {
+    fn default() -> Fence {
+        Fence::new()
+    }
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSync(self.sync);
+        }
+    }
+}
@@ -199,6 +199,43 @@ impl Texture {
         tex
     }
 
+    /// Initialise an uninitialised `Texture2D` of `width` by `height` pixels, e.g. to render into.
+    ///
+    /// `format` and `pixel_type` must be compatible with `internal_format` even though no pixel
+    /// data is uploaded; for example `gl::RGBA_INTEGER` and `gl::UNSIGNED_INT` for
+    /// `gl::RGBA32UI`. The texture is sampled with `Nearest` filtering.
+    pub fn empty(
+        internal_format: gl::types::GLenum,
+        format: gl::types::GLenum,
+        pixel_type: gl::types::GLenum,
+        width: i32,
+        height: i32,
+    ) -> Texture {
+        let tex = Texture::texture_2d();
+
+        tex.bind();
+        unsafe {
+            gl::TexImage2D(
+                tex.target.into(),
+                0,
+                internal_format as gl::types::GLint,
+                width,
+                height,
+                0,
+                format,
+                pixel_type,
+                std::ptr::null(),
+            );
+        }
+        tex.unbind();
+
+        TextureParameters::new()
+            .filter_method(FilterMethod::Nearest)
+            .wrap_method2d(WrapMethod::ClampToEdge)
+            .apply_to(&tex);
+        tex
+    }
+
     /// Construct a cubemap from a collection of 6 `DynamicImage`s.
     ///
     /// Returns:
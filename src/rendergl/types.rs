@@ -55,7 +55,7 @@ impl From<TextureTarget> for gl::types::GLenum {
     }
 }
 
-/// Enumerate possible `GLenum` variants for representing triangle layouts.
+/// Enumerate possible `GLenum` variants for representing primitive layouts.
 ///
 /// Refer to: [OpenGL docs](https://www.khronos.org/opengl/wiki/Primitive#Triangle_primitives)
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -65,6 +65,7 @@ pub enum GlLayout {
     TriangleStrip = gl::TRIANGLE_STRIP,
     TriangleFan = gl::TRIANGLE_FAN,
     LineStrip = gl::LINE_STRIP,
+    Points = gl::POINTS,
 }
 impl From<GlLayout> for gl::types::GLenum {
     fn from(item: GlLayout) -> gl::types::GLenum {
@@ -119,6 +120,7 @@ impl From<ShaderAttrib> for gl::types::GLuint {
 /// [`glVertexAttribPointer`](https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glVertexAttribPointer.xhtml).
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u32)]
+#[allow(non_camel_case_types)]
 pub enum VertexAttrib {
     BYTE = gl::BYTE,
     UBYTE = gl::UNSIGNED_BYTE,
@@ -126,10 +128,42 @@ pub enum VertexAttrib {
     USHORT = gl::UNSIGNED_SHORT,
     INT = gl::INT,
     UINT = gl::UNSIGNED_INT,
+    HALF_FLOAT = gl::HALF_FLOAT,
     FLOAT = gl::FLOAT,
+    /// Three signed 10-bit components and a 2-bit one packed into 32 bits, in reverse order.
+    INT_2_10_10_10_REV = gl::INT_2_10_10_10_REV,
+    UINT_2_10_10_10_REV = gl::UNSIGNED_INT_2_10_10_10_REV,
 }
 impl From<VertexAttrib> for gl::types::GLenum {
     fn from(item: VertexAttrib) -> gl::types::GLenum {
         item as gl::types::GLenum
     }
 }
+
+/// Enumerate accepted types of indices for
+/// [`glDrawElements`](https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glDrawElements.xhtml).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum IndexType {
+    UBYTE = gl::UNSIGNED_BYTE,
+    USHORT = gl::UNSIGNED_SHORT,
+    UINT = gl::UNSIGNED_INT,
+}
+impl IndexType {
+    /// Smallest type that can hold every index up to `max_index`. Byte indices are only chosen
+    /// if `allow_bytes` is set, since many drivers convert them on the CPU at draw time.
+    pub fn for_max(max_index: u32, allow_bytes: bool) -> IndexType {
+        if allow_bytes && max_index <= u32::from(u8::MAX) {
+            IndexType::UBYTE
+        } else if max_index <= u32::from(u16::MAX) {
+            IndexType::USHORT
+        } else {
+            IndexType::UINT
+        }
+    }
+}
+impl From<IndexType> for gl::types::GLenum {
+    fn from(item: IndexType) -> gl::types::GLenum {
+        item as gl::types::GLenum
+    }
+}
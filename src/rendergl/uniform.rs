@@ -122,3 +122,26 @@ impl UniformSet for glm::Mat4 {
         }
     }
 }
+
+/// A uniform value whose type is only known at runtime.
+///
+/// Useful for storing heterogeneous uniforms together, such as the values produced by an
+/// animation that drives several uniforms of a program.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UniformValue {
+    Float(f32),
+    Vec2(glm::Vec2),
+    Vec3(glm::Vec3),
+    Vec4(glm::Vec4),
+}
+
+impl UniformSet for UniformValue {
+    fn set_uniform_gl(&self, loc: gl::types::GLint) {
+        match self {
+            UniformValue::Float(v) => v.set_uniform_gl(loc),
+            UniformValue::Vec2(v) => v.set_uniform_gl(loc),
+            UniformValue::Vec3(v) => v.set_uniform_gl(loc),
+            UniformValue::Vec4(v) => v.set_uniform_gl(loc),
+        }
+    }
+}
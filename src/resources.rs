@@ -5,12 +5,23 @@
 use std;
 use std::ffi::{CString, NulError};
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 
+use gltf;
 use image;
 use tobj;
 
+use crate::animation::AnimationClip;
+use crate::shape::MeshData;
+
+mod clip;
+mod obj;
+mod ply;
+mod stl;
+
+pub use self::obj::{ObjColors, ObjFile};
+
 /// Error types for resource loading.
 #[derive(Debug, Fail)]
 pub enum Error {
@@ -20,6 +31,16 @@ pub enum Error {
     DecodeError(#[cause] image::ImageError),
     #[fail(display = "OBJ Load Error")]
     ObjLoadError(String),
+    #[fail(display = "glTF Load Error")]
+    GltfLoadError(String),
+    #[fail(display = "PLY Load Error: {}", _0)]
+    PlyLoadError(String),
+    #[fail(display = "STL Load Error: {}", _0)]
+    StlLoadError(String),
+    #[fail(display = "STL Save Error: {}", _0)]
+    StlSaveError(String),
+    #[fail(display = "Animation Clip Load Error: {}", _0)]
+    ClipLoadError(String),
     #[fail(display = "Failed to get executable path")]
     CurrentExeNotFound,
 }
@@ -48,6 +69,26 @@ impl From<tobj::LoadError> for Error {
     }
 }
 
+impl From<gltf::Error> for Error {
+    fn from(other: gltf::Error) -> Self {
+        Error::GltfLoadError(other.to_string())
+    }
+}
+
+/// A glTF document with the contents of its buffers and images, as returned by `gltf::import`.
+pub type GltfImport = (
+    gltf::Document,
+    Vec<gltf::buffer::Data>,
+    Vec<gltf::image::Data>,
+);
+
+/// Encoding of a mesh file format with both text and binary variants, such as PLY and STL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Ascii,
+    Binary,
+}
+
 /// Safely loads data from a resource path root.
 pub struct ResourceLoader {
     path_root: PathBuf,
@@ -69,6 +110,11 @@ impl ResourceLoader {
         })
     }
 
+    /// Whether a resource file named `resource_name` exists.
+    pub fn exists(&self, resource_name: &Path) -> bool {
+        self.path_root.join(resource_name).is_file()
+    }
+
     /// Load a resource file named `resource_name` under the `ResourceLoader`'s
     /// assets root directory.
     pub fn load_cstring(&self, resource_name: &Path) -> Result<CString, Error> {
@@ -86,6 +132,45 @@ impl ResourceLoader {
         tobj::load_obj(&self.path_root.join(resource_name)).map_err(|e| e.into())
     }
 
+    /// Load an OBJ model like `load_obj`, along with the vertex colours and face sizes which
+    /// `tobj` drops. The file is read only once, for all of them.
+    pub fn load_obj_with_colors(&self, resource_name: &Path) -> Result<ObjFile, Error> {
+        let path = self.path_root.join(resource_name);
+        let data = fs::read(&path)?;
+        let (models, materials) = tobj::load_obj_buf(&mut io::Cursor::new(&data), |mtl| {
+            tobj::load_mtl(&path.parent().map_or(mtl.to_owned(), |dir| dir.join(mtl)))
+        })?;
+        Ok(ObjFile {
+            face_sizes: obj::face_sizes(&data, &models),
+            colors: ObjColors::parse(&data),
+            models,
+            materials,
+        })
+    }
+
+    /// Load a glTF 2.0 model, either a `.gltf` file or a binary `.glb`. Wraps `gltf::import`,
+    /// which resolves buffers and images that are embedded, stored in the binary chunk, or in
+    /// files relative to the model.
+    pub fn load_gltf(&self, resource_name: &Path) -> Result<GltfImport, Error> {
+        gltf::import(self.path_root.join(resource_name)).map_err(|e| e.into())
+    }
+
+    /// Load a PLY mesh or point cloud, in ASCII or binary format, with per-vertex normals,
+    /// colours and texture coordinates where it has them.
+    pub fn load_ply(&self, resource_name: &Path) -> Result<MeshData, Error> {
+        ply::parse(&fs::read(self.path_root.join(resource_name))?)
+    }
+
+    /// Load an STL mesh, in ASCII or binary format, with facet colours where it has them.
+    pub fn load_stl(&self, resource_name: &Path) -> Result<MeshData, Error> {
+        stl::parse(&fs::read(self.path_root.join(resource_name))?)
+    }
+
+    /// Load an animation clip, in the line-based format described in the `clip` module.
+    pub fn load_clip(&self, resource_name: &Path) -> Result<AnimationClip, Error> {
+        clip::parse(&fs::read_to_string(self.path_root.join(resource_name))?)
+    }
+
     /// Load an image `resource_name` under the `ResourceLoader` root assets directory.
     pub fn load_image(&self, resource_name: &Path) -> Result<image::DynamicImage, Error> {
         image::open(self.path_root.join(resource_name)).map_err(|e| e.into())
@@ -101,3 +186,45 @@ impl std::fmt::Display for ResourceLoader {
         )
     }
 }
+
+impl MeshData {
+    /// Save as a Wavefront OBJ file at `path`. A `material` is written to an MTL library next
+    /// to it, with the same file stem.
+    pub fn save_obj(&self, path: &Path, material: Option<&tobj::Material>) -> Result<(), Error> {
+        let mut w = BufWriter::new(fs::File::create(path)?);
+        match material {
+            Some(material) => {
+                let mtl_path = path.with_extension("mtl");
+                let mut mtl = BufWriter::new(fs::File::create(&mtl_path)?);
+                obj::write_mtl(material, &mut mtl)?;
+                mtl.flush()?;
+                let library = mtl_path.file_name().unwrap_or_default().to_string_lossy();
+                obj::write(self, &mut w, Some((&library, material)))?;
+            }
+            None => obj::write(self, &mut w, None)?,
+        }
+        // flush here, since dropping the writer would swallow a failed final write
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Save as a PLY file at `path`, with the vertex attributes the mesh has.
+    pub fn save_ply(&self, path: &Path, encoding: Encoding) -> Result<(), Error> {
+        let mut w = BufWriter::new(fs::File::create(path)?);
+        ply::write(self, &mut w, encoding)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Save the triangles as an STL file at `path`. Point clouds cannot be saved as STL, and
+    /// meshes without triangles fail without creating the file.
+    pub fn save_stl(&self, path: &Path, encoding: Encoding) -> Result<(), Error> {
+        if self.triangles().next().is_none() {
+            return Err(Error::StlSaveError("mesh has no triangles".into()));
+        }
+        let mut w = BufWriter::new(fs::File::create(path)?);
+        stl::write(self, &mut w, encoding)?;
+        w.flush()?;
+        Ok(())
+    }
+}
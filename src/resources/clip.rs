@@ -0,0 +1,159 @@
+//! Reader for animation clips, a line-based text format for `AnimationClip`s.
+//!
+//! ```text
+//! # comments run to the end of the line
+//! playback ping_pong
+//!
+//! translation cubic
+//! 0.0   0.0 0.2 0.0
+//! 1.0   0.0 1.2 0.0
+//!
+//! rotation linear
+//! 0.0   0 1 0   0
+//! 1.0   0 1 0   90
+//!
+//! uniform cDiffuse vec3 step
+//! 0.0   1 0 0
+//! 1.0   0 0 1
+//! ```
+//!
+//! `playback` is `once` (the default), `loop` or `ping_pong`. Each track starts with a header
+//! naming what it animates and its interpolation (`step`, `linear` or `cubic`): `translation`,
+//! `rotation`, `scale`, or `uniform` followed by the uniform's name and type (`float`, `vec2`,
+//! `vec3` or `vec4`). The lines after it are its keyframes: a time in seconds, then the value.
+//! Rotations are an axis and an angle in degrees, and consecutive rotation keys should be less
+//! than half a turn apart, since rotations interpolate along the shortest arc.
+
+use glm;
+
+use crate::animation::{AnimationClip, Interpolation, Playback, Track, UniformTrack};
+use crate::resources::Error;
+use crate::util::Quat;
+
+/// Track being read, until the next header.
+enum Pending {
+    Translation(Track<glm::Vec3>),
+    Rotation(Track<Quat>),
+    Scale(Track<glm::Vec3>),
+    Uniform(String, UniformTrack),
+}
+
+impl Pending {
+    fn add_to(self, clip: AnimationClip) -> AnimationClip {
+        match self {
+            Pending::Translation(track) => clip.translation(track),
+            Pending::Rotation(track) => clip.rotation(track),
+            Pending::Scale(track) => clip.scale(track),
+            Pending::Uniform(name, track) => clip.uniform(&name, track),
+        }
+    }
+
+    /// Add a keyframe with the values following its time, if there are the right number.
+    fn key(self, time: f32, v: &[f32]) -> Result<Pending, Pending> {
+        Ok(match (self, v.len()) {
+            (Pending::Translation(track), 3) => {
+                Pending::Translation(track.key(time, glm::vec3(v[0], v[1], v[2])))
+            }
+            (Pending::Rotation(track), 4) => {
+                let rotation =
+                    Quat::from_axis_angle(glm::vec3(v[0], v[1], v[2]), v[3].to_radians());
+                Pending::Rotation(track.key(time, rotation))
+            }
+            (Pending::Scale(track), 3) => {
+                Pending::Scale(track.key(time, glm::vec3(v[0], v[1], v[2])))
+            }
+            (Pending::Uniform(name, UniformTrack::Float(track)), 1) => {
+                Pending::Uniform(name, UniformTrack::Float(track.key(time, v[0])))
+            }
+            (Pending::Uniform(name, UniformTrack::Vec2(track)), 2) => Pending::Uniform(
+                name,
+                UniformTrack::Vec2(track.key(time, glm::vec2(v[0], v[1]))),
+            ),
+            (Pending::Uniform(name, UniformTrack::Vec3(track)), 3) => Pending::Uniform(
+                name,
+                UniformTrack::Vec3(track.key(time, glm::vec3(v[0], v[1], v[2]))),
+            ),
+            (Pending::Uniform(name, UniformTrack::Vec4(track)), 4) => Pending::Uniform(
+                name,
+                UniformTrack::Vec4(track.key(time, glm::vec4(v[0], v[1], v[2], v[3]))),
+            ),
+            (pending, _) => return Err(pending),
+        })
+    }
+}
+
+/// Parse an animation clip.
+pub fn parse(text: &str) -> Result<AnimationClip, Error> {
+    let error = |line: usize, message: &str| {
+        Error::ClipLoadError(format!("line {}: {}", line + 1, message))
+    };
+
+    let mut playback = Playback::Once;
+    let mut tracks: Vec<Pending> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let interpolation =
+            |mode: &str| parse_interpolation(mode).ok_or_else(|| error(i, "unknown interpolation"));
+        let line = line.split('#').next().unwrap_or("");
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let pending = match words.as_slice() {
+            [] => continue,
+            ["playback", mode] => {
+                playback = match *mode {
+                    "once" => Playback::Once,
+                    "loop" => Playback::Loop,
+                    "ping_pong" => Playback::PingPong,
+                    _ => return Err(error(i, "unknown playback mode")),
+                };
+                continue;
+            }
+            ["translation", mode] => Pending::Translation(Track::new(interpolation(mode)?)),
+            ["rotation", mode] => Pending::Rotation(Track::new(interpolation(mode)?)),
+            ["scale", mode] => Pending::Scale(Track::new(interpolation(mode)?)),
+            ["uniform", name, kind, mode] => {
+                let mode = interpolation(mode)?;
+                let track = match *kind {
+                    "float" => UniformTrack::Float(Track::new(mode)),
+                    "vec2" => UniformTrack::Vec2(Track::new(mode)),
+                    "vec3" => UniformTrack::Vec3(Track::new(mode)),
+                    "vec4" => UniformTrack::Vec4(Track::new(mode)),
+                    _ => return Err(error(i, "unknown uniform type")),
+                };
+                Pending::Uniform(name.to_string(), track)
+            }
+            [time, values @ ..] => {
+                let time: f32 = time
+                    .parse()
+                    .map_err(|_| error(i, "expected a track header"))?;
+                let values: Vec<f32> = values
+                    .iter()
+                    .map(|v| v.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| error(i, "malformed keyframe"))?;
+                let track = tracks
+                    .pop()
+                    .ok_or_else(|| error(i, "keyframe before any track"))?;
+                let track = track
+                    .key(time, &values)
+                    .map_err(|_| error(i, "wrong number of values for the track"))?;
+                tracks.push(track);
+                continue;
+            }
+        };
+        tracks.push(pending);
+    }
+
+    Ok(tracks
+        .into_iter()
+        .fold(AnimationClip::new(playback), |clip, track| {
+            track.add_to(clip)
+        }))
+}
+
+fn parse_interpolation(mode: &str) -> Option<Interpolation> {
+    match mode {
+        "step" => Some(Interpolation::Step),
+        "linear" => Some(Interpolation::Linear),
+        "cubic" => Some(Interpolation::Cubic),
+        _ => None,
+    }
+}
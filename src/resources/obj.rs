@@ -0,0 +1,193 @@
+//! Writer for Wavefront OBJ meshes and their MTL materials. OBJ files are read with `tobj`,
+//! apart from their vertex colours and face sizes.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use glm;
+use tobj;
+
+use crate::shape::{vec3_key, MeshData};
+
+/// An OBJ file loaded by `ResourceLoader::load_obj_with_colors`.
+pub struct ObjFile {
+    pub models: Vec<tobj::Model>,
+    pub materials: Vec<tobj::Material>,
+    pub colors: ObjColors,
+    /// Number of vertices of each face of each model, from the `f` lines which `tobj`
+    /// triangulates, to rebuild polygons for `MeshData::subdivide_catmull_clark`.
+    pub face_sizes: Vec<Vec<u32>>,
+}
+
+/// Vertex colours of an OBJ file, given after positions (`v x y z r g b`), by position.
+///
+/// `tobj` drops the colours, and splits and renumbers vertices, so they are matched back to
+/// its meshes by position. Positions given more than once keep their last colour.
+#[derive(Clone, Debug, Default)]
+pub struct ObjColors {
+    by_position: HashMap<[u32; 3], glm::Vec4>,
+}
+
+impl ObjColors {
+    /// Read the colours of an OBJ file's vertices, with an optional alpha after the blue
+    /// channel. Lines `tobj` cannot read are skipped here, and reported when it loads them.
+    pub fn parse(data: &[u8]) -> ObjColors {
+        let mut by_position = HashMap::new();
+        for line in String::from_utf8_lossy(data).lines() {
+            let mut words = line.split_whitespace();
+            if words.next() != Some("v") {
+                continue;
+            }
+            let values: Vec<f32> = match words.map(str::parse).collect() {
+                Ok(values) => values,
+                Err(_) => continue,
+            };
+            if values.len() >= 6 {
+                let alpha = values.get(6).cloned().unwrap_or(1.0);
+                by_position.insert(
+                    vec3_key(glm::vec3(values[0], values[1], values[2])),
+                    glm::vec4(values[3], values[4], values[5], alpha),
+                );
+            }
+        }
+        ObjColors { by_position }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_position.is_empty()
+    }
+
+    /// Colour the vertices of `mesh` by their positions, white where a position has no colour.
+    pub fn apply(&self, mesh: &mut MeshData) {
+        let white = glm::vec4(1.0, 1.0, 1.0, 1.0);
+        mesh.colors = mesh
+            .positions
+            .iter()
+            .map(|&p| self.by_position.get(&vec3_key(p)).cloned().unwrap_or(white))
+            .collect();
+    }
+}
+
+/// Sizes of the faces of each of `models`, read from the `f` lines of their OBJ file.
+///
+/// `tobj` splits the faces into models in file order without saying where, so each model takes
+/// the next faces until they add up to its triangles. Faces of fewer than three vertices,
+/// which `tobj` gives no triangles, are left out.
+pub fn face_sizes(data: &[u8], models: &[tobj::Model]) -> Vec<Vec<u32>> {
+    let text = String::from_utf8_lossy(data);
+    let mut sizes = text
+        .lines()
+        .map(|line| line.split_whitespace())
+        .filter_map(|mut words| match words.next() {
+            Some("f") => Some(words.count() as u32),
+            _ => None,
+        })
+        .filter(|&n| n >= 3);
+    models
+        .iter()
+        .map(|model| {
+            let triangles = model.mesh.indices.len() / 3;
+            let mut model_sizes = Vec::new();
+            let mut taken = 0;
+            while taken < triangles {
+                match sizes.next() {
+                    Some(n) => {
+                        taken += n as usize - 2;
+                        model_sizes.push(n);
+                    }
+                    None => break,
+                }
+            }
+            model_sizes
+        })
+        .collect()
+}
+
+/// Write `mesh` as OBJ, using `material` from the material library `mtllib` if given.
+///
+/// Vertex colours are written after positions (`v x y z r g b`), an extension which many tools
+/// read. Faces refer to texture coordinates and normals only if the mesh has them.
+pub fn write<W: Write>(
+    mesh: &MeshData,
+    w: &mut W,
+    mtllib: Option<(&str, &tobj::Material)>,
+) -> io::Result<()> {
+    writeln!(
+        w,
+        "# {} vertices, {} triangles",
+        mesh.positions.len(),
+        mesh.indices.len() / 3
+    )?;
+    if let Some((library, material)) = mtllib {
+        writeln!(w, "mtllib {}", library)?;
+        writeln!(w, "usemtl {}", material_name(material))?;
+    }
+
+    for (i, p) in mesh.positions.iter().enumerate() {
+        match mesh.colors.get(i) {
+            Some(c) => writeln!(w, "v {} {} {} {} {} {}", p.x, p.y, p.z, c.x, c.y, c.z)?,
+            None => writeln!(w, "v {} {} {}", p.x, p.y, p.z)?,
+        }
+    }
+    for t in &mesh.texcoords {
+        writeln!(w, "vt {} {}", t.x, t.y)?;
+    }
+    for n in &mesh.normals {
+        writeln!(w, "vn {} {} {}", n.x, n.y, n.z)?;
+    }
+
+    let (uvs, normals) = (!mesh.texcoords.is_empty(), !mesh.normals.is_empty());
+    if mesh.is_point_cloud() {
+        // OBJ has no point cloud element of its own, but a point per vertex is close
+        for i in 1..=mesh.positions.len() {
+            writeln!(w, "p {}", i)?;
+        }
+    }
+    for triangle in mesh.triangles() {
+        // OBJ indices start at 1
+        let corner = |i: u32| match (uvs, normals) {
+            (true, true) => format!("{0}/{0}/{0}", i + 1),
+            (true, false) => format!("{0}/{0}", i + 1),
+            (false, true) => format!("{0}//{0}", i + 1),
+            (false, false) => format!("{}", i + 1),
+        };
+        let [a, b, c] = triangle;
+        writeln!(w, "f {} {} {}", corner(a), corner(b), corner(c))?;
+    }
+    Ok(())
+}
+
+/// Write `material` as an MTL material library.
+pub fn write_mtl<W: Write>(material: &tobj::Material, w: &mut W) -> io::Result<()> {
+    let [ka, kd, ks] = [material.ambient, material.diffuse, material.specular];
+    writeln!(w, "newmtl {}", material_name(material))?;
+    writeln!(w, "Ka {} {} {}", ka[0], ka[1], ka[2])?;
+    writeln!(w, "Kd {} {} {}", kd[0], kd[1], kd[2])?;
+    writeln!(w, "Ks {} {} {}", ks[0], ks[1], ks[2])?;
+    writeln!(w, "Ns {}", material.shininess)?;
+    writeln!(w, "Ni {}", material.optical_density)?;
+    writeln!(w, "d {}", material.dissolve)?;
+    if let Some(illum) = material.illumination_model {
+        writeln!(w, "illum {}", illum)?;
+    }
+    let maps = [
+        ("map_Ka", &material.ambient_texture),
+        ("map_Kd", &material.diffuse_texture),
+        ("map_Ks", &material.specular_texture),
+        ("map_Bump", &material.normal_texture),
+        ("map_d", &material.dissolve_texture),
+    ];
+    for (name, texture) in maps.iter().filter(|(_, texture)| !texture.is_empty()) {
+        writeln!(w, "{} {}", name, texture)?;
+    }
+    Ok(())
+}
+
+/// Name of `material`, which must not be empty in an MTL file.
+fn material_name(material: &tobj::Material) -> &str {
+    if material.name.is_empty() {
+        "default"
+    } else {
+        &material.name
+    }
+}
@@ -0,0 +1,394 @@
+//! Reader and writer for PLY (Polygon File Format) meshes and point clouds, in ASCII or binary.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use glm;
+
+use super::{Encoding, Error};
+use crate::shape::MeshData;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar, Error> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid(format!("unknown property type '{}'", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Scale mapping the type's range onto `[0, 1]`, for colour channels.
+    fn unit_scale(self) -> f64 {
+        match self {
+            Scalar::I8 => f64::from(i8::MAX),
+            Scalar::U8 => f64::from(u8::MAX),
+            Scalar::I16 => f64::from(i16::MAX),
+            Scalar::U16 => f64::from(u16::MAX),
+            Scalar::I32 => f64::from(i32::MAX),
+            Scalar::U32 => f64::from(u32::MAX),
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+enum Property {
+    Scalar(Scalar),
+    /// A list, prefixed with its length.
+    List {
+        count: Scalar,
+        item: Scalar,
+    },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, Property)>,
+}
+
+/// Source of property values, in the file's format.
+struct Values<'a> {
+    format: Format,
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Values<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, Error> {
+        if self.format == Format::Ascii {
+            return self.read_ascii();
+        }
+
+        let size = scalar.size();
+        let bytes = self
+            .data
+            .get(self.position..self.position + size)
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        self.position += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = buf;
+        Ok(match scalar {
+            Scalar::I8 => f64::from(b0 as i8),
+            Scalar::U8 => f64::from(b0),
+            Scalar::I16 => f64::from(i16::from_le_bytes([b0, b1])),
+            Scalar::U16 => f64::from(u16::from_le_bytes([b0, b1])),
+            Scalar::I32 => f64::from(i32::from_le_bytes([b0, b1, b2, b3])),
+            Scalar::U32 => f64::from(u32::from_le_bytes([b0, b1, b2, b3])),
+            Scalar::F32 => f64::from(f32::from_le_bytes([b0, b1, b2, b3])),
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+
+    fn read_ascii(&mut self) -> Result<f64, Error> {
+        let rest = &self.data[self.position..];
+        let start = rest
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        let len = rest[start..]
+            .iter()
+            .position(|b| b.is_ascii_whitespace())
+            .unwrap_or(rest.len() - start);
+        self.position += start + len;
+        let token = String::from_utf8_lossy(&rest[start..start + len]);
+        token
+            .parse()
+            .map_err(|_| invalid(format!("invalid number '{}'", token)))
+    }
+}
+
+/// Parse a PLY file.
+///
+/// Reads vertex positions, along with normals (`nx`, `ny`, `nz`), colours (`red`, `green`,
+/// `blue` and optionally `alpha`, scaled to `[0, 1]` from integer types) and texture
+/// coordinates (`s`/`t`, `u`/`v` or `texture_u`/`texture_v`) if every vertex has them. Polygons
+/// of the `face` element are triangulated as fans. Other elements and properties are skipped.
+pub fn parse(data: &[u8]) -> Result<MeshData, Error> {
+    let (format, elements, body) = parse_header(data)?;
+    let mut values = Values {
+        format,
+        data: &data[body..],
+        position: 0,
+    };
+
+    let mut mesh = MeshData::default();
+    for element in &elements {
+        let mut columns: HashMap<&str, Vec<f64>> = HashMap::new();
+        let mut faces: Vec<Vec<u32>> = Vec::new();
+        for _ in 0..element.count {
+            for (name, property) in &element.properties {
+                match *property {
+                    Property::Scalar(scalar) => {
+                        let value = values.read(scalar)?;
+                        columns
+                            .entry(name)
+                            .or_default()
+                            .push(value / color_scale(name, scalar));
+                    }
+                    Property::List { count, item } => {
+                        let count = values.read(count)? as usize;
+                        let list = (0..count)
+                            .map(|_| values.read(item))
+                            .collect::<Result<Vec<f64>, Error>>()?;
+                        if name == "vertex_indices" || name == "vertex_index" {
+                            faces.push(list.into_iter().map(|i| i as u32).collect());
+                        }
+                    }
+                }
+            }
+        }
+
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut mesh, &columns)?,
+            "face" => {
+                for face in faces {
+                    for i in 1..face.len().saturating_sub(1) {
+                        mesh.indices.extend(&[face[0], face[i], face[i + 1]]);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let count = mesh.positions.len() as u32;
+    if let Some(&i) = mesh.indices.iter().find(|&&i| i >= count) {
+        return Err(invalid(format!("vertex index {} out of range", i)));
+    }
+    Ok(mesh)
+}
+
+/// Write `mesh` as PLY, with whichever of normals, texture coordinates (as `s`, `t`) and
+/// colours (as `uchar`) it has. Binary files are little-endian.
+pub fn write<W: Write>(mesh: &MeshData, w: &mut W, encoding: Encoding) -> io::Result<()> {
+    let count = mesh.positions.len();
+    let normals = mesh.normals.len() == count;
+    let uvs = mesh.texcoords.len() == count;
+    let colors = mesh.colors.len() == count;
+
+    writeln!(w, "ply")?;
+    match encoding {
+        Encoding::Ascii => writeln!(w, "format ascii 1.0")?,
+        Encoding::Binary => writeln!(w, "format binary_little_endian 1.0")?,
+    }
+    writeln!(w, "element vertex {}", count)?;
+    let mut properties = vec!["x", "y", "z"];
+    if normals {
+        properties.extend(&["nx", "ny", "nz"]);
+    }
+    if uvs {
+        properties.extend(&["s", "t"]);
+    }
+    for property in &properties {
+        writeln!(w, "property float {}", property)?;
+    }
+    if colors {
+        for channel in &["red", "green", "blue", "alpha"] {
+            writeln!(w, "property uchar {}", channel)?;
+        }
+    }
+    if !mesh.is_point_cloud() {
+        writeln!(w, "element face {}", mesh.indices.len() / 3)?;
+        writeln!(w, "property list uchar int vertex_indices")?;
+    }
+    writeln!(w, "end_header")?;
+
+    for i in 0..count {
+        let (p, mut floats) = (mesh.positions[i], Vec::with_capacity(8));
+        floats.extend(&[p.x, p.y, p.z]);
+        if normals {
+            let n = mesh.normals[i];
+            floats.extend(&[n.x, n.y, n.z]);
+        }
+        if uvs {
+            let t = mesh.texcoords[i];
+            floats.extend(&[t.x, t.y]);
+        }
+        let bytes: Vec<u8> = if colors {
+            let c = mesh.colors[i];
+            [c.x, c.y, c.z, c.w]
+                .iter()
+                .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        match encoding {
+            Encoding::Ascii => {
+                let mut fields: Vec<String> = floats.iter().map(f32::to_string).collect();
+                fields.extend(bytes.iter().map(u8::to_string));
+                writeln!(w, "{}", fields.join(" "))?;
+            }
+            Encoding::Binary => {
+                for f in &floats {
+                    w.write_all(&f.to_le_bytes())?;
+                }
+                w.write_all(&bytes)?;
+            }
+        }
+    }
+
+    for [a, b, c] in mesh.triangles() {
+        match encoding {
+            Encoding::Ascii => writeln!(w, "3 {} {} {}", a, b, c)?,
+            Encoding::Binary => {
+                w.write_all(&[3])?;
+                for i in &[a, b, c] {
+                    w.write_all(&(*i as i32).to_le_bytes())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse the header, returning the format, the elements it declares and the offset of the body.
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), Error> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut offset = 0;
+    let mut lines = data.split(|&b| b == b'\n');
+
+    match lines.next() {
+        Some(line) if line.trim_ascii() == b"ply" => offset += line.len() + 1,
+        _ => return Err(invalid("missing 'ply' magic number")),
+    }
+    for line in lines {
+        offset += line.len() + 1;
+        let line = String::from_utf8_lossy(line);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => {
+                let format = format.ok_or_else(|| invalid("missing format"))?;
+                return Ok((format, elements, offset.min(data.len())));
+            }
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid(format!("unknown format '{}'", name))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid element count '{}'", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let property = Property::List {
+                    count: Scalar::parse(count)?,
+                    item: Scalar::parse(item)?,
+                };
+                push_property(&mut elements, name, property)?;
+            }
+            ["property", scalar, name] => {
+                let property = Property::Scalar(Scalar::parse(scalar)?);
+                push_property(&mut elements, name, property)?;
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid(format!("invalid header line '{}'", line.trim()))),
+        }
+    }
+    Err(invalid("missing 'end_header'"))
+}
+
+fn push_property(elements: &mut [Element], name: &str, property: Property) -> Result<(), Error> {
+    let element = elements
+        .last_mut()
+        .ok_or_else(|| invalid("property declared before any element"))?;
+    element.properties.push((name.to_string(), property));
+    Ok(())
+}
+
+/// Colour channels are scaled to `[0, 1]`; other properties are read as they are.
+fn color_scale(name: &str, scalar: Scalar) -> f64 {
+    match name {
+        "red" | "green" | "blue" | "alpha" => scalar.unit_scale(),
+        _ => 1.0,
+    }
+}
+
+fn read_vertices(mesh: &mut MeshData, columns: &HashMap<&str, Vec<f64>>) -> Result<(), Error> {
+    let find = |names: &[&str]| find_columns(columns, names);
+    let vec3 = |c: &[&[f64]], i: usize| glm::vec3(c[0][i] as f32, c[1][i] as f32, c[2][i] as f32);
+
+    let xyz = find(&["x", "y", "z"]).ok_or_else(|| invalid("vertices have no position"))?;
+    let count = xyz[0].len();
+    mesh.positions = (0..count).map(|i| vec3(&xyz, i)).collect();
+
+    if let Some(n) = find(&["nx", "ny", "nz"]) {
+        mesh.normals = (0..count).map(|i| vec3(&n, i)).collect();
+    }
+    if let Some(rgb) = find(&["red", "green", "blue"]) {
+        let alpha = columns.get("alpha");
+        mesh.colors = (0..count)
+            .map(|i| vec3(&rgb, i).extend(alpha.map_or(1.0, |a| a[i] as f32)))
+            .collect();
+    }
+    let uv = find(&["s", "t"])
+        .or_else(|| find(&["u", "v"]))
+        .or_else(|| find(&["texture_u", "texture_v"]));
+    if let Some(uv) = uv {
+        mesh.texcoords = (0..count)
+            .map(|i| glm::vec2(uv[0][i] as f32, uv[1][i] as f32))
+            .collect();
+    }
+    Ok(())
+}
+
+/// All of the columns `names`, if the element has every one of them.
+fn find_columns<'a>(
+    columns: &'a HashMap<&str, Vec<f64>>,
+    names: &[&str],
+) -> Option<Vec<&'a [f64]>> {
+    names
+        .iter()
+        .map(|&name| columns.get(name).map(|c| &c[..]))
+        .collect()
+}
+
+fn invalid<S: Into<String>>(message: S) -> Error {
+    Error::PlyLoadError(message.into())
+}
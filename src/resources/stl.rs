@@ -0,0 +1,208 @@
+//! Reader and writer for STL (stereolithography) triangle meshes, in ASCII or binary.
+
+use std::io::{self, Write};
+
+use glm;
+
+use super::{Encoding, Error};
+use crate::shape::MeshData;
+
+/// Parse an STL file.
+///
+/// STL stores each triangle separately, so the mesh has three vertices of its own per triangle,
+/// with the facet normal (or the normal of the triangle's winding, if the file leaves it zero).
+/// Binary files may colour their facets: colours are read with the Materialise convention if
+/// the header declares `COLOR=`, and with the VisCAM/SolidView convention otherwise.
+pub fn parse(data: &[u8]) -> Result<MeshData, Error> {
+    // ASCII files start with "solid", but so do some binary ones; the size tells them apart
+    let binary_size = data
+        .get(80..84)
+        .map(|n| 84 + 50 * u32::from_le_bytes([n[0], n[1], n[2], n[3]]) as usize);
+    if binary_size == Some(data.len()) {
+        parse_binary(data)
+    } else if data.trim_ascii_start().starts_with(b"solid") {
+        parse_ascii(&String::from_utf8_lossy(data))
+    } else {
+        Err(invalid("neither an ASCII nor a binary STL file"))
+    }
+}
+
+fn parse_ascii(text: &str) -> Result<MeshData, Error> {
+    let mut mesh = MeshData::default();
+    let mut words = text.split_whitespace();
+    let read_vec3 = |words: &mut std::str::SplitWhitespace| -> Result<glm::Vec3, Error> {
+        let mut v = [0.0; 3];
+        for x in &mut v {
+            let word = words
+                .next()
+                .ok_or_else(|| invalid("unexpected end of file"))?;
+            *x = word
+                .parse()
+                .map_err(|_| invalid(format!("invalid number '{}'", word)))?;
+        }
+        Ok(glm::vec3(v[0], v[1], v[2]))
+    };
+
+    let mut normal = glm::vec3(0.0, 0.0, 0.0);
+    let mut corners: Vec<glm::Vec3> = Vec::with_capacity(3);
+    while let Some(word) = words.next() {
+        match word {
+            "facet" => {
+                if words.next() != Some("normal") {
+                    return Err(invalid("expected 'normal' after 'facet'"));
+                }
+                normal = read_vec3(&mut words)?;
+                corners.clear();
+            }
+            "vertex" => corners.push(read_vec3(&mut words)?),
+            "endfacet" => {
+                if corners.len() != 3 {
+                    return Err(invalid("facet does not have three vertices"));
+                }
+                push_facet(&mut mesh, normal, [corners[0], corners[1], corners[2]]);
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+fn parse_binary(data: &[u8]) -> Result<MeshData, Error> {
+    // Materialise files give a default colour in the header, used by facets without their own.
+    // Headers are free-form bytes, so search them as bytes rather than text
+    let default_color = data[..80]
+        .windows(6)
+        .position(|w| w == b"COLOR=")
+        .and_then(|i| {
+            let rgba = data.get(i + 6..i + 10)?;
+            Some(glm::vec4(
+                f32::from(rgba[0]) / 255.0,
+                f32::from(rgba[1]) / 255.0,
+                f32::from(rgba[2]) / 255.0,
+                f32::from(rgba[3]) / 255.0,
+            ))
+        });
+
+    let mut mesh = MeshData::default();
+    let mut colors: Vec<Option<glm::Vec4>> = Vec::new();
+    for facet in data[84..].chunks(50) {
+        let f = |i: usize| {
+            let b = &facet[i * 4..i * 4 + 4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        };
+        let v = |i: usize| glm::vec3(f(i), f(i + 1), f(i + 2));
+        push_facet(&mut mesh, v(0), [v(3), v(6), v(9)]);
+
+        let attribute = u16::from_le_bytes([facet[48], facet[49]]);
+        let channel = |shift: u16| f32::from((attribute >> shift) & 0x1f) / 31.0;
+        colors.push(match default_color {
+            // Materialise: red in the low bits, valid when bit 15 is clear
+            Some(_) if attribute & 0x8000 == 0 => {
+                Some(glm::vec4(channel(0), channel(5), channel(10), 1.0))
+            }
+            Some(default) => Some(default),
+            // VisCAM/SolidView: blue in the low bits, valid when bit 15 is set
+            None if attribute & 0x8000 != 0 => {
+                Some(glm::vec4(channel(10), channel(5), channel(0), 1.0))
+            }
+            None => None,
+        });
+    }
+
+    // facets without a colour of their own are white
+    if colors.iter().any(Option::is_some) {
+        let white = glm::vec4(1.0, 1.0, 1.0, 1.0);
+        mesh.colors = colors
+            .into_iter()
+            .flat_map(|c| vec![c.unwrap_or(white); 3])
+            .collect();
+    }
+    Ok(mesh)
+}
+
+/// Write the triangles of `mesh` as STL, with the normal of each triangle's winding.
+///
+/// Binary files colour facets with the average of their vertex colours, using the
+/// VisCAM/SolidView convention, if the mesh has colours.
+pub fn write<W: Write>(mesh: &MeshData, w: &mut W, encoding: Encoding) -> io::Result<()> {
+    let triangles: Vec<[u32; 3]> = mesh.triangles().collect();
+    let corners = |t: &[u32; 3]| t.map(|i| mesh.positions[i as usize]);
+    let normal = |[a, b, c]: [glm::Vec3; 3]| {
+        let n = glm::cross(b - a, c - a);
+        if glm::length(n) > 0.0 {
+            glm::normalize(n)
+        } else {
+            n
+        }
+    };
+
+    match encoding {
+        Encoding::Ascii => {
+            writeln!(w, "solid mesh")?;
+            for t in &triangles {
+                let corners = corners(t);
+                let n = normal(corners);
+                writeln!(w, "  facet normal {} {} {}", n.x, n.y, n.z)?;
+                writeln!(w, "    outer loop")?;
+                for v in &corners {
+                    writeln!(w, "      vertex {} {} {}", v.x, v.y, v.z)?;
+                }
+                writeln!(w, "    endloop")?;
+                writeln!(w, "  endfacet")?;
+            }
+            writeln!(w, "endsolid mesh")?;
+        }
+        Encoding::Binary => {
+            // the header must not start with "solid", which would mark an ASCII file
+            let mut header = [b' '; 80];
+            header[..6].copy_from_slice(b"binary");
+            w.write_all(&header)?;
+            w.write_all(&(triangles.len() as u32).to_le_bytes())?;
+            let colored = mesh.colors.len() == mesh.positions.len();
+            for t in &triangles {
+                let corners = corners(t);
+                let n = normal(corners);
+                for v in [n].iter().chain(&corners) {
+                    for x in &[v.x, v.y, v.z] {
+                        w.write_all(&x.to_le_bytes())?;
+                    }
+                }
+
+                let attribute = if colored {
+                    let c = t.iter().fold(glm::vec4(0.0, 0.0, 0.0, 0.0), |c, &i| {
+                        c + mesh.colors[i as usize]
+                    }) / 3.0;
+                    let channel = |x: f32| (x.clamp(0.0, 1.0) * 31.0).round() as u16;
+                    0x8000 | channel(c.x) << 10 | channel(c.y) << 5 | channel(c.z)
+                } else {
+                    0
+                };
+                w.write_all(&attribute.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn push_facet(mesh: &mut MeshData, normal: glm::Vec3, corners: [glm::Vec3; 3]) {
+    let [a, b, c] = corners;
+    let normal = if glm::length(normal) > 0.0 {
+        glm::normalize(normal)
+    } else {
+        let n = glm::cross(b - a, c - a);
+        if glm::length(n) > 0.0 {
+            glm::normalize(n)
+        } else {
+            n
+        }
+    };
+    for &corner in &corners {
+        mesh.indices.push(mesh.positions.len() as u32);
+        mesh.positions.push(corner);
+        mesh.normals.push(normal);
+    }
+}
+
+fn invalid<S: Into<String>>(message: S) -> Error {
+    Error::StlLoadError(message.into())
+}
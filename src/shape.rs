@@ -3,13 +3,17 @@
 //! The `shape` module exposes a `Drawable` Trait, defines a wrapper for OpenGL buffer data
 //! representing a shape, and implements a few `Drawable` shapes (Cylinder, Sphere).
 //!
+//! `MeshData` holds mesh vertices and triangles on the CPU, where they can be inspected or
+//! exported. It can generate a number of primitives: spheres, cylinders, cubes, planes, disks,
+//! cones, tori, capsules and icospheres. `ShapeGL` uploads them in any vertex layout.
+//!
 //! # Examples
 //!
 //! Generate buffer data for a cylinder with 10 radial slices and 3 vertical strips. Draw
 //! the shape.
 //! ```rust
-//! let cylinder_data = ShapeGL::cylinder(3, 10);
-//! cylinder_data.draw_vertices();
+//! let cylinder_data = ShapeGL::cylinder::<VertexN>(3, 10);
+//! cylinder_data.draw_vertices(&program)?;
 //! ```
 //!
 //! Construct a unit `Sphere` and draw it:
@@ -17,22 +21,46 @@
 //! let shader: rendergl::Program; // load a shader
 //! let sphere = Sphere::new(&shader, 50, 50);
 //! ```
+use std::cell::RefCell;
+
+use gl;
 use glm::ext::consts;
 use tobj;
 
+use crate::animation::Pose;
 use crate::camera::Camera;
+use crate::picking::{Hit, Ray};
 use crate::rendergl::types::*;
 use crate::rendergl::{self, uniform};
 use crate::resources;
-use crate::util::SurfacePoint;
-
+use crate::util::{Aabb, SurfacePoint};
+
+mod halfedge;
+mod lod;
+mod meshdata;
+mod normals;
+mod optimize;
+mod parametric;
+mod primitives;
 mod quad;
 mod shadershape;
+mod simplify;
 mod skybox;
-
+mod subdivide;
+mod validate;
+
+pub use self::halfedge::{HalfEdgeMesh, TopologyIssue};
+pub use self::lod::LodGroup;
+pub(crate) use self::meshdata::vec3_key;
+pub use self::meshdata::{strip_to_triangles, MeshData};
+pub use self::normals::Shading;
+pub use self::optimize::{CacheStats, OptimizeStats};
+pub use self::parametric::ParametricSurface;
 pub use self::quad::Quad;
 pub use self::shadershape::ShaderShape;
 pub use self::skybox::Skybox;
+pub use self::subdivide::PolygonReport;
+pub use self::validate::ValidationReport;
 
 #[derive(Debug, Fail)]
 pub enum DrawError {
@@ -41,6 +69,10 @@ pub enum DrawError {
         #[cause]
         inner: uniform::Error,
     },
+    #[fail(display = "Framebuffer is incomplete")]
+    FramebufferError,
+    #[fail(display = "Vertex layout does not match the program: {}", message)]
+    LayoutError { message: String },
 }
 impl From<uniform::Error> for DrawError {
     fn from(other: uniform::Error) -> Self {
@@ -72,16 +104,48 @@ pub trait Drawable {
     }
     fn tick(&mut self) {}
     fn draw(&self, camera: &Camera) -> Result<(), DrawError>;
+
+    /// Apply an animated `Pose`, such as the model transform and uniforms sampled from an
+    /// `AnimationClip`. Does nothing by default.
+    fn apply_pose(&mut self, _pose: &Pose) {}
+
+    /// Closest point at which a world-space `ray` hits this object, for picking. Objects which
+    /// cannot be picked return `None`, which is the default.
+    fn ray_cast(&self, _ray: &Ray) -> Option<Hit> {
+        None
+    }
+
+    /// Draw the object's triangles with `program`, for ID-buffer picking (see
+    /// `picking::IdPicker`). The program is already bound and has its camera uniforms set;
+    /// implementations set the `model` transform, and `submesh` to the index of each sub-mesh
+    /// they draw. Objects which should not be picked draw nothing.
+    fn draw_ids(&self, program: &rendergl::Program) -> Result<(), DrawError>;
+
+    /// World-space bounds of the object, for framing the camera. Objects without a meaningful
+    /// extent, such as a skybox, return `None`, which is the default.
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
 }
 
 /// Owner of OpenGL handles for VBO, IBO, VAO.
 ///
 /// Associates given vertex data with a permutation of indices defining drawing order,
 /// constructs an internal VAO handle, and exposes a method for drawing its data.
+///
+/// Vertex data is usually interleaved in one VBO. Shapes built with `from_streams` keep
+/// positions in a VBO of their own, which `update_positions` replaces for deformation, and
+/// shapes given instances with `with_instances` draw once per instance.
 pub struct ShapeGL {
-    _vbo: rendergl::VBO,
+    /// Vertex streams, the first holding positions alone if `split_positions`, and the last
+    /// holding per-instance data if any was given.
+    vbos: Vec<rendergl::VBO>,
+    split_positions: bool,
     ibo: rendergl::IBO,
     vao: rendergl::VAO,
+    bounds: Aabb,
+    /// Programs whose missing attributes have been reported, so they are only reported once.
+    reported: RefCell<Vec<gl::types::GLuint>>,
 }
 
 impl ShapeGL {
@@ -109,19 +173,171 @@ impl ShapeGL {
     ///
     /// let shape = ShapeGL::new(&vertex_data, &index_data, layout);
     /// ```
+    ///
+    /// In debug builds, triangles are validated (see `MeshData::validate`) and any problems
+    /// are printed.
     pub fn new<T: rendergl::Vertex>(
         vertex_data: &[T],
         indices: &[u32],
         layout: GlLayout,
     ) -> ShapeGL {
+        if cfg!(debug_assertions) {
+            ShapeGL::report_problems(vertex_data, indices, layout);
+        }
         let vbo = rendergl::VBO::from_data(vertex_data);
         let ibo = rendergl::IBO::from_data(indices);
         let vao = rendergl::VAO::new(&vbo, Some(&ibo), layout);
+        let bounds = Aabb::from_points(&rendergl::vertex_positions(vertex_data));
         ShapeGL {
-            _vbo: vbo,
+            vbos: vec![vbo],
+            split_positions: false,
+            ibo,
+            vao,
+            bounds,
+            reported: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Upload `data` with positions in a `VertexP` stream of their own, and the other
+    /// attributes in the layout `T` (e.g. `StreamNT`). Point clouds are drawn as
+    /// `GlLayout::Points`.
+    ///
+    /// Positions can then be replaced with `update_positions`, such as for a deforming mesh,
+    /// without uploading the other attributes again. Fails if `T` has a position too.
+    pub fn from_streams<T: rendergl::Vertex>(
+        data: &MeshData,
+    ) -> Result<ShapeGL, rendergl::StreamError> {
+        rendergl::VAO::check_streams(&[
+            &<rendergl::VertexP as rendergl::Vertex>::vertex_attrib_markers(),
+            &T::vertex_attrib_markers(),
+        ])?;
+        let positions: Vec<rendergl::VertexP> = data.positions.iter().map(|&p| p.into()).collect();
+        let attributes: Vec<T> = (0..data.positions.len())
+            .map(|i| T::from_point_color(&data.point(i), data.color(i)))
+            .collect();
+        let (indices, layout) = if data.is_point_cloud() {
+            let points: Vec<u32> = (0..data.positions.len() as u32).collect();
+            (points, GlLayout::Points)
+        } else {
+            (data.indices.clone(), GlLayout::Triangles)
+        };
+        if cfg!(debug_assertions) {
+            ShapeGL::report_problems(&positions, &indices, layout);
+        }
+
+        let vbos = vec![
+            rendergl::VBO::from_data(&positions),
+            rendergl::VBO::from_data(&attributes),
+        ];
+        let ibo = rendergl::IBO::from_data(&indices);
+        let vao = rendergl::VAO::with_streams(&[&vbos[0], &vbos[1]], Some(&ibo), layout)?;
+        Ok(ShapeGL {
+            vbos,
+            split_positions: true,
             ibo,
             vao,
+            bounds: Aabb::from_points(&data.positions),
+            reported: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Replace the positions of a shape built with `from_streams`, one for each of its
+    /// vertices. The bounds are updated to match.
+    ///
+    /// # Panics
+    ///
+    /// If the shape keeps its positions interleaved with other attributes, or the number of
+    /// positions differs.
+    pub fn update_positions(&mut self, positions: &[glm::Vec3]) {
+        assert!(
+            self.split_positions,
+            "positions can only be updated in shapes built with ShapeGL::from_streams"
+        );
+        assert_eq!(
+            positions.len(),
+            self.vbos[0].len(),
+            "positions must be updated for every vertex"
+        );
+        let data: Vec<rendergl::VertexP> = positions.iter().map(|&p| p.into()).collect();
+        self.vbos[0].update(&data);
+        self.bounds = Aabb::from_points(positions);
+    }
+
+    /// Draw the shape once for each of `instances`, a per-instance stream in the layout `T`
+    /// (e.g. `InstanceOffset`), replacing any given before. Fails if `T` has an attribute the
+    /// vertices already have.
+    pub fn with_instances<T: rendergl::Vertex>(
+        mut self,
+        instances: &[T],
+    ) -> Result<ShapeGL, rendergl::StreamError> {
+        if self.has_instances() {
+            self.vbos.pop();
         }
+        let instance_markers = T::vertex_attrib_markers();
+        let mut layouts: Vec<&[rendergl::VBOAttribMarker]> =
+            self.vbos.iter().map(|vbo| vbo.markers()).collect();
+        layouts.push(&instance_markers);
+        rendergl::VAO::check_streams(&layouts)?;
+
+        self.vbos
+            .push(rendergl::VBO::from_data(instances).with_divisor(1));
+        let streams: Vec<&rendergl::VBO> = self.vbos.iter().collect();
+        self.vao = rendergl::VAO::with_streams(&streams, Some(&self.ibo), self.vao.layout())?;
+        Ok(self)
+    }
+
+    /// Replace the per-instance data given with `with_instances`, which may change the number
+    /// of instances but not their layout `T`.
+    ///
+    /// # Panics
+    ///
+    /// If the shape has no instances.
+    pub fn update_instances<T: rendergl::Vertex>(&mut self, instances: &[T]) {
+        assert!(
+            self.has_instances(),
+            "instances can only be updated in shapes built with ShapeGL::with_instances"
+        );
+        if let Some(vbo) = self.vbos.last_mut() {
+            vbo.update(instances);
+        }
+        self.vao.set_instances(Some(instances.len()));
+    }
+
+    fn has_instances(&self) -> bool {
+        self.vbos.last().is_some_and(|vbo| vbo.divisor() != 0)
+    }
+
+    /// Validate the triangles of vertex data with a triangle layout, printing any problems.
+    fn report_problems<T: rendergl::Vertex>(vertex_data: &[T], indices: &[u32], layout: GlLayout) {
+        let indices = match layout {
+            GlLayout::Triangles => indices.to_vec(),
+            GlLayout::TriangleStrip => strip_to_triangles(indices),
+            GlLayout::TriangleFan => indices
+                .windows(2)
+                .skip(1)
+                .flat_map(|pair| vec![indices[0], pair[0], pair[1]])
+                .collect(),
+            _ => return,
+        };
+        let positions = rendergl::vertex_positions(vertex_data);
+        if positions.len() != vertex_data.len() {
+            // without positions, there is nothing to check the indices against
+            return;
+        }
+        let data = MeshData {
+            positions,
+            indices,
+            ..MeshData::default()
+        };
+        let report = data.validate();
+        if !report.is_valid() {
+            println!("Invalid mesh: {}", report);
+        }
+    }
+
+    /// Object-space bounds of the vertex data.
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
     }
 
     /// Construct a new `ShapeGL` from `tobj` mesh data.
@@ -202,26 +418,115 @@ impl ShapeGL {
         }
     }
 
-    /// Draw vertex data using internal VAO and IBO.
-    pub fn draw_vertices(&self) {
+    /// Upload `data` in the vertex layout `T`, built from each vertex's `MeshData::point` and
+    /// `MeshData::color`. Point clouds are drawn as `GlLayout::Points`.
+    pub fn from_data<T: rendergl::Vertex>(data: &MeshData) -> ShapeGL {
+        let vertex_data: Vec<T> = (0..data.positions.len())
+            .map(|i| T::from_point_color(&data.point(i), data.color(i)))
+            .collect();
+        if data.is_point_cloud() {
+            let points: Vec<u32> = (0..vertex_data.len() as u32).collect();
+            ShapeGL::new(&vertex_data, &points, GlLayout::Points)
+        } else {
+            ShapeGL::new(&vertex_data, &data.indices, GlLayout::Triangles)
+        }
+    }
+
+    /// Draw vertex data using internal VAO and IBO, with `program`, which must be bound.
+    ///
+    /// In debug builds, the vertex layout is first checked against the attributes `program`
+    /// reads (see `Program::check_layout`). Attributes read with the wrong type fail with
+    /// `DrawError::LayoutError`; attributes missing from the vertex data read a constant
+    /// instead, and are only reported.
+    pub fn draw_vertices(&self, program: &rendergl::Program) -> Result<(), DrawError> {
+        if cfg!(debug_assertions) {
+            self.check_layout(program)?;
+        }
         self.vao.bind();
         self.ibo.bind();
         self.vao.draw();
         self.ibo.unbind();
         self.vao.unbind();
+        Ok(())
+    }
+
+    fn check_layout(&self, program: &rendergl::Program) -> Result<(), DrawError> {
+        let markers: Vec<rendergl::VBOAttribMarker> = self
+            .vbos
+            .iter()
+            .flat_map(|vbo| vbo.markers().iter().cloned())
+            .collect();
+        let mismatches = program.check_layout(&markers);
+        let (errors, missing): (Vec<_>, Vec<_>) = mismatches.iter().partition(|m| m.is_error());
+        if !errors.is_empty() {
+            let messages: Vec<String> = errors.iter().map(|m| m.to_string()).collect();
+            return Err(DrawError::LayoutError {
+                message: messages.join("; "),
+            });
+        }
+        let mut reported = self.reported.borrow_mut();
+        if !missing.is_empty() && !reported.contains(&program.id()) {
+            reported.push(program.id());
+            for m in missing {
+                println!("Vertex layout: {}", m);
+            }
+        }
+        Ok(())
     }
 }
 
+/// Generators for primitives, uploaded in the vertex layout `T`. See the `MeshData` generator
+/// of the same name for each primitive's shape and arguments.
 impl ShapeGL {
+    pub fn sphere<T: rendergl::Vertex>(lat_strips: u32, lon_slices: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::sphere(lat_strips, lon_slices))
+    }
+
+    pub fn cylinder<T: rendergl::Vertex>(strips: u32, slices: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::cylinder(strips, slices))
+    }
+
+    pub fn cube<T: rendergl::Vertex>(subdivisions: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::cube(subdivisions))
+    }
+
+    pub fn plane<T: rendergl::Vertex>(x_cells: u32, z_cells: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::plane(x_cells, z_cells))
+    }
+
+    pub fn disk<T: rendergl::Vertex>(rings: u32, slices: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::disk(rings, slices))
+    }
+
+    pub fn cone<T: rendergl::Vertex>(strips: u32, slices: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::cone(strips, slices))
+    }
+
+    pub fn torus<T: rendergl::Vertex>(rings: u32, slices: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::torus(rings, slices))
+    }
+
+    pub fn capsule<T: rendergl::Vertex>(cap_strips: u32, side_strips: u32, slices: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::capsule(cap_strips, side_strips, slices))
+    }
+
+    pub fn icosphere<T: rendergl::Vertex>(subdivisions: u32) -> ShapeGL {
+        ShapeGL::from_data::<T>(&MeshData::icosphere(subdivisions))
+    }
+}
+
+impl MeshData {
     /// Generate vertices for a unit sphere (unit diameter).
     ///
     /// # Arguments
     ///
     /// * `lat_strips`: number of subdivisions in latitude (vertical lod)
     /// * `lon_strips`: number of subdivisions in longitude (horizontal lod)
-    pub fn sphere<T: rendergl::Vertex>(lat_strips: u32, lon_slices: u32) -> ShapeGL {
-        let mut vert_data: Vec<T> = Vec::new();
-        let mut index_data: Vec<u32> = Vec::new();
+    ///
+    /// A sphere has at least 2 strips and 3 slices; fewer are rounded up.
+    pub fn sphere(lat_strips: u32, lon_slices: u32) -> MeshData {
+        let (lat_strips, lon_slices) = (lat_strips.max(2), lon_slices.max(3));
+        let mut data = MeshData::default();
 
         const R: f32 = SurfacePoint::R;
         let pi: f32 = consts::pi();
@@ -235,50 +540,52 @@ impl ShapeGL {
             for phi_step in 0..(lat_strips + 1) {
                 let phi = lat_stepsz * (phi_step as f32);
                 let p = SurfacePoint::Sphere { r: R, theta, phi };
-                vert_data.push(T::from_point3d(&p));
+                data.push_point(&p);
             }
         }
 
-        let nvert = vert_data.len() as u32;
+        let nvert = data.positions.len() as u32;
 
         // generate indices
         for slice in 0..lon_slices {
             let istart = slice * (lat_strips + 1);
-            index_data.push(istart);
-            index_data.push(istart + 1);
-            index_data.push((istart + lat_strips + 2) % nvert);
+            data.indices.push(istart);
+            data.indices.push(istart + 1);
+            data.indices.push((istart + lat_strips + 2) % nvert);
 
             for strip in 1..(lat_strips - 1) {
                 let i = istart + strip;
-                index_data.push(i);
-                index_data.push((i + lat_strips + 2) % nvert);
-                index_data.push((i + lat_strips + 1) % nvert);
+                data.indices.push(i);
+                data.indices.push((i + lat_strips + 2) % nvert);
+                data.indices.push((i + lat_strips + 1) % nvert);
 
-                index_data.push(i);
-                index_data.push(i + 1);
-                index_data.push((i + lat_strips + 2) % nvert)
+                data.indices.push(i);
+                data.indices.push(i + 1);
+                data.indices.push((i + lat_strips + 2) % nvert)
             }
 
             let i = istart + lat_strips - 1;
-            index_data.push(i);
-            index_data.push(i + 1);
-            index_data.push((i + lat_strips + 1) % nvert);
+            data.indices.push(i);
+            data.indices.push(i + 1);
+            data.indices.push((i + lat_strips + 1) % nvert);
         }
 
-        ShapeGL::new(&vert_data, &index_data, GlLayout::Triangles)
+        data
     }
 }
 
-impl ShapeGL {
+impl MeshData {
     /// Generate vertices for a unit cylinder (unit diameter, unit height).
     ///
     /// # Arguments
     ///
     /// * `strips`: number of vertical subdivisions
     /// * `slices`: number of radial subdivisions
-    pub fn cylinder<T: rendergl::Vertex>(strips: u32, slices: u32) -> ShapeGL {
-        let mut vert_data: Vec<T> = Vec::new();
-        let mut index_data: Vec<u32> = Vec::new();
+    ///
+    /// A cylinder has at least 1 strip and 3 slices; fewer are rounded up.
+    pub fn cylinder(strips: u32, slices: u32) -> MeshData {
+        let (strips, slices) = (strips.max(1), slices.max(3));
+        let mut data = MeshData::default();
 
         const R: f32 = SurfacePoint::R;
         let pi: f32 = consts::pi();
@@ -295,7 +602,7 @@ impl ShapeGL {
                 let r = r_stepsz * (r_step as f32);
 
                 let p = SurfacePoint::Disk { r, theta, y: R };
-                vert_data.push(T::from_point3d(&p));
+                data.push_point(&p);
             }
 
             // slice side
@@ -303,7 +610,7 @@ impl ShapeGL {
                 let y = R - side_stepsz * (y_step as f32);
 
                 let p = SurfacePoint::Cylinder { r: R, theta, y };
-                vert_data.push(T::from_point3d(&p));
+                data.push_point(&p);
             }
 
             // bottom cap slice
@@ -311,37 +618,31 @@ impl ShapeGL {
                 let r = r_stepsz * (r_step as f32);
 
                 let p = SurfacePoint::Disk { r, theta, y: -R };
-                vert_data.push(T::from_point3d(&p));
+                data.push_point(&p);
             }
         }
 
-        let nvert = vert_data.len() as u32;
-
-        // generate indices
+        // generate indices, joining each slice to the next across the top cap, the side and
+        // the bottom cap in turn
         let stride = 3 * (strips + 1); // each slice has `stride` vertices in it
         for slice in 0..slices {
-            let istart = slice * stride;
-            index_data.push(istart);
-            index_data.push(istart + 1);
-            index_data.push((istart + stride + 1) % nvert);
-
-            for step in 1..(stride - 1) {
-                let i = istart + step;
-                index_data.push(i);
-                index_data.push(i + 1);
-                index_data.push((i + stride) % nvert);
-
-                index_data.push(i + 1);
-                index_data.push((i + stride + 1) % nvert);
-                index_data.push((i + stride) % nvert);
+            let (istart, inext) = (slice * stride, ((slice + 1) % slices) * stride);
+            for section in 0..3 {
+                for step in 0..strips {
+                    let i = section * (strips + 1) + step;
+                    let (a, b) = (istart + i, istart + i + 1);
+                    let (c, d) = (inext + i, inext + i + 1);
+                    // the caps meet at their centres, where half of each quad has no area
+                    if !(section == 0 && step == 0) {
+                        data.indices.extend_from_slice(&[a, b, c]);
+                    }
+                    if !(section == 2 && step == strips - 1) {
+                        data.indices.extend_from_slice(&[b, d, c]);
+                    }
+                }
             }
-
-            let i = istart + stride - 1;
-            index_data.push(i);
-            index_data.push(i + 1);
-            index_data.push((i + stride) % nvert);
         }
 
-        ShapeGL::new(&vert_data, &index_data, GlLayout::Triangles)
+        data
     }
 }
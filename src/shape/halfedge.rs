@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+
+use crate::rendergl;
+use crate::shape::{MeshData, ShapeGL};
+
+/// One side of an edge, running from its `origin` vertex around a face (or around a hole in the
+/// mesh, for half-edges on a border).
+#[derive(Copy, Clone, Debug)]
+struct HalfEdge {
+    origin: u32,
+    face: Option<usize>,
+    next: usize,
+    prev: usize,
+    twin: usize,
+    removed: bool,
+}
+
+/// Problem with the triangles a `HalfEdgeMesh` was built from. Faces with problems are left out
+/// of the mesh.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TopologyIssue {
+    /// A triangle with a repeated vertex.
+    DegenerateFace { face: usize },
+    /// A triangle on an edge which already has a triangle on either side.
+    NonManifoldEdge { face: usize, from: u32, to: u32 },
+    /// A triangle wound the other way to the triangle it shares an edge with.
+    FlippedFace { face: usize, from: u32, to: u32 },
+    /// A vertex where separate fans of triangles meet, such as the tip shared by two cones.
+    NonManifoldVertex { vertex: u32 },
+}
+
+/// Half-edge mesh of triangles, for adjacency queries and local edits such as edge flips and
+/// collapses.
+///
+/// Each edge is a pair of twin half-edges, one on each side, and each face is a loop of three
+/// half-edges. Half-edges on open borders have no face, and run around the hole in loops of
+/// their own. Half-edges are numbered, and faces and vertices keep the indices they had in the
+/// `MeshData` the mesh was built from. Vertices are taken as they are, so vertices split on
+/// attribute seams are not connected, and the seams are borders.
+///
+/// `MeshData::simplify`, the subdivisions and `MeshData::validate` keep adjacency of their own:
+/// they join vertices across attribute seams, work on polygons, or count the faces this mesh
+/// leaves out.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let mut mesh = HalfEdgeMesh::new(&MeshData::sphere(10, 10));
+/// for neighbour in mesh.neighbours(0) { /* ... */ }
+/// let shape = mesh.shapegl::<VertexN>();
+/// ```
+pub struct HalfEdgeMesh {
+    /// Vertex attributes; the indices are those of the faces the mesh was built from.
+    data: MeshData,
+    half_edges: Vec<HalfEdge>,
+    /// An outgoing half-edge of each vertex, on the border if the vertex is on one.
+    vertex_edge: Vec<Option<usize>>,
+    vertex_removed: Vec<bool>,
+    /// A half-edge of each face, or `None` for faces which were left out or removed.
+    face_edge: Vec<Option<usize>>,
+    issues: Vec<TopologyIssue>,
+}
+
+impl HalfEdgeMesh {
+    /// Build a half-edge mesh from the triangles of `data`. Triangles which cannot be part of
+    /// a manifold mesh are left out, and reported by `issues`.
+    pub fn new(data: &MeshData) -> HalfEdgeMesh {
+        let mut mesh = HalfEdgeMesh {
+            data: data.clone(),
+            half_edges: Vec::new(),
+            vertex_edge: vec![None; data.positions.len()],
+            vertex_removed: vec![false; data.positions.len()],
+            face_edge: Vec::new(),
+            issues: Vec::new(),
+        };
+
+        let mut directed: HashMap<(u32, u32), usize> = HashMap::new();
+        for (face, [a, b, c]) in data.triangles().enumerate() {
+            mesh.face_edge.push(None);
+            if a == b || b == c || a == c {
+                mesh.issues.push(TopologyIssue::DegenerateFace { face });
+                continue;
+            }
+            let sides = [(a, b), (b, c), (c, a)];
+            if let Some(&(from, to)) = sides.iter().find(|s| directed.contains_key(s)) {
+                mesh.issues.push(if directed.contains_key(&(to, from)) {
+                    TopologyIssue::NonManifoldEdge { face, from, to }
+                } else {
+                    TopologyIssue::FlippedFace { face, from, to }
+                });
+                continue;
+            }
+
+            let first = mesh.half_edges.len();
+            for (k, &(from, to)) in sides.iter().enumerate() {
+                directed.insert((from, to), first + k);
+                mesh.half_edges.push(HalfEdge {
+                    origin: from,
+                    face: Some(face),
+                    next: first + (k + 1) % 3,
+                    prev: first + (k + 2) % 3,
+                    twin: usize::MAX,
+                    removed: false,
+                });
+                mesh.vertex_edge[from as usize] = Some(first + k);
+            }
+            mesh.face_edge[face] = Some(first);
+        }
+
+        // pair up twins, giving edges with a face on only one side a border half-edge
+        let mut border = Vec::new();
+        for h in 0..mesh.half_edges.len() {
+            let (from, to) = (mesh.origin(h), mesh.origin(mesh.next(h)));
+            match directed.get(&(to, from)) {
+                Some(&twin) => mesh.half_edges[h].twin = twin,
+                None => {
+                    let g = mesh.half_edges.len();
+                    mesh.half_edges.push(HalfEdge {
+                        origin: to,
+                        face: None,
+                        next: usize::MAX,
+                        prev: usize::MAX,
+                        twin: h,
+                        removed: false,
+                    });
+                    mesh.half_edges[h].twin = g;
+                    border.push(g);
+                }
+            }
+        }
+
+        // link each border half-edge to the next one around the hole, which leaves the
+        // vertex it ends at along the same fan of triangles
+        for &g in &border {
+            let mut h = mesh.half_edges[g].twin;
+            while mesh.half_edges[h].face.is_some() {
+                h = mesh.rotate(h);
+            }
+            mesh.half_edges[g].next = h;
+            mesh.half_edges[h].prev = g;
+        }
+        for &g in &border {
+            let v = mesh.origin(g);
+            mesh.vertex_edge[v as usize] = Some(g);
+        }
+
+        // vertices whose outgoing half-edges are not all in one fan are non-manifold
+        let mut outgoing = vec![0; data.positions.len()];
+        for h in &mesh.half_edges {
+            outgoing[h.origin as usize] += 1;
+        }
+        for v in 0..data.positions.len() as u32 {
+            if mesh.outgoing(v).count() < outgoing[v as usize] {
+                mesh.issues
+                    .push(TopologyIssue::NonManifoldVertex { vertex: v });
+            }
+        }
+        mesh
+    }
+
+    /// Problems found in the triangles the mesh was built from.
+    pub fn issues(&self) -> &[TopologyIssue] {
+        &self.issues
+    }
+
+    /// Vertex attributes, indexed by vertex.
+    pub fn vertex_data(&self) -> &MeshData {
+        &self.data
+    }
+
+    pub fn num_half_edges(&self) -> usize {
+        self.half_edges.len()
+    }
+
+    pub fn num_faces(&self) -> usize {
+        self.face_edge.len()
+    }
+
+    pub fn num_vertices(&self) -> usize {
+        self.vertex_edge.len()
+    }
+
+    /// Whether face `f` is part of the mesh, i.e. it was not left out or removed.
+    pub fn has_face(&self, f: usize) -> bool {
+        self.face_edge[f].is_some()
+    }
+
+    /// Whether half-edge `h` is part of the mesh, i.e. it was not removed by a collapse.
+    pub fn has_half_edge(&self, h: usize) -> bool {
+        !self.half_edges[h].removed
+    }
+
+    /// Whether vertex `v` is part of the mesh, i.e. it was not removed by a collapse.
+    pub fn has_vertex(&self, v: u32) -> bool {
+        !self.vertex_removed[v as usize]
+    }
+
+    pub fn origin(&self, h: usize) -> u32 {
+        self.half_edges[h].origin
+    }
+
+    pub fn target(&self, h: usize) -> u32 {
+        self.half_edges[self.half_edges[h].twin].origin
+    }
+
+    pub fn next(&self, h: usize) -> usize {
+        self.half_edges[h].next
+    }
+
+    pub fn prev(&self, h: usize) -> usize {
+        self.half_edges[h].prev
+    }
+
+    pub fn twin(&self, h: usize) -> usize {
+        self.half_edges[h].twin
+    }
+
+    /// Face on the left of half-edge `h`, or `None` on a border.
+    pub fn face(&self, h: usize) -> Option<usize> {
+        self.half_edges[h].face
+    }
+
+    pub fn is_border_edge(&self, h: usize) -> bool {
+        self.face(h).is_none() || self.face(self.twin(h)).is_none()
+    }
+
+    pub fn is_border_vertex(&self, v: u32) -> bool {
+        match self.vertex_edge[v as usize] {
+            Some(h) => self.face(h).is_none(),
+            None => false,
+        }
+    }
+
+    /// Half-edge running from `from` to `to`, if they share an edge.
+    pub fn find_half_edge(&self, from: u32, to: u32) -> Option<usize> {
+        self.outgoing(from).find(|&h| self.target(h) == to)
+    }
+
+    /// Half-edges of face `f`.
+    pub fn face_half_edges(&self, f: usize) -> Option<[usize; 3]> {
+        let h = self.face_edge[f]?;
+        Some([h, self.next(h), self.prev(h)])
+    }
+
+    /// Vertices of face `f`, in winding order.
+    pub fn face_vertices(&self, f: usize) -> Option<[u32; 3]> {
+        Some(self.face_half_edges(f)?.map(|h| self.origin(h)))
+    }
+
+    /// Next half-edge out of the origin of `h`, turning clockwise around it.
+    fn rotate(&self, h: usize) -> usize {
+        self.twin(self.prev(h))
+    }
+
+    /// Half-edges leaving vertex `v`, turning around it. On the border, the first is the
+    /// border half-edge.
+    pub fn outgoing(&self, v: u32) -> impl Iterator<Item = usize> + '_ {
+        let start = self.vertex_edge[v as usize];
+        let mut h = start;
+        std::iter::from_fn(move || {
+            let current = h?;
+            let next = self.rotate(current);
+            h = if Some(next) == start {
+                None
+            } else {
+                Some(next)
+            };
+            Some(current)
+        })
+    }
+
+    /// Vertices sharing an edge with `v`.
+    pub fn neighbours(&self, v: u32) -> impl Iterator<Item = u32> + '_ {
+        self.outgoing(v).map(move |h| self.target(h))
+    }
+
+    /// Faces around `v`.
+    pub fn vertex_faces(&self, v: u32) -> impl Iterator<Item = usize> + '_ {
+        self.outgoing(v).filter_map(move |h| self.face(h))
+    }
+
+    /// Faces sharing an edge with face `f`.
+    pub fn face_neighbours(&self, f: usize) -> Vec<usize> {
+        self.face_half_edges(f)
+            .map(|hs| hs.iter().filter_map(|&h| self.face(self.twin(h))).collect())
+            .unwrap_or_default()
+    }
+
+    /// Vertices around each hole in the mesh, in order around the hole.
+    pub fn border_loops(&self) -> Vec<Vec<u32>> {
+        let mut visited = vec![false; self.half_edges.len()];
+        let mut loops = Vec::new();
+        for start in 0..self.half_edges.len() {
+            let edge = &self.half_edges[start];
+            if edge.removed || edge.face.is_some() || visited[start] {
+                continue;
+            }
+            let mut vertices = Vec::new();
+            let mut h = start;
+            while !visited[h] {
+                visited[h] = true;
+                vertices.push(self.origin(h));
+                h = self.next(h);
+            }
+            loops.push(vertices);
+        }
+        loops
+    }
+
+    /// Flip the edge of `h` between the two triangles on either side of it, so that it joins
+    /// their other two vertices instead. Returns whether it could be flipped: edges on the
+    /// border cannot be, nor can those whose flipped edge already exists or which were removed.
+    pub fn flip_edge(&mut self, h: usize) -> bool {
+        if !self.has_half_edge(h) {
+            return false;
+        }
+        let t = self.twin(h);
+        let (f, g) = match (self.face(h), self.face(t)) {
+            (Some(f), Some(g)) => (f, g),
+            _ => return false,
+        };
+        // h: a -> b in (a, b, c), and t: b -> a in (b, a, d)
+        let (h1, h2, t1, t2) = (self.next(h), self.prev(h), self.next(t), self.prev(t));
+        let (a, b, c, d) = (
+            self.origin(h),
+            self.origin(t),
+            self.origin(h2),
+            self.origin(t2),
+        );
+        if c == d || self.find_half_edge(c, d).is_some() {
+            return false;
+        }
+
+        // (c, a, d) with h: d -> c, and (d, b, c) with t: c -> d
+        self.link_face(f, [h2, t1, h]);
+        self.link_face(g, [t2, h1, t]);
+        self.half_edges[h].origin = d;
+        self.half_edges[t].origin = c;
+        if self.vertex_edge[a as usize] == Some(h) {
+            self.vertex_edge[a as usize] = Some(t1);
+        }
+        if self.vertex_edge[b as usize] == Some(t) {
+            self.vertex_edge[b as usize] = Some(h1);
+        }
+        true
+    }
+
+    /// Collapse the edge of `h` by merging its origin into its target, which keeps its position
+    /// and attributes, and removing the triangles on either side. Returns whether it could be
+    /// collapsed: the result must stay manifold, so the ends may only share the neighbours
+    /// across the triangles on the edge, and edges which were removed cannot be collapsed again.
+    pub fn collapse_edge(&mut self, h: usize) -> bool {
+        if !self.has_half_edge(h) {
+            return false;
+        }
+        let t = self.twin(h);
+        let (a, b) = (self.origin(h), self.origin(t));
+        let opposite: Vec<u32> = [h, t]
+            .iter()
+            .filter(|&&e| self.face(e).is_some())
+            .map(|&e| self.origin(self.prev(e)))
+            .collect();
+        let b_neighbours: Vec<u32> = self.neighbours(b).collect();
+        let shared = self
+            .neighbours(a)
+            .filter(|v| b_neighbours.contains(v))
+            .any(|v| !opposite.contains(&v));
+        // joining two borders through the inside would pinch the mesh into a non-manifold vertex
+        let pinch = !self.is_border_edge(h) && self.is_border_vertex(a) && self.is_border_vertex(b);
+        // collapsing one of two triangles around an edge would leave the other doubled
+        let doubled = [h, t].iter().any(|&e| {
+            self.face(e).is_some()
+                && self.face(self.twin(self.next(e))) == self.face(self.twin(self.prev(e)))
+        });
+        if shared || pinch || doubled {
+            return false;
+        }
+
+        let moved: Vec<usize> = self.outgoing(a).collect();
+        let mut survivors = Vec::new();
+        for &e in &[h, t] {
+            survivors.extend(self.remove_edge_side(e));
+        }
+        for e in moved {
+            if !self.half_edges[e].removed {
+                self.half_edges[e].origin = b;
+            }
+        }
+        self.vertex_removed[a as usize] = true;
+        self.vertex_edge[a as usize] = None;
+
+        // the survivors include an outgoing half-edge of every vertex whose own may have gone
+        for e in survivors {
+            for v in [self.origin(e), self.target(e)] {
+                let edge = self.vertex_edge[v as usize];
+                if edge.is_none_or(|edge| self.half_edges[edge].removed) {
+                    self.vertex_edge[v as usize] =
+                        Some(if self.origin(e) == v { e } else { self.twin(e) });
+                }
+            }
+        }
+        self.fix_vertex_edge(b);
+        true
+    }
+
+    /// Remove the collapsing half-edge `e` and, if it has a face, the face and its other two
+    /// edges, which are replaced by their twins joined together. Returns the half-edges which
+    /// take the place of those removed.
+    fn remove_edge_side(&mut self, e: usize) -> Vec<usize> {
+        self.half_edges[e].removed = true;
+        match self.face(e) {
+            None => {
+                let (prev, next) = (self.prev(e), self.next(e));
+                self.half_edges[prev].next = next;
+                self.half_edges[next].prev = prev;
+                vec![prev, next]
+            }
+            Some(f) => {
+                let (e1, e2) = (self.next(e), self.prev(e));
+                let (o1, o2) = (self.twin(e1), self.twin(e2));
+                self.half_edges[e1].removed = true;
+                self.half_edges[e2].removed = true;
+                self.half_edges[o1].twin = o2;
+                self.half_edges[o2].twin = o1;
+                self.face_edge[f] = None;
+                vec![o1, o2]
+            }
+        }
+    }
+
+    /// Make sure the outgoing half-edge of `v` is on the border, if `v` is on one.
+    fn fix_vertex_edge(&mut self, v: u32) {
+        let border = self.outgoing(v).find(|&h| self.face(h).is_none());
+        if let Some(border) = border {
+            self.vertex_edge[v as usize] = Some(border);
+        }
+    }
+
+    fn link_face(&mut self, f: usize, edges: [usize; 3]) {
+        for k in 0..3 {
+            let e = &mut self.half_edges[edges[k]];
+            e.face = Some(f);
+            e.next = edges[(k + 1) % 3];
+            e.prev = edges[(k + 2) % 3];
+        }
+        self.face_edge[f] = Some(edges[0]);
+    }
+
+    /// Mesh data of the remaining faces, with only the vertices they use.
+    pub fn to_mesh_data(&self) -> MeshData {
+        let data = &self.data;
+        let mut mesh = MeshData::default();
+        let mut new_index: HashMap<u32, u32> = HashMap::new();
+        for f in 0..self.face_edge.len() {
+            for v in self.face_vertices(f).iter().flatten() {
+                let index = *new_index.entry(*v).or_insert_with(|| {
+                    let i = *v as usize;
+                    mesh.positions.push(data.positions[i]);
+                    if let Some(&n) = data.normals.get(i) {
+                        mesh.normals.push(n);
+                    }
+                    if let Some(&t) = data.texcoords.get(i) {
+                        mesh.texcoords.push(t);
+                    }
+                    if let Some(&c) = data.colors.get(i) {
+                        mesh.colors.push(c);
+                    }
+                    mesh.positions.len() as u32 - 1
+                });
+                mesh.indices.push(index);
+            }
+        }
+        mesh
+    }
+
+    /// Upload the remaining faces in the vertex layout `T`.
+    pub fn shapegl<T: rendergl::Vertex>(&self) -> ShapeGL {
+        ShapeGL::from_data::<T>(&self.to_mesh_data())
+    }
+}
@@ -0,0 +1,142 @@
+use std::cell::Cell;
+
+use crate::animation::Pose;
+use crate::camera::Camera;
+use crate::picking::{Hit, Ray};
+use crate::rendergl;
+use crate::shape::{DrawError, Drawable};
+use crate::util::{Aabb, BoundingVolume};
+
+/// A `Drawable` with levels of detail, drawing one of them depending on how large the object
+/// appears on screen.
+///
+/// The object's size is that of a sphere around the bounds of its most detailed level, as a
+/// fraction of the view's height (see `Camera::screen_size`). Each coarser level is drawn when
+/// the object is smaller than that level's screen size. The switch back to a finer level only
+/// happens once the object is a `hysteresis` fraction larger than the switch to the coarser
+/// one, so that levels do not flicker as the size wavers around a threshold.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let lod = LodGroup::new(Box::new(full))
+///     .with_level(Box::new(half), 0.5)
+///     .with_level(Box::new(quarter), 0.25);
+/// ```
+pub struct LodGroup {
+    levels: Vec<LodLevel>,
+    hysteresis: f32,
+    current: Cell<usize>,
+}
+
+struct LodLevel {
+    drawable: Box<dyn Drawable>,
+    /// Screen size below which this level is drawn.
+    screen_size: f32,
+}
+
+impl LodGroup {
+    /// Default fraction by which the screen size must grow past a threshold to switch back to a
+    /// finer level.
+    pub const DEFAULT_HYSTERESIS: f32 = 0.1;
+
+    /// Construct a group whose most detailed level is `base`.
+    pub fn new(base: Box<dyn Drawable>) -> LodGroup {
+        LodGroup {
+            levels: vec![LodLevel {
+                drawable: base,
+                screen_size: f32::INFINITY,
+            }],
+            hysteresis: LodGroup::DEFAULT_HYSTERESIS,
+            current: Cell::new(0),
+        }
+    }
+
+    /// Add a coarser level, drawn when the object covers less than `screen_size` of the view's
+    /// height. Levels must be added from finest to coarsest, with decreasing screen sizes.
+    pub fn with_level(mut self, drawable: Box<dyn Drawable>, screen_size: f32) -> LodGroup {
+        self.levels.push(LodLevel {
+            drawable,
+            screen_size,
+        });
+        self
+    }
+
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> LodGroup {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Index of the level drawn last, where 0 is the most detailed.
+    pub fn level(&self) -> usize {
+        self.current.get()
+    }
+
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Choose the level to draw for `camera`, moving from the current one so that thresholds
+    /// are crossed with hysteresis.
+    fn select(&self, camera: &Camera) -> usize {
+        let bounds = match self.levels[0].drawable.bounds() {
+            Some(bounds) => bounds,
+            None => return 0,
+        };
+        let size = camera.screen_size(&BoundingVolume::Box(bounds));
+        let mut level = self.current.get().min(self.levels.len() - 1);
+        while level + 1 < self.levels.len()
+            && size < self.levels[level + 1].screen_size * (1.0 - self.hysteresis)
+        {
+            level += 1;
+        }
+        while level > 0 && size > self.levels[level].screen_size * (1.0 + self.hysteresis) {
+            level -= 1;
+        }
+        self.current.set(level);
+        level
+    }
+
+    fn current(&self) -> &dyn Drawable {
+        self.levels[self.current.get()].drawable.as_ref()
+    }
+}
+
+impl Drawable for LodGroup {
+    fn init(&mut self) -> Result<(), DrawError> {
+        for level in &mut self.levels {
+            level.drawable.init()?;
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        for level in &mut self.levels {
+            level.drawable.tick();
+        }
+    }
+
+    fn draw(&self, camera: &Camera) -> Result<(), DrawError> {
+        let level = self.select(camera);
+        self.levels[level].drawable.draw(camera)
+    }
+
+    fn apply_pose(&mut self, pose: &Pose) {
+        for level in &mut self.levels {
+            level.drawable.apply_pose(pose);
+        }
+    }
+
+    /// Picks against the level drawn last, so that picks match what is on screen.
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        self.current().ray_cast(ray)
+    }
+
+    fn draw_ids(&self, program: &rendergl::Program) -> Result<(), DrawError> {
+        self.current().draw_ids(program)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.levels[0].drawable.bounds()
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use glm;
+use tobj;
+
+use crate::util::{Aabb, SurfacePoint};
+
+/// Vertex and triangle data of a mesh on the CPU.
+///
+/// Shape generators such as `MeshData::sphere` build their vertices here before they are
+/// uploaded with `ShapeGL::from_data`, and `ResourceLoader::load_ply` and `load_stl` read into
+/// it. It can be written out with `save_obj`, `save_ply` and `save_stl`, and its triangles and
+/// vertices reordered for the GPU's caches with `optimize` before it is uploaded.
+///
+/// Attributes the mesh does not have are empty. Point clouds, which have no faces, have no
+/// indices.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<glm::Vec3>,
+    pub normals: Vec<glm::Vec3>,
+    pub texcoords: Vec<glm::Vec2>,
+    /// RGBA colours, with channels in `[0, 1]`.
+    pub colors: Vec<glm::Vec4>,
+    /// Triangle list indices.
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// Whether the data is a point cloud, with no triangles.
+    pub fn is_point_cloud(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Append a vertex with the position, normal and texture coordinates of `point`, returning
+    /// its index.
+    pub fn push_point(&mut self, point: &SurfacePoint) -> u32 {
+        self.positions.push(point.position());
+        self.normals.push(point.normal());
+        self.texcoords.push(point.texcoord());
+        self.positions.len() as u32 - 1
+    }
+
+    /// `SurfacePoint` of vertex `i`, for building a vertex of any layout. Missing normals are
+    /// zero, and missing texture coordinates are the origin.
+    pub fn point(&self, i: usize) -> SurfacePoint {
+        SurfacePoint::Parametric {
+            position: self.positions[i],
+            normal: self
+                .normals
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| glm::vec3(0.0, 0.0, 0.0)),
+            texcoord: self
+                .texcoords
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| glm::vec2(0.0, 0.0)),
+        }
+    }
+
+    /// Colour of vertex `i`, or white if the mesh has no colours.
+    pub fn color(&self, i: usize) -> glm::Vec4 {
+        self.colors
+            .get(i)
+            .cloned()
+            .unwrap_or_else(|| glm::vec4(1.0, 1.0, 1.0, 1.0))
+    }
+
+    /// Colour every vertex `color`, e.g. to give a generated primitive a colour of its own.
+    pub fn with_color(self, color: glm::Vec4) -> MeshData {
+        self.with_colors(|_, _| color)
+    }
+
+    /// Colour each vertex by `color_of` its position and normal (zero if the mesh has none),
+    /// e.g. to bake lighting or ambient occlusion into a generated primitive.
+    pub fn with_colors<F>(mut self, color_of: F) -> MeshData
+    where
+        F: Fn(glm::Vec3, glm::Vec3) -> glm::Vec4,
+    {
+        let zero = glm::vec3(0.0, 0.0, 0.0);
+        self.colors = (0..self.positions.len())
+            .map(|i| {
+                color_of(
+                    self.positions[i],
+                    self.normals.get(i).cloned().unwrap_or(zero),
+                )
+            })
+            .collect();
+        self
+    }
+
+    /// Relative distance, as a fraction of the diagonal of the mesh's bounds, within which
+    /// `weld_positions` takes positions to be the same. Vertices which should meet, such as on
+    /// the seams and poles of the generated shapes, can be apart by rounding errors.
+    const WELD_TOLERANCE: f32 = 1e-5;
+
+    /// Distinct vertex positions, and the index among them of each vertex's position. Vertices
+    /// split on attribute seams share a position, even if rounding has moved them slightly
+    /// apart (see `WELD_TOLERANCE`).
+    pub(crate) fn weld_positions(&self) -> (Vec<glm::Vec3>, Vec<usize>) {
+        let finite = self
+            .positions
+            .iter()
+            .filter(|p| [p.x, p.y, p.z].iter().all(|x| x.is_finite()));
+        let size = Aabb::from_points(finite).size();
+        let tolerance = glm::length(size) * MeshData::WELD_TOLERANCE;
+        // positions are bucketed into cells as large as the tolerance, so that positions which
+        // are welded together are in the same or neighbouring cells
+        let cell_size = if tolerance > 0.0 { tolerance } else { 1.0 };
+        let cell = |p: glm::Vec3| [p.x, p.y, p.z].map(|x| (x / cell_size).floor() as i64);
+
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        let mut positions: Vec<glm::Vec3> = Vec::new();
+        let position_of = self
+            .positions
+            .iter()
+            .map(|&p| {
+                let [x, y, z] = cell(p);
+                let near = (0..27).find_map(|k| {
+                    let neighbour = [x + k % 3 - 1, y + k / 3 % 3 - 1, z + k / 9 - 1];
+                    cells.get(&neighbour)?.iter().cloned().find(|&i| {
+                        let q = positions[i];
+                        vec3_key(p) == vec3_key(q) || glm::distance(p, q) <= tolerance
+                    })
+                });
+                near.unwrap_or_else(|| {
+                    positions.push(p);
+                    cells
+                        .entry([x, y, z])
+                        .or_default()
+                        .push(positions.len() - 1);
+                    positions.len() - 1
+                })
+            })
+            .collect();
+        (positions, position_of)
+    }
+
+    /// Vertex indices of each triangle.
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        self.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]])
+    }
+}
+
+impl From<&tobj::Mesh> for MeshData {
+    fn from(mesh: &tobj::Mesh) -> MeshData {
+        MeshData {
+            positions: mesh
+                .positions
+                .chunks(3)
+                .map(|p| glm::vec3(p[0], p[1], p[2]))
+                .collect(),
+            normals: mesh
+                .normals
+                .chunks(3)
+                .map(|n| glm::vec3(n[0], n[1], n[2]))
+                .collect(),
+            texcoords: mesh
+                .texcoords
+                .chunks(2)
+                .map(|t| glm::vec2(t[0], t[1]))
+                .collect(),
+            colors: Vec::new(),
+            indices: mesh.indices.clone(),
+        }
+    }
+}
+
+impl From<&MeshData> for tobj::Mesh {
+    /// Convert to a `tobj` mesh, with no material. `tobj` meshes have no vertex colours.
+    fn from(data: &MeshData) -> tobj::Mesh {
+        tobj::Mesh::new(
+            data.positions
+                .iter()
+                .flat_map(|p| vec![p.x, p.y, p.z])
+                .collect(),
+            data.normals
+                .iter()
+                .flat_map(|n| vec![n.x, n.y, n.z])
+                .collect(),
+            data.texcoords.iter().flat_map(|t| vec![t.x, t.y]).collect(),
+            data.indices.clone(),
+            None,
+        )
+    }
+}
+
+/// Unroll the indices of a triangle strip into a triangle list, leaving out the degenerate
+/// triangles used to join strips together.
+pub fn strip_to_triangles(strip: &[u32]) -> Vec<u32> {
+    let mut indices = Vec::new();
+    for i in 0..strip.len().saturating_sub(2) {
+        // every other triangle in a strip is flipped to keep a consistent winding
+        let [a, b, c] = if i % 2 == 0 {
+            [strip[i], strip[i + 1], strip[i + 2]]
+        } else {
+            [strip[i + 1], strip[i], strip[i + 2]]
+        };
+        if a != b && b != c && a != c {
+            indices.extend_from_slice(&[a, b, c]);
+        }
+    }
+    indices
+}
+
+/// Exact bit pattern of a vector, for grouping equal vectors.
+pub(crate) fn vec3_key(v: glm::Vec3) -> [u32; 3] {
+    // adding zero turns -0 into 0, which are equal but have different bits
+    [v.x + 0.0, v.y + 0.0, v.z + 0.0].map(f32::to_bits)
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use glm;
+
+use crate::shape::meshdata::vec3_key;
+use crate::shape::MeshData;
+
+/// How `MeshData::compute_normals` shades a mesh.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shading {
+    /// Average the normals of the triangles around each vertex, weighted by their angle at the
+    /// vertex. Triangles whose normals differ by more than `crease_angle` (in radians) do not
+    /// share a normal, so their vertices are split into hard edges.
+    Smooth { crease_angle: f32 },
+    /// Give each triangle its own vertices, with the triangle's normal.
+    Flat,
+}
+
+impl Default for Shading {
+    fn default() -> Shading {
+        Shading::Smooth {
+            crease_angle: MeshData::DEFAULT_CREASE_ANGLE,
+        }
+    }
+}
+
+impl MeshData {
+    /// Crease angle of the default smooth `Shading`: 60 degrees, so that the edges of boxes
+    /// stay hard while curved surfaces tessellated with a dozen or so segments are smooth.
+    pub const DEFAULT_CREASE_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+
+    /// Replace the normals of the mesh with ones computed from its triangles.
+    ///
+    /// Vertices are duplicated where they need more than one normal: on hard edges with
+    /// `Shading::Smooth`, and everywhere with `Shading::Flat`. Vertices at the same position
+    /// are smoothed together even if they are separate vertices, e.g. on texture seams. Point
+    /// clouds have no triangles to compute normals from, and are left as they are.
+    pub fn compute_normals(&mut self, shading: Shading) {
+        if self.is_point_cloud() {
+            return;
+        }
+        let triangles: Vec<[u32; 3]> = self.triangles().collect();
+        let face_normals: Vec<glm::Vec3> = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let [a, b, c] = [a, b, c].map(|i| self.positions[i as usize]);
+                let n = glm::cross(b - a, c - a);
+                if glm::length(n) > 0.0 {
+                    glm::normalize(n)
+                } else {
+                    n
+                }
+            })
+            .collect();
+
+        let corner_normals: Vec<glm::Vec3> = match shading {
+            Shading::Flat => face_normals.iter().flat_map(|&n| vec![n; 3]).collect(),
+            Shading::Smooth { crease_angle } => {
+                self.smooth_corner_normals(&triangles, &face_normals, crease_angle.cos())
+            }
+        };
+        self.split_corners(&corner_normals, shading == Shading::Flat);
+    }
+
+    /// Angle-weighted normal at each triangle corner, from the triangles around the corner's
+    /// position within the crease angle of its own triangle.
+    fn smooth_corner_normals(
+        &self,
+        triangles: &[[u32; 3]],
+        face_normals: &[glm::Vec3],
+        min_cos: f32,
+    ) -> Vec<glm::Vec3> {
+        // corners around each position, with the angle of their triangle there
+        let mut around: HashMap<[u32; 3], Vec<(usize, f32)>> = HashMap::new();
+        for (t, triangle) in triangles.iter().enumerate() {
+            for k in 0..3 {
+                let p = self.positions[triangle[k] as usize];
+                let e1 = self.positions[triangle[(k + 1) % 3] as usize] - p;
+                let e2 = self.positions[triangle[(k + 2) % 3] as usize] - p;
+                let angle = if glm::length(e1) > 0.0 && glm::length(e2) > 0.0 {
+                    glm::dot(glm::normalize(e1), glm::normalize(e2))
+                        .clamp(-1.0, 1.0)
+                        .acos()
+                } else {
+                    0.0
+                };
+                around.entry(vec3_key(p)).or_default().push((t, angle));
+            }
+        }
+
+        let mut normals = Vec::with_capacity(triangles.len() * 3);
+        for (t, triangle) in triangles.iter().enumerate() {
+            let face = face_normals[t];
+            for &i in triangle {
+                let corners = &around[&vec3_key(self.positions[i as usize])];
+                let sum = corners
+                    .iter()
+                    .filter(|&&(other, _)| {
+                        other == t || glm::dot(face, face_normals[other]) >= min_cos
+                    })
+                    .fold(glm::vec3(0.0, 0.0, 0.0), |sum, &(other, angle)| {
+                        sum + face_normals[other] * angle
+                    });
+                normals.push(if glm::length(sum) > 0.0 {
+                    glm::normalize(sum)
+                } else {
+                    face
+                });
+            }
+        }
+        normals
+    }
+
+    /// Give each triangle corner its normal, sharing a vertex between corners of the same
+    /// vertex with the same normal (or never, if `unshare`) and duplicating it otherwise.
+    fn split_corners(&mut self, corner_normals: &[glm::Vec3], unshare: bool) {
+        let count = self.positions.len();
+        let mut normals = vec![glm::vec3(0.0, 0.0, 0.0); count];
+        let mut assigned = vec![false; count];
+        let mut copies: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+
+        for (corner, &normal) in corner_normals.iter().enumerate() {
+            let i = self.indices[corner];
+            if !assigned[i as usize] {
+                assigned[i as usize] = true;
+                normals[i as usize] = normal;
+                copies.insert((i, vec3_key(normal)), i);
+                continue;
+            }
+            let key = (i, vec3_key(normal));
+            let copy = match copies.get(&key) {
+                Some(&copy) if !unshare => copy,
+                _ => {
+                    let copy = self.copy_vertex(i as usize);
+                    normals.push(normal);
+                    copies.insert(key, copy);
+                    copy
+                }
+            };
+            self.indices[corner] = copy;
+        }
+        self.normals = normals;
+    }
+
+    /// Append a copy of vertex `i` with every attribute but its normal, returning its index.
+    fn copy_vertex(&mut self, i: usize) -> u32 {
+        self.positions.push(self.positions[i]);
+        if let Some(&t) = self.texcoords.get(i) {
+            self.texcoords.push(t);
+        }
+        if let Some(&c) = self.colors.get(i) {
+            self.colors.push(c);
+        }
+        self.positions.len() as u32 - 1
+    }
+}
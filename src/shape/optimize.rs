@@ -0,0 +1,416 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use glm;
+
+use crate::shape::MeshData;
+
+/// Post-transform vertex cache statistics of an index buffer, from simulating a FIFO cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    /// Average cache miss ratio: vertices transformed per triangle, between 0.5 at best for
+    /// large meshes and 3 at worst.
+    pub acmr: f32,
+    /// Average transform to vertex ratio: vertices transformed per vertex, 1 at best.
+    pub atvr: f32,
+}
+
+/// Cache statistics before and after an optimisation pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OptimizeStats {
+    pub before: CacheStats,
+    pub after: CacheStats,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ACMR {:.3}, ATVR {:.3}", self.acmr, self.atvr)
+    }
+}
+
+impl fmt::Display for OptimizeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.before, self.after)
+    }
+}
+
+/// FIFO vertex cache, as found in GPUs after the vertex shader.
+struct FifoCache {
+    entries: VecDeque<u32>,
+    size: usize,
+}
+
+impl FifoCache {
+    fn new(size: usize) -> FifoCache {
+        FifoCache {
+            entries: VecDeque::with_capacity(size + 1),
+            size: size.max(1),
+        }
+    }
+
+    /// Look up vertex `i`, adding it on a miss. Returns whether it missed.
+    fn fetch(&mut self, i: u32) -> bool {
+        if self.entries.contains(&i) {
+            return false;
+        }
+        self.entries.push_back(i);
+        if self.entries.len() > self.size {
+            self.entries.pop_front();
+        }
+        true
+    }
+
+    /// Number of vertices of `triangle` which miss.
+    fn fetch_triangle(&mut self, triangle: [u32; 3]) -> usize {
+        triangle.iter().filter(|&&i| self.fetch(i)).count()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl MeshData {
+    /// Vertex cache size assumed by the optimisation passes when the target GPU is unknown.
+    pub const DEFAULT_CACHE_SIZE: usize = 16;
+
+    /// Default factor by which `optimize_overdraw` may let the ACMR grow.
+    pub const DEFAULT_OVERDRAW_THRESHOLD: f32 = 1.05;
+
+    /// Simulate drawing the triangles through a FIFO vertex cache of `cache_size` entries.
+    pub fn cache_stats(&self, cache_size: usize) -> CacheStats {
+        let mut cache = FifoCache::new(cache_size);
+        let misses: usize = self
+            .triangles()
+            .map(|triangle| cache.fetch_triangle(triangle))
+            .sum();
+        let triangles = self.indices.len() / 3;
+        CacheStats {
+            acmr: if triangles == 0 {
+                0.0
+            } else {
+                misses as f32 / triangles as f32
+            },
+            atvr: if self.positions.is_empty() {
+                0.0
+            } else {
+                misses as f32 / self.positions.len() as f32
+            },
+        }
+    }
+
+    /// Reorder the triangles so that their vertices are found in a post-transform cache of
+    /// `cache_size` entries as often as possible, with Tipsify (Sander et al. 2007).
+    ///
+    /// Triangles are emitted in fans around each vertex, moving on to the neighbour which
+    /// will still be in the cache once its own fan is done. Triangles with out-of-range
+    /// indices are moved to the end.
+    pub fn optimize_vertex_cache(&mut self, cache_size: usize) -> OptimizeStats {
+        let before = self.cache_stats(cache_size);
+        let order = self.tipsify(cache_size);
+        self.reorder_triangles(&order);
+        OptimizeStats {
+            before,
+            after: self.cache_stats(cache_size),
+        }
+    }
+
+    /// Reorder the triangles so that those facing out from the middle of the mesh come first
+    /// and hide more of the others behind them, reducing overdraw.
+    ///
+    /// The triangles are split into clusters wherever the cache order jumps to a new part of
+    /// the mesh, and then wherever the ACMR since the last split is within a factor of
+    /// `threshold` of the whole cluster's, and the clusters are sorted by how far out they
+    /// face. A larger threshold gives smaller clusters, trading cache efficiency for less
+    /// overdraw. This works on the current order, so it should follow
+    /// `optimize_vertex_cache`. Triangles with out-of-range indices are moved to the end.
+    pub fn optimize_overdraw(&mut self, cache_size: usize, threshold: f32) -> OptimizeStats {
+        let before = self.cache_stats(cache_size);
+        let count = self.positions.len();
+        let (valid, invalid): (Vec<usize>, Vec<usize>) =
+            (0..self.indices.len() / 3).partition(|&t| {
+                self.indices[3 * t..3 * t + 3]
+                    .iter()
+                    .all(|&i| (i as usize) < count)
+            });
+        let triangles: Vec<[u32; 3]> = valid
+            .iter()
+            .map(|&t| {
+                [
+                    self.indices[3 * t],
+                    self.indices[3 * t + 1],
+                    self.indices[3 * t + 2],
+                ]
+            })
+            .collect();
+        if triangles.is_empty() {
+            return OptimizeStats {
+                before,
+                after: before,
+            };
+        }
+
+        let clusters = soft_boundaries(
+            &triangles,
+            &hard_boundaries(&triangles, cache_size),
+            cache_size,
+            threshold,
+        );
+
+        // the middle of the mesh, weighted by area so that dense patches do not pull it over
+        let (mut middle, mut total_area) = (glm::vec3(0.0, 0.0, 0.0), 0.0);
+        for &triangle in &triangles {
+            let (centroid, area_normal) = self.triangle_centroid(triangle);
+            let area = glm::length(area_normal);
+            middle = middle + centroid * area;
+            total_area += area;
+        }
+        if total_area > 0.0 {
+            middle = middle / total_area;
+        }
+
+        let mut keyed: Vec<(f32, usize, usize)> = clusters
+            .windows(2)
+            .map(|bounds| {
+                let (mut centroid, mut normal, mut area) =
+                    (glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 0.0), 0.0);
+                for &triangle in &triangles[bounds[0]..bounds[1]] {
+                    let (c, n) = self.triangle_centroid(triangle);
+                    let a = glm::length(n);
+                    centroid = centroid + c * a;
+                    normal = normal + n;
+                    area += a;
+                }
+                let key = if area > 0.0 {
+                    glm::dot(centroid / area - middle, normal / area)
+                } else {
+                    0.0
+                };
+                (key, bounds[0], bounds[1])
+            })
+            .collect();
+        // outermost first; the sort is stable, so ties keep their cache order
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let order: Vec<usize> = keyed
+            .iter()
+            .flat_map(|&(_, start, end)| valid[start..end].to_vec())
+            .chain(invalid)
+            .collect();
+        self.reorder_triangles(&order);
+        OptimizeStats {
+            before,
+            after: self.cache_stats(cache_size),
+        }
+    }
+
+    /// Reorder the vertices in the order the triangles first use them, so that vertex fetches
+    /// read memory in order, and drop vertices which no triangle uses. Triangles with
+    /// out-of-range indices are dropped too.
+    ///
+    /// This leaves the triangle order alone, so it should be the last pass. The cache
+    /// statistics only change as much as unused vertices are dropped.
+    pub fn optimize_vertex_fetch(&mut self) -> OptimizeStats {
+        let before = self.cache_stats(MeshData::DEFAULT_CACHE_SIZE);
+        if self.is_point_cloud() {
+            return OptimizeStats {
+                before,
+                after: before,
+            };
+        }
+        let count = self.positions.len();
+        let mut remap: Vec<Option<u32>> = vec![None; count];
+        let mut order = Vec::new();
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for triangle in self.triangles() {
+            if triangle.iter().any(|&i| i as usize >= count) {
+                continue;
+            }
+            for &i in &triangle {
+                let new = *remap[i as usize].get_or_insert_with(|| {
+                    order.push(i as usize);
+                    order.len() as u32 - 1
+                });
+                indices.push(new);
+            }
+        }
+
+        fn gather<T: Copy>(values: &[T], order: &[usize]) -> Vec<T> {
+            if values.is_empty() {
+                Vec::new()
+            } else {
+                order.iter().map(|&i| values[i]).collect()
+            }
+        }
+        self.positions = gather(&self.positions, &order);
+        self.normals = gather(&self.normals, &order);
+        self.texcoords = gather(&self.texcoords, &order);
+        self.colors = gather(&self.colors, &order);
+        self.indices = indices;
+        OptimizeStats {
+            before,
+            after: self.cache_stats(MeshData::DEFAULT_CACHE_SIZE),
+        }
+    }
+
+    /// Run `optimize_vertex_cache`, `optimize_overdraw` and `optimize_vertex_fetch` in turn,
+    /// with the default overdraw threshold. Returns the statistics before the first and after
+    /// the last.
+    pub fn optimize(&mut self, cache_size: usize) -> OptimizeStats {
+        let before = self.cache_stats(cache_size);
+        self.optimize_vertex_cache(cache_size);
+        self.optimize_overdraw(cache_size, MeshData::DEFAULT_OVERDRAW_THRESHOLD);
+        self.optimize_vertex_fetch();
+        OptimizeStats {
+            before,
+            after: self.cache_stats(cache_size),
+        }
+    }
+
+    /// Tipsify triangle order, followed by the triangles with out-of-range indices.
+    fn tipsify(&self, cache_size: usize) -> Vec<usize> {
+        let count = self.positions.len();
+        let triangles: Vec<[u32; 3]> = self.triangles().collect();
+        let valid = |t: &[u32; 3]| t.iter().all(|&i| (i as usize) < count);
+
+        let mut adjacent: Vec<Vec<usize>> = vec![Vec::new(); count];
+        for (t, triangle) in triangles.iter().enumerate().filter(|(_, t)| valid(t)) {
+            for &i in triangle {
+                adjacent[i as usize].push(t);
+            }
+        }
+        let mut live: Vec<usize> = adjacent.iter().map(Vec::len).collect();
+        // a vertex is in the cache while `time - stamp[v] < cache_size`
+        let mut stamp = vec![0usize; count];
+        let mut time = cache_size + 1;
+        let mut emitted = vec![false; triangles.len()];
+        let mut dead_ends: Vec<usize> = Vec::new();
+        let mut order = Vec::with_capacity(triangles.len());
+        let mut cursor = 0;
+
+        let mut fanning = (0..count).find(|&v| live[v] > 0);
+        while let Some(f) = fanning {
+            let mut candidates = Vec::new();
+            for &t in &adjacent[f] {
+                if emitted[t] {
+                    continue;
+                }
+                for &i in &triangles[t] {
+                    let v = i as usize;
+                    dead_ends.push(v);
+                    candidates.push(v);
+                    live[v] -= 1;
+                    if time - stamp[v] > cache_size {
+                        stamp[v] = time;
+                        time += 1;
+                    }
+                }
+                emitted[t] = true;
+                order.push(t);
+            }
+
+            // prefer the candidate which has been in the cache longest, as long as it stays
+            // there through its own fan
+            let mut best = None;
+            let mut best_priority = 0;
+            for &v in &candidates {
+                if live[v] == 0 {
+                    continue;
+                }
+                let mut priority = 1;
+                if time - stamp[v] + 2 * live[v] <= cache_size {
+                    priority += time - stamp[v];
+                }
+                if priority > best_priority {
+                    best = Some(v);
+                    best_priority = priority;
+                }
+            }
+            fanning = best.or_else(|| {
+                // dead end: go back to a recently used vertex, or else the next one with
+                // triangles left
+                while let Some(v) = dead_ends.pop() {
+                    if live[v] > 0 {
+                        return Some(v);
+                    }
+                }
+                while cursor < count {
+                    if live[cursor] > 0 {
+                        return Some(cursor);
+                    }
+                    cursor += 1;
+                }
+                None
+            });
+        }
+
+        order.extend((0..triangles.len()).filter(|&t| !emitted[t]));
+        order
+    }
+
+    /// Rearrange the triangles so that the `i`th is the `order[i]`th of before.
+    fn reorder_triangles(&mut self, order: &[usize]) {
+        let indices = order
+            .iter()
+            .flat_map(|&t| self.indices[3 * t..3 * t + 3].to_vec())
+            .collect();
+        self.indices = indices;
+    }
+
+    /// Centroid of `triangle`, and its normal scaled by its area.
+    fn triangle_centroid(&self, triangle: [u32; 3]) -> (glm::Vec3, glm::Vec3) {
+        let [a, b, c] = triangle.map(|i| self.positions[i as usize]);
+        ((a + b + c) / 3.0, glm::cross(b - a, c - a) / 2.0)
+    }
+}
+
+/// Starts of clusters, and the end of the triangles, where the cache simulation misses on
+/// every vertex of a triangle, which is where the order jumps to a new part of the mesh.
+fn hard_boundaries(triangles: &[[u32; 3]], cache_size: usize) -> Vec<usize> {
+    let mut cache = FifoCache::new(cache_size);
+    let mut boundaries: Vec<usize> = triangles
+        .iter()
+        .enumerate()
+        .filter(|&(t, &triangle)| cache.fetch_triangle(triangle) == 3 || t == 0)
+        .map(|(t, _)| t)
+        .collect();
+    boundaries.push(triangles.len());
+    boundaries
+}
+
+/// Split each cluster between `hard` boundaries further, wherever the ACMR of the triangles
+/// since the last split, starting from an empty cache, is within `threshold` of that of the
+/// whole cluster.
+fn soft_boundaries(
+    triangles: &[[u32; 3]],
+    hard: &[usize],
+    cache_size: usize,
+    threshold: f32,
+) -> Vec<usize> {
+    let mut cache = FifoCache::new(cache_size);
+    let mut boundaries = Vec::new();
+    for bounds in hard.windows(2) {
+        let (start, end) = (bounds[0], bounds[1]);
+        cache.clear();
+        let misses: usize = triangles[start..end]
+            .iter()
+            .map(|&t| cache.fetch_triangle(t))
+            .sum();
+        let limit = threshold * misses as f32 / (end - start) as f32;
+
+        cache.clear();
+        boundaries.push(start);
+        let (mut split, mut misses) = (start, 0);
+        for (t, &triangle) in triangles.iter().enumerate().take(end).skip(start) {
+            misses += cache.fetch_triangle(triangle);
+            if t + 1 < end && misses as f32 / (t + 1 - split) as f32 <= limit {
+                boundaries.push(t + 1);
+                cache.clear();
+                split = t + 1;
+                misses = 0;
+            }
+        }
+    }
+    boundaries.push(triangles.len());
+    boundaries
+}
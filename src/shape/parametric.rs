@@ -0,0 +1,247 @@
+use glm;
+use std::collections::HashMap;
+
+use crate::rendergl;
+use crate::shape::{MeshData, ShapeGL};
+use crate::util::{Aabb, SurfacePoint};
+
+type SurfaceFn<'a, T> = Box<dyn Fn(f32, f32) -> T + 'a>;
+
+/// Builder for a `ShapeGL` tessellating the surface traced out by a function `f(u, v)`.
+///
+/// The surface is sampled on an evenly spaced grid over the `u` and `v` ranges. Samples which
+/// land on the same position (such as along the seam of a closed surface, or at a pole where a
+/// whole row collapses to a point) are welded together: triangles which collapse are left out,
+/// and normals are shared across the weld.
+///
+/// Normals and texture coordinates may be given as closures too. Otherwise each normal is the
+/// area-weighted average of the normals of the triangles around its position, and texture
+/// coordinates run from 0 to 1 across the `u` and `v` ranges. Vertices are only shared between
+/// welded samples which also agree on these, so that textures do not wrap around at a seam.
+///
+/// Triangles are wound counter-clockwise around `df/du x df/dv`, which is the direction normals
+/// point in; swap `u` and `v` to turn a surface inside out.
+///
+/// # Examples
+///
+/// Plot a ripple, `y = sin(r) / r`, over a square:
+/// ```rust,ignore
+/// let ripple = ParametricSurface::new(|u, v| {
+///         let r = (u * u + v * v).sqrt().max(1e-6);
+///         glm::vec3(u, r.sin() / r, v)
+///     })
+///     .u_range(-10.0, 10.0)
+///     .v_range(10.0, -10.0)
+///     .resolution(200, 200)
+///     .build::<VertexNT>();
+/// ```
+pub struct ParametricSurface<'a> {
+    position: SurfaceFn<'a, glm::Vec3>,
+    normal: Option<SurfaceFn<'a, glm::Vec3>>,
+    texcoord: Option<SurfaceFn<'a, glm::Vec2>>,
+    u_range: (f32, f32),
+    v_range: (f32, f32),
+    resolution: (u32, u32),
+}
+
+impl<'a> ParametricSurface<'a> {
+    /// Samples closer together than this fraction of the surface's size are welded.
+    const WELD_TOLERANCE: f32 = 1e-5;
+
+    /// Construct a builder for the surface traced out by `position(u, v)`, with `u` and `v` both
+    /// ranging over `[0, 1]` in 32 steps.
+    pub fn new<F: Fn(f32, f32) -> glm::Vec3 + 'a>(position: F) -> ParametricSurface<'a> {
+        ParametricSurface {
+            position: Box::new(position),
+            normal: None,
+            texcoord: None,
+            u_range: (0.0, 1.0),
+            v_range: (0.0, 1.0),
+            resolution: (32, 32),
+        }
+    }
+
+    /// Use `normal(u, v)` for normals, rather than estimating them from the tessellation. It
+    /// need not return unit vectors.
+    pub fn normal<F: Fn(f32, f32) -> glm::Vec3 + 'a>(mut self, normal: F) -> Self {
+        self.normal = Some(Box::new(normal));
+        self
+    }
+
+    /// Use `texcoord(u, v)` for texture coordinates.
+    pub fn texcoord<F: Fn(f32, f32) -> glm::Vec2 + 'a>(mut self, texcoord: F) -> Self {
+        self.texcoord = Some(Box::new(texcoord));
+        self
+    }
+
+    pub fn u_range(mut self, start: f32, end: f32) -> Self {
+        self.u_range = (start, end);
+        self
+    }
+
+    pub fn v_range(mut self, start: f32, end: f32) -> Self {
+        self.v_range = (start, end);
+        self
+    }
+
+    /// Number of steps to divide the `u` and `v` ranges into.
+    pub fn resolution(mut self, u_steps: u32, v_steps: u32) -> Self {
+        self.resolution = (u_steps.max(1), v_steps.max(1));
+        self
+    }
+
+    /// Tessellate the surface into a `ShapeGL` of `GlLayout::Triangles`.
+    pub fn build<T: rendergl::Vertex>(&self) -> ShapeGL {
+        ShapeGL::from_data::<T>(&self.mesh_data())
+    }
+
+    /// Parameters `(u, v)` of sample `(i, j)` of the grid.
+    fn param(&self, i: u32, j: u32) -> (f32, f32) {
+        let (u0, u1) = self.u_range;
+        let (v0, v1) = self.v_range;
+        let (us, vs) = self.resolution;
+        (
+            u0 + (u1 - u0) * i as f32 / us as f32,
+            v0 + (v1 - v0) * j as f32 / vs as f32,
+        )
+    }
+
+    /// Tessellate the surface into triangles on the CPU.
+    pub fn mesh_data(&self) -> MeshData {
+        let (us, vs) = self.resolution;
+        let stride = us + 1;
+        let samples: Vec<(f32, f32)> = (0..=vs)
+            .flat_map(|j| (0..=us).map(move |i| (i, j)))
+            .map(|(i, j)| self.param(i, j))
+            .collect();
+        let sampled: Vec<glm::Vec3> = samples
+            .iter()
+            .map(|&(u, v)| (self.position)(u, v))
+            .collect();
+
+        // weld samples to distinct positions
+        let size = glm::length(Aabb::from_points(&sampled).size());
+        let mut welder = Welder::new(size * ParametricSurface::WELD_TOLERANCE);
+        let welded: Vec<usize> = sampled.iter().map(|p| welder.weld(*p)).collect();
+
+        // triangles between samples, leaving out any which have collapsed
+        let mut triangles: Vec<[usize; 3]> = Vec::new();
+        for j in 0..vs {
+            for i in 0..us {
+                let a = (j * stride + i) as usize;
+                let (b, c) = (a + 1, a + stride as usize);
+                for &triangle in &[[a, b, c + 1], [a, c + 1, c]] {
+                    let [p, q, r] = triangle.map(|s| welded[s]);
+                    if p != q && q != r && p != r {
+                        triangles.push(triangle);
+                    }
+                }
+            }
+        }
+
+        // estimated normals, accumulated per welded position
+        let mut position_normals = vec![glm::to_vec3(0.0); welder.positions.len()];
+        if self.normal.is_none() {
+            for triangle in &triangles {
+                let [a, b, c] = triangle.map(|s| sampled[s]);
+                // the cross product's length is twice the area, weighting larger triangles more
+                let n = glm::cross(b - a, c - a);
+                for &s in triangle {
+                    position_normals[welded[s]] = position_normals[welded[s]] + n;
+                }
+            }
+        }
+
+        let mut data = MeshData::default();
+        let mut emitted: HashMap<(usize, [u32; 5]), u32> = HashMap::new();
+        for triangle in &triangles {
+            for &s in triangle {
+                let (u, v) = samples[s];
+                let normal = match self.normal {
+                    Some(ref normal) => normal(u, v),
+                    None => position_normals[welded[s]],
+                };
+                let normal = if glm::length(normal) > 0.0 {
+                    glm::normalize(normal)
+                } else {
+                    normal
+                };
+                let texcoord = match self.texcoord {
+                    Some(ref texcoord) => texcoord(u, v),
+                    None => {
+                        let (us, vs) = self.resolution;
+                        let (i, j) = (s as u32 % stride, s as u32 / stride);
+                        glm::vec2(i as f32 / us as f32, j as f32 / vs as f32)
+                    }
+                };
+
+                let key = (
+                    welded[s],
+                    [
+                        normal.x.to_bits(),
+                        normal.y.to_bits(),
+                        normal.z.to_bits(),
+                        texcoord.x.to_bits(),
+                        texcoord.y.to_bits(),
+                    ],
+                );
+                let index = *emitted.entry(key).or_insert_with(|| {
+                    let p = SurfacePoint::Parametric {
+                        position: welder.positions[welded[s]],
+                        normal,
+                        texcoord,
+                    };
+                    data.push_point(&p)
+                });
+                data.indices.push(index);
+            }
+        }
+        data
+    }
+}
+
+/// Merges positions within `tolerance` of each other, using a hash grid of cells `tolerance`
+/// wide.
+struct Welder {
+    tolerance: f32,
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+    positions: Vec<glm::Vec3>,
+}
+
+impl Welder {
+    fn new(tolerance: f32) -> Welder {
+        Welder {
+            tolerance: tolerance.max(f32::MIN_POSITIVE),
+            cells: HashMap::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    fn cell(&self, p: glm::Vec3) -> (i64, i64, i64) {
+        let c = |x: f32| (x / self.tolerance).floor() as i64;
+        (c(p.x), c(p.y), c(p.z))
+    }
+
+    /// Index of the position `p` is welded to, adding a new one if there is none close enough.
+    fn weld(&mut self, p: glm::Vec3) -> usize {
+        let (x, y, z) = self.cell(p);
+        // a position within tolerance may be just across the border of a neighbouring cell
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(indices) = self.cells.get(&(x + dx, y + dy, z + dz)) {
+                        for &i in indices {
+                            if glm::distance(self.positions[i], p) <= self.tolerance {
+                                return i;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.positions.push(p);
+        let index = self.positions.len() - 1;
+        self.cells.entry((x, y, z)).or_default().push(index);
+        index
+    }
+}
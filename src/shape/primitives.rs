@@ -0,0 +1,400 @@
+//! Procedural primitives beyond `MeshData::sphere` and `MeshData::cylinder`.
+//!
+//! Like those, every generator builds its vertices from `SurfacePoint`s into a `MeshData`, with
+//! positions, normals and UVs, so that `ShapeGL` can upload it in any `rendergl::Vertex` layout.
+//! All primitives are centred on the origin and fit in a unit cube, and are wound
+//! counter-clockwise when seen from outside.
+
+use glm::ext::consts;
+use std::collections::HashMap;
+
+use crate::shape::MeshData;
+use crate::util::SurfacePoint;
+
+/// Append a `rows` by `cols` grid of quads, with vertices at `point(row, col)` for `row` in
+/// `0..=rows` and `col` in `0..=cols`.
+///
+/// Seen from outside, columns must run to the right and rows downwards. Triangles which have
+/// collapsed to a point or a line, such as at the poles of a sphere, are left out.
+fn grid<F>(rows: u32, cols: u32, point: F, data: &mut MeshData)
+where
+    F: Fn(u32, u32) -> SurfacePoint,
+{
+    let start = data.positions.len() as u32;
+    let mut positions = Vec::new();
+    for row in 0..=rows {
+        for col in 0..=cols {
+            let p = point(row, col);
+            positions.push(p.position());
+            data.push_point(&p);
+        }
+    }
+
+    let stride = cols + 1;
+    let mut triangle = |a: u32, b: u32, c: u32| {
+        let (pa, pb, pc) = (
+            positions[a as usize],
+            positions[b as usize],
+            positions[c as usize],
+        );
+        let apart = |p: glm::Vec3, q: glm::Vec3| glm::distance(p, q) > 1e-6;
+        if apart(pa, pb) && apart(pb, pc) && apart(pa, pc) {
+            data.indices
+                .extend_from_slice(&[start + a, start + b, start + c]);
+        }
+    };
+    for row in 0..rows {
+        for col in 0..cols {
+            let a = row * stride + col;
+            let (b, c) = (a + 1, a + stride);
+            triangle(a, c, b);
+            triangle(b, c, c + 1);
+        }
+    }
+}
+
+/// Longitude of column `col` of `slices`, decreasing so that columns run to the right when seen
+/// from outside (see `MeshData::sphere`).
+fn longitude(col: u32, slices: u32) -> f32 {
+    -2.0 * consts::pi::<f32, f32>() * col as f32 / slices as f32
+}
+
+impl MeshData {
+    /// Generate vertices for a unit cube, with each face split into `subdivisions` by
+    /// `subdivisions` quads. Every face is textured with the whole of `[0, 1]` in UV space.
+    pub fn cube(subdivisions: u32) -> MeshData {
+        let mut data = MeshData::default();
+
+        const R: f32 = SurfacePoint::R;
+        let n = subdivisions.max(1);
+        let step = 2.0 * R / n as f32;
+        let normals = [
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(-1.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(0.0, -1.0, 0.0),
+            glm::vec3(0.0, 0.0, 1.0),
+            glm::vec3(0.0, 0.0, -1.0),
+        ];
+        for &normal in &normals {
+            let point = |row: u32, col: u32| SurfacePoint::Cube {
+                normal,
+                s: -R + step * col as f32,
+                t: R - step * row as f32,
+            };
+            grid(n, n, point, &mut data);
+        }
+
+        data
+    }
+}
+
+impl MeshData {
+    /// Generate vertices for a unit square in the xz-plane facing up, as a grid of `x_cells` by
+    /// `z_cells` quads.
+    pub fn plane(x_cells: u32, z_cells: u32) -> MeshData {
+        let mut data = MeshData::default();
+
+        const R: f32 = SurfacePoint::R;
+        let (cols, rows) = (x_cells.max(1), z_cells.max(1));
+        let point = |row: u32, col: u32| SurfacePoint::Plane {
+            x: -R + 2.0 * R * col as f32 / cols as f32,
+            z: -R + 2.0 * R * row as f32 / rows as f32,
+        };
+        grid(rows, cols, point, &mut data);
+
+        data
+    }
+}
+
+impl MeshData {
+    /// Generate vertices for a unit disk (unit diameter) in the xz-plane facing up.
+    ///
+    /// # Arguments
+    ///
+    /// * `rings`: number of radial subdivisions
+    /// * `slices`: number of angular subdivisions
+    pub fn disk(rings: u32, slices: u32) -> MeshData {
+        let mut data = MeshData::default();
+
+        const R: f32 = SurfacePoint::R;
+        let (rings, slices) = (rings.max(1), slices.max(3));
+        // seen from above, increasing angle runs downwards and increasing radius to the right
+        let point = |row: u32, col: u32| SurfacePoint::Disk {
+            r: R * col as f32 / rings as f32,
+            theta: -longitude(row, slices),
+            y: 0.0,
+        };
+        grid(slices, rings, point, &mut data);
+
+        data
+    }
+}
+
+impl MeshData {
+    /// Generate vertices for a unit cone (unit base diameter, unit height) with its apex at the
+    /// top.
+    ///
+    /// # Arguments
+    ///
+    /// * `strips`: number of vertical subdivisions (and radial subdivisions of the base)
+    /// * `slices`: number of angular subdivisions
+    pub fn cone(strips: u32, slices: u32) -> MeshData {
+        let mut data = MeshData::default();
+
+        const R: f32 = SurfacePoint::R;
+        let (strips, slices) = (strips.max(1), slices.max(3));
+        let half_slice = longitude(1, slices) / 2.0;
+
+        let side = |row: u32, col: u32| {
+            // the apex only has a direction within each slice, so face it mid-way across
+            let theta = if row == 0 {
+                longitude(col, slices) - half_slice
+            } else {
+                longitude(col, slices)
+            };
+            SurfacePoint::Cone {
+                theta,
+                y: R - 2.0 * R * row as f32 / strips as f32,
+            }
+        };
+        grid(strips, slices, side, &mut data);
+
+        // seen from below, decreasing angle runs downwards
+        let base = |row: u32, col: u32| SurfacePoint::Disk {
+            r: R * col as f32 / strips as f32,
+            theta: longitude(row, slices),
+            y: -R,
+        };
+        grid(slices, strips, base, &mut data);
+
+        data
+    }
+}
+
+impl MeshData {
+    /// Radius of the tube of `MeshData::torus`.
+    pub const TORUS_MINOR_RADIUS: f32 = 0.15;
+
+    /// Generate vertices for a torus around the y-axis, with unit outer diameter and a tube of
+    /// radius `TORUS_MINOR_RADIUS`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rings`: number of subdivisions around the tube
+    /// * `slices`: number of subdivisions around the y-axis
+    pub fn torus(rings: u32, slices: u32) -> MeshData {
+        let mut data = MeshData::default();
+
+        let minor = MeshData::TORUS_MINOR_RADIUS;
+        let major = SurfacePoint::R - minor;
+        let (rings, slices) = (rings.max(3), slices.max(3));
+        // going round the tube from the outside, downwards first
+        let point = |row: u32, col: u32| SurfacePoint::Torus {
+            major,
+            minor,
+            theta: longitude(col, slices),
+            phi: longitude(row, rings),
+        };
+        grid(rings, slices, point, &mut data);
+
+        data
+    }
+}
+
+impl MeshData {
+    /// Generate vertices for a capsule of unit height and half unit diameter, standing along the
+    /// y-axis. The texture's v coordinate runs evenly down the profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `cap_strips`: number of vertical subdivisions of each hemispherical cap
+    /// * `side_strips`: number of vertical subdivisions of the cylindrical side
+    /// * `slices`: number of angular subdivisions
+    pub fn capsule(cap_strips: u32, side_strips: u32, slices: u32) -> MeshData {
+        let mut data = MeshData::default();
+
+        let r = SurfacePoint::R / 2.0;
+        let half_height = SurfacePoint::R - r;
+        let (cap_strips, side_strips, slices) =
+            (cap_strips.max(1), side_strips.max(1), slices.max(3));
+
+        // distances down the profile of each row, so that rows fall exactly on the seams
+        // between the caps and the side
+        let quarter = consts::half_pi::<f32, f32>() * r;
+        let length = 2.0 * quarter + 2.0 * half_height;
+        let mut profile: Vec<f32> = (0..cap_strips)
+            .map(|i| quarter * i as f32 / cap_strips as f32)
+            .collect();
+        profile.extend(
+            (0..side_strips).map(|i| quarter + 2.0 * half_height * i as f32 / side_strips as f32),
+        );
+        profile.extend(
+            (0..=cap_strips).map(|i| length - quarter + quarter * i as f32 / cap_strips as f32),
+        );
+
+        let rows = profile.len() as u32 - 1;
+        let half_slice = longitude(1, slices) / 2.0;
+        let point = |row: u32, col: u32| {
+            // the poles only have a direction within each slice, so face them mid-way across
+            let theta = if row == 0 {
+                longitude(col, slices) - half_slice
+            } else if row == rows {
+                longitude(col, slices) + half_slice
+            } else {
+                longitude(col, slices)
+            };
+            SurfacePoint::Capsule {
+                r,
+                half_height,
+                theta,
+                s: profile[row as usize] / length,
+            }
+        };
+        grid(rows, slices, point, &mut data);
+
+        data
+    }
+}
+
+impl MeshData {
+    /// Generate vertices for a unit geodesic sphere (unit diameter), by splitting each face of
+    /// an icosahedron into four `subdivisions` times. Unlike `MeshData::sphere`, its triangles
+    /// are all close to the same size.
+    ///
+    /// Texture coordinates are the same as for `MeshData::sphere`; vertices on the texture's
+    /// seam and at the poles are duplicated so that no triangle wraps around the texture.
+    pub fn icosphere(subdivisions: u32) -> MeshData {
+        let (positions, faces) = icosahedron(subdivisions);
+
+        let mut data = MeshData::default();
+        // one vertex per position and longitude
+        let mut emitted: HashMap<(u32, u32), u32> = HashMap::new();
+
+        let pi: f32 = consts::pi();
+        let is_pole = |d: &glm::Vec3| d.y.abs() > 1.0 - 1e-6;
+        for face in &faces {
+            let dirs = [
+                positions[face[0] as usize],
+                positions[face[1] as usize],
+                positions[face[2] as usize],
+            ];
+            let mut thetas = [
+                dirs[0].z.atan2(dirs[0].x),
+                dirs[1].z.atan2(dirs[1].x),
+                dirs[2].z.atan2(dirs[2].x),
+            ];
+
+            // keep the triangle on one side of the seam at longitude +/-pi
+            let max = (0..3)
+                .filter(|&k| !is_pole(&dirs[k]))
+                .map(|k| thetas[k])
+                .fold(-pi, f32::max);
+            for theta in &mut thetas {
+                if max - *theta > pi {
+                    *theta += 2.0 * pi;
+                }
+            }
+            // a pole has no longitude of its own, so use that of the rest of the triangle
+            for k in 0..3 {
+                if is_pole(&dirs[k]) {
+                    thetas[k] = (thetas[(k + 1) % 3] + thetas[(k + 2) % 3]) / 2.0;
+                }
+            }
+
+            for k in 0..3 {
+                let theta = thetas[k];
+                let pole = is_pole(&dirs[k]);
+                let (up, phi) = (dirs[k].y.signum(), dirs[k].y.clamp(-1.0, 1.0).acos());
+                let index = *emitted
+                    .entry((face[k], theta.to_bits()))
+                    .or_insert_with(|| {
+                        let p = SurfacePoint::Sphere {
+                            r: SurfacePoint::R,
+                            theta,
+                            phi: if pole { pi * (1.0 - up) / 2.0 } else { phi },
+                        };
+                        let i = data.push_point(&p);
+                        // the copies of a pole must meet exactly, but their longitudes would
+                        // spread any error around it into a ring
+                        if pole {
+                            data.positions[i as usize] = glm::vec3(0.0, up * SurfacePoint::R, 0.0);
+                            data.normals[i as usize] = glm::vec3(0.0, up, 0.0);
+                        }
+                        i
+                    });
+                data.indices.push(index);
+            }
+        }
+
+        data
+    }
+}
+
+/// Unit vectors to the vertices of an icosahedron subdivided `subdivisions` times, and its
+/// faces wound counter-clockwise seen from outside.
+fn icosahedron(subdivisions: u32) -> (Vec<glm::Vec3>, Vec<[u32; 3]>) {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let mut positions: Vec<glm::Vec3> = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| glm::normalize(glm::vec3(x, y, z)))
+    .collect();
+    let mut faces: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // midpoints shared between the two faces on either side of each edge
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, positions: &mut Vec<glm::Vec3>| {
+            let key = (a.min(b), a.max(b));
+            *midpoints.entry(key).or_insert_with(|| {
+                let m = glm::normalize(positions[a as usize] + positions[b as usize]);
+                positions.push(m);
+                positions.len() as u32 - 1
+            })
+        };
+        faces = faces
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let ab = midpoint(a, b, &mut positions);
+                let bc = midpoint(b, c, &mut positions);
+                let ca = midpoint(c, a, &mut positions);
+                vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+    (positions, faces)
+}
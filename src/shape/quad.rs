@@ -1,3 +1,4 @@
+use crate::animation::Pose;
 use crate::camera::Camera;
 use crate::rendergl;
 use crate::rendergl::types::*;
@@ -14,6 +15,7 @@ pub struct Quad {
     ibo: rendergl::IBO,
     vao: rendergl::VAO,
     transform: glm::Mat4,
+    pose: Pose,
     time: f32,
 }
 
@@ -41,6 +43,7 @@ impl Quad {
             ibo,
             vao,
             transform: glm::ext::scale(&num::one(), glm::vec3(2.0, 2.0, 1.0)),
+            pose: Pose::default(),
             time: 0.,
         }
     }
@@ -55,6 +58,7 @@ impl Drawable for Quad {
         self.program.bind();
         self.program.set_uniform("u_time", &self.time)?;
         self.program.set_uniform("m", &self.transform)?;
+        self.pose.apply_uniforms(&self.program)?;
 
         self.vao.bind();
         self.ibo.bind();
@@ -64,4 +68,22 @@ impl Drawable for Quad {
 
         Ok(())
     }
+
+    fn draw_ids(&self, program: &rendergl::Program) -> Result<(), DrawError> {
+        program.set_uniform("model", &self.transform)?;
+        program.set_uniform("submesh", &0u32)?;
+        self.vao.bind();
+        self.ibo.bind();
+        self.vao.draw();
+        self.ibo.unbind();
+        self.vao.unbind();
+        Ok(())
+    }
+
+    fn apply_pose(&mut self, pose: &Pose) {
+        if let Some(transform) = pose.transform {
+            self.transform = transform;
+        }
+        self.pose = pose.clone();
+    }
 }
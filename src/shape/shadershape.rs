@@ -1,10 +1,12 @@
 use image::DynamicImage;
 use std::path::Path;
 
+use crate::animation::Pose;
 use crate::camera::Camera;
 use crate::rendergl;
 use crate::resources;
 use crate::shape::*;
+use crate::util::Aabb;
 
 /// Shape that uses the "shader" vertex and fragment shaders.
 pub struct ShaderShape {
@@ -12,6 +14,7 @@ pub struct ShaderShape {
     shapegl: ShapeGL,
     texture: rendergl::texture::Texture,
     transform: glm::Mat4,
+    pose: Pose,
     time: f32,
 }
 type ShaderVertex = rendergl::VertexNT;
@@ -43,6 +46,7 @@ impl ShaderShape {
             shapegl,
             texture,
             transform: num::one(),
+            pose: Pose::default(),
             time: 0.0,
         })
     }
@@ -82,10 +86,28 @@ impl Drawable for ShaderShape {
             .set_uniform("perspective", &camera.perspective)?;
         self.program.set_uniform("model", &self.transform)?;
         self.program.set_uniform("u_time", &self.time)?;
+        self.pose.apply_uniforms(&self.program)?;
 
-        self.shapegl.draw_vertices();
+        self.shapegl.draw_vertices(&self.program)?;
         self.texture.unbind();
 
         Ok(())
     }
+
+    fn apply_pose(&mut self, pose: &Pose) {
+        if let Some(transform) = pose.transform {
+            self.transform = transform;
+        }
+        self.pose = pose.clone();
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.shapegl.bounds().transform(&self.transform))
+    }
+
+    fn draw_ids(&self, program: &rendergl::Program) -> Result<(), DrawError> {
+        program.set_uniform("model", &self.transform)?;
+        program.set_uniform("submesh", &0u32)?;
+        self.shapegl.draw_vertices(program)
+    }
 }
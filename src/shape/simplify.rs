@@ -0,0 +1,391 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use glm;
+
+use crate::shape::MeshData;
+
+/// Symmetric 4x4 quadric `[a b c d; b e f g; c f h i; d g i j]`, the sum of squared distances
+/// to a set of planes.
+#[derive(Copy, Clone, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Quadric of the plane through `point` with unit `normal`, scaled by `weight`.
+    fn plane(normal: glm::Vec3, point: glm::Vec3, weight: f64) -> Quadric {
+        let [a, b, c] = [normal.x, normal.y, normal.z].map(f64::from);
+        let d = -(a * f64::from(point.x) + b * f64::from(point.y) + c * f64::from(point.z));
+        let q = [
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ];
+        Quadric(q.map(|x| x * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (x, y) in self.0.iter_mut().zip(&other.0) {
+            *x += y;
+        }
+    }
+
+    /// Squared distance error of `p`.
+    fn error(&self, p: glm::Vec3) -> f64 {
+        let [x, y, z] = [p.x, p.y, p.z].map(f64::from);
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// Candidate collapse of position `from` onto position `to`, ordered so the cheapest comes
+/// first out of a `BinaryHeap`. The versions of both ends detect candidates made stale by
+/// earlier collapses.
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Working state of a simplification. Vertices of the mesh at the same position are collapsed
+/// together, so they are grouped by position; attribute seams are where a position has more
+/// than one vertex.
+struct Simplifier<'a> {
+    mesh: &'a MeshData,
+    /// Position of each vertex.
+    position_of: Vec<usize>,
+    positions: Vec<glm::Vec3>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    /// Triangles around each position, including some which have since been removed.
+    around: Vec<Vec<usize>>,
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+}
+
+impl MeshData {
+    /// Weight of the planes which keep open borders in place, relative to those of triangles.
+    const BORDER_WEIGHT: f64 = 100.0;
+
+    /// Simplify the mesh to at most `target_triangles` triangles, if it can, by collapsing
+    /// edges in order of the quadric error metric (Garland and Heckbert).
+    ///
+    /// Each collapse moves one end of an edge onto the other, so the remaining vertices keep
+    /// their attributes. Open borders are kept in place, collapses that would flip triangles are
+    /// skipped, and attribute seams (such as texture seams) are only collapsed along themselves,
+    /// so they may limit how far a mesh can be simplified. Point clouds are returned as they
+    /// are.
+    pub fn simplify(&self, target_triangles: usize) -> MeshData {
+        if self.is_point_cloud() {
+            return self.clone();
+        }
+        let mut simplifier = Simplifier::new(self);
+        simplifier.run(target_triangles);
+        simplifier.finish()
+    }
+
+    /// Chain of `levels` levels of detail, starting with the mesh itself, with each level
+    /// simplified to `ratio` times the triangles of the one before. The chain stops early if a
+    /// level cannot be simplified any further.
+    pub fn lod_chain(&self, levels: usize, ratio: f32) -> Vec<MeshData> {
+        let mut chain = vec![self.clone()];
+        while chain.len() < levels {
+            let last = &chain[chain.len() - 1];
+            let triangles = last.indices.len() / 3;
+            let target = (triangles as f32 * ratio) as usize;
+            let next = last.simplify(target);
+            if next.indices.len() / 3 >= triangles {
+                break;
+            }
+            chain.push(next);
+        }
+        chain
+    }
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a MeshData) -> Simplifier<'a> {
+        let (positions, position_of) = mesh.weld_positions();
+        let triangles: Vec<[u32; 3]> = mesh.triangles().collect();
+        let mut simplifier = Simplifier {
+            mesh,
+            position_of,
+            quadrics: vec![Quadric::default(); positions.len()],
+            versions: vec![0; positions.len()],
+            around: vec![Vec::new(); positions.len()],
+            positions,
+            alive: vec![true; triangles.len()],
+            triangles,
+        };
+        simplifier.init_quadrics();
+        simplifier
+    }
+
+    fn corners(&self, t: usize) -> [usize; 3] {
+        self.triangles[t].map(|i| self.position_of[i as usize])
+    }
+
+    /// Unnormalised normal of a triangle with corners at `corners`, whose length is twice its
+    /// area.
+    fn area_normal(&self, corners: [usize; 3]) -> glm::Vec3 {
+        let [a, b, c] = corners.map(|p| self.positions[p]);
+        glm::cross(b - a, c - a)
+    }
+
+    fn init_quadrics(&mut self) {
+        // triangles on each side of each edge, to find open borders
+        let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+        for t in 0..self.triangles.len() {
+            let corners = self.corners(t);
+            for k in 0..3 {
+                self.around[corners[k]].push(t);
+                let (a, b) = (corners[k], corners[(k + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+
+            let n = self.area_normal(corners);
+            let area = glm::length(n) / 2.0;
+            if area > 0.0 {
+                let q = Quadric::plane(n / (2.0 * area), self.positions[corners[0]], area.into());
+                for &p in &corners {
+                    self.quadrics[p].add(&q);
+                }
+            }
+        }
+
+        // a plane through each border edge, perpendicular to its triangle, holds it in place
+        for t in 0..self.triangles.len() {
+            let corners = self.corners(t);
+            let n = self.area_normal(corners);
+            for k in 0..3 {
+                let (a, b) = (corners[k], corners[(k + 1) % 3]);
+                if edges[&(a.min(b), a.max(b))] != 1 {
+                    continue;
+                }
+                let edge = self.positions[b] - self.positions[a];
+                let perpendicular = glm::cross(edge, n);
+                if glm::length(perpendicular) > 0.0 {
+                    let weight = MeshData::BORDER_WEIGHT * f64::from(glm::dot(edge, edge));
+                    let q =
+                        Quadric::plane(glm::normalize(perpendicular), self.positions[a], weight);
+                    self.quadrics[a].add(&q);
+                    self.quadrics[b].add(&q);
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, target_triangles: usize) {
+        let mut heap = BinaryHeap::new();
+        for p in 0..self.positions.len() {
+            self.push_candidates(p, &mut heap);
+        }
+
+        let mut remaining = self.triangles.len();
+        while remaining > target_triangles {
+            let collapse = match heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            let (from, to) = (collapse.from, collapse.to);
+            if collapse.versions != (self.versions[from], self.versions[to]) {
+                continue;
+            }
+            if let Some(remap) = self.collapse_remap(from, to) {
+                remaining -= self.collapse(from, to, &remap);
+                self.push_candidates(to, &mut heap);
+            }
+        }
+    }
+
+    /// Queue the collapses of `p` onto each neighbour, and of each neighbour onto `p`.
+    fn push_candidates(&self, p: usize, heap: &mut BinaryHeap<Collapse>) {
+        for q in self.neighbours(p) {
+            let mut quadric = self.quadrics[p];
+            quadric.add(&self.quadrics[q]);
+            for &(from, to) in &[(p, q), (q, p)] {
+                heap.push(Collapse {
+                    cost: quadric.error(self.positions[to]),
+                    from,
+                    to,
+                    versions: (self.versions[from], self.versions[to]),
+                });
+            }
+        }
+    }
+
+    /// Positions which share a triangle with `p`.
+    fn neighbours(&self, p: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self
+            .live_around(p)
+            .flat_map(|t| self.corners(t).to_vec())
+            .filter(|&q| q != p)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    fn live_around(&self, p: usize) -> impl Iterator<Item = usize> + '_ {
+        self.around[p]
+            .iter()
+            .cloned()
+            .filter(move |&t| self.alive[t])
+    }
+
+    /// Vertex at `to` which each vertex at `from` becomes, if `from` can be collapsed onto `to`.
+    ///
+    /// Each vertex at `from` must share a triangle with exactly one vertex at `to`, which keeps
+    /// attribute seams intact. The collapse must also keep the surface manifold, so the only
+    /// neighbours the ends share are the far corners of the triangles on the edge, and must not
+    /// flip any triangle which survives it.
+    fn collapse_remap(&self, from: usize, to: usize) -> Option<HashMap<u32, u32>> {
+        let mut remap: HashMap<u32, u32> = HashMap::new();
+        let mut opposite = Vec::new();
+        for t in self.live_around(from) {
+            let triangle = self.triangles[t];
+            let corners = self.corners(t);
+            if corners.contains(&to) {
+                let k_from = corners.iter().position(|&p| p == from)?;
+                let k_to = corners.iter().position(|&p| p == to)?;
+                let (a, b) = (triangle[k_from], triangle[k_to]);
+                if *remap.entry(a).or_insert(b) != b {
+                    return None;
+                }
+                opposite.extend(corners.iter().filter(|&&p| p != from && p != to));
+            } else {
+                let before = self.area_normal(corners);
+                let after = self.area_normal(corners.map(|p| if p == from { to } else { p }));
+                let (length_before, length_after) = (glm::length(before), glm::length(after));
+                if length_before > 0.0
+                    && glm::dot(before, after) <= 0.2 * length_before * length_after
+                {
+                    return None;
+                }
+            }
+        }
+
+        let to_neighbours = self.neighbours(to);
+        let shared = self
+            .neighbours(from)
+            .into_iter()
+            .filter(|p| to_neighbours.binary_search(p).is_ok());
+        if shared.into_iter().any(|p| !opposite.contains(&p)) {
+            return None;
+        }
+
+        let vertices_at_from = self
+            .live_around(from)
+            .flat_map(|t| {
+                let corners = self.corners(t);
+                let triangle = self.triangles[t];
+                (0..3)
+                    .filter(move |&k| corners[k] == from)
+                    .map(move |k| triangle[k])
+            })
+            .collect::<Vec<u32>>();
+        if vertices_at_from.iter().all(|i| remap.contains_key(i)) {
+            Some(remap)
+        } else {
+            None
+        }
+    }
+
+    /// Collapse `from` onto `to`, returning the number of triangles removed.
+    fn collapse(&mut self, from: usize, to: usize, remap: &HashMap<u32, u32>) -> usize {
+        let mut removed = 0;
+        let moved: Vec<usize> = self.live_around(from).collect();
+        for t in moved {
+            if self.corners(t).contains(&to) {
+                self.alive[t] = false;
+                removed += 1;
+                continue;
+            }
+            for i in self.triangles[t].iter_mut() {
+                if let Some(&j) = remap.get(i) {
+                    *i = j;
+                }
+            }
+            self.around[to].push(t);
+        }
+        for (&i, &j) in remap {
+            self.position_of[i as usize] = self.position_of[j as usize];
+        }
+
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+        self.around[from].clear();
+        self.versions[from] += 1;
+        self.versions[to] += 1;
+        removed
+    }
+
+    /// Mesh of the remaining triangles, with only the vertices they use.
+    fn finish(self) -> MeshData {
+        let mesh = self.mesh;
+        let mut data = MeshData::default();
+        let mut new_index: HashMap<u32, u32> = HashMap::new();
+        for (t, triangle) in self.triangles.iter().enumerate() {
+            if !self.alive[t] {
+                continue;
+            }
+            for &i in triangle {
+                let index = *new_index.entry(i).or_insert_with(|| {
+                    let i = i as usize;
+                    data.positions.push(mesh.positions[i]);
+                    if let Some(&n) = mesh.normals.get(i) {
+                        data.normals.push(n);
+                    }
+                    if let Some(&t) = mesh.texcoords.get(i) {
+                        data.texcoords.push(t);
+                    }
+                    if let Some(&c) = mesh.colors.get(i) {
+                        data.colors.push(c);
+                    }
+                    data.positions.len() as u32 - 1
+                });
+                data.indices.push(index);
+            }
+        }
+        data
+    }
+}
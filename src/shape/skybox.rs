@@ -105,13 +105,23 @@ impl Drawable for Skybox {
     fn draw(&self, camera: &Camera) -> Result<(), DrawError> {
         self.program.bind();
         self.texture.bind();
+        // the sky is always seen in perspective, so it still reads as surroundings in
+        // orthographic views
+        let params = camera.params();
+        let perspective = glm::ext::perspective(params.fov, params.ratio, params.near, params.far);
         self.program.set_uniform("view", &camera.view)?;
+        self.program.set_uniform("perspective", &perspective)?;
         self.program
-            .set_uniform("perspective", &camera.perspective)?;
+            .set_uniform("farDepth", &camera.far_plane_ndc())?;
 
-        self.shapegl.draw_vertices();
+        self.shapegl.draw_vertices(&self.program)?;
         self.texture.unbind();
 
         Ok(())
     }
+
+    /// The sky surrounds everything at infinity, so clicking it picks nothing.
+    fn draw_ids(&self, _program: &rendergl::Program) -> Result<(), DrawError> {
+        Ok(())
+    }
 }
@@ -0,0 +1,469 @@
+use std::collections::{HashMap, HashSet};
+
+use glm;
+
+use crate::shape::{MeshData, Shading};
+
+/// Attributes of a face corner which are interpolated across faces: texture coordinates,
+/// then colour.
+type CornerAttribs = [f32; 6];
+
+type Edge = (usize, usize);
+
+fn edge(a: usize, b: usize) -> Edge {
+    (a.min(b), a.max(b))
+}
+
+fn average(attribs: &[CornerAttribs]) -> CornerAttribs {
+    let mut sum = [0.0; 6];
+    for a in attribs {
+        for (s, x) in sum.iter_mut().zip(a) {
+            *s += x;
+        }
+    }
+    sum.map(|s| s / attribs.len() as f32)
+}
+
+/// Polygon mesh being subdivided. Faces refer to welded positions, so the surface is smooth
+/// across attribute seams, while corners keep their own attributes.
+struct PolyMesh {
+    positions: Vec<glm::Vec3>,
+    faces: Vec<Vec<usize>>,
+    attribs: Vec<Vec<CornerAttribs>>,
+    /// Edges which are kept sharp, besides the open and non-manifold ones.
+    creases: HashSet<Edge>,
+}
+
+/// How a vertex moves when it is subdivided, from the creases and borders through it.
+enum VertexRule {
+    Smooth,
+    /// On a crease or border between the two neighbours.
+    Crease(usize, usize),
+    /// Where three or more creases meet, it stays where it is.
+    Corner,
+}
+
+impl MeshData {
+    /// Subdivide the triangles of the mesh `levels` times with Loop subdivision, which splits
+    /// each triangle into four and smooths the surface towards a limit surface.
+    ///
+    /// Edges where the triangles on either side meet at more than `crease_angle` (in radians)
+    /// are kept sharp, as are open borders. Texture coordinates and colours are interpolated
+    /// linearly across each original triangle, and normals are recomputed with the same crease
+    /// angle if the mesh has normals.
+    pub fn subdivide_loop(&self, levels: u32, crease_angle: f32) -> MeshData {
+        let faces = self.triangles().map(|t| t.to_vec()).collect();
+        let mut mesh = PolyMesh::new(self, faces, crease_angle);
+        for _ in 0..levels {
+            mesh = mesh.loop_step();
+        }
+        mesh.into_mesh_data(self, crease_angle)
+    }
+
+    /// Subdivide the mesh `levels` times with Catmull-Clark subdivision, which splits each
+    /// polygon into quads and smooths the surface towards a limit surface. The result is
+    /// triangulated.
+    ///
+    /// Without `face_sizes`, each triangle is a face of its own. With them, polygons are
+    /// rebuilt from the triangles: each face of `n` vertices is the next `n - 2` triangles,
+    /// merged across the edges they share, however the face was triangulated. Pass `4` for
+    /// every face of a quad mesh, or the face sizes `ResourceLoader::load_obj_with_colors`
+    /// reads from the `f` lines of an OBJ file. Faces whose triangles do not form a polygon
+    /// with `n` vertices, or sizes which do not add up to the triangles, are left as triangles
+    /// and counted in the returned report. Creases, borders and attributes are handled as in
+    /// `subdivide_loop`.
+    pub fn subdivide_catmull_clark(
+        &self,
+        levels: u32,
+        crease_angle: f32,
+        face_sizes: Option<&[u32]>,
+    ) -> (MeshData, PolygonReport) {
+        let triangles: Vec<[u32; 3]> = self.triangles().collect();
+        let (faces, report) = match face_sizes {
+            Some(sizes) => merge_polygons(&triangles, sizes),
+            None => (
+                triangles.iter().map(|t| t.to_vec()).collect(),
+                PolygonReport::default(),
+            ),
+        };
+        let mut mesh = PolyMesh::new(self, faces, crease_angle);
+        for _ in 0..levels {
+            mesh = mesh.catmull_clark_step();
+        }
+        (mesh.into_mesh_data(self, crease_angle), report)
+    }
+}
+
+/// How `MeshData::subdivide_catmull_clark` rebuilt polygons from the given face sizes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PolygonReport {
+    /// Faces rebuilt as polygons.
+    pub merged: usize,
+    /// Faces left as triangles, since their triangles do not form a polygon of their size.
+    pub unmerged: usize,
+    /// Whether the face sizes did not add up to the triangles of the mesh, so that every face
+    /// was left as triangles.
+    pub mismatched_sizes: bool,
+}
+
+/// Merge `triangles` back into faces of the given `sizes`.
+fn merge_polygons(triangles: &[[u32; 3]], sizes: &[u32]) -> (Vec<Vec<u32>>, PolygonReport) {
+    let mut report = PolygonReport::default();
+    let needed: usize = sizes.iter().map(|&n| (n as usize).saturating_sub(2)).sum();
+    if needed != triangles.len() {
+        report.mismatched_sizes = true;
+        return (triangles.iter().map(|t| t.to_vec()).collect(), report);
+    }
+
+    let mut polygons: Vec<Vec<u32>> = Vec::new();
+    let mut rest = triangles;
+    for &n in sizes.iter().filter(|&&n| n >= 3) {
+        let (face, next) = rest.split_at(n as usize - 2);
+        rest = next;
+        match merge_polygon(face) {
+            Some(polygon) if polygon.len() == n as usize => {
+                report.merged += 1;
+                polygons.push(polygon);
+            }
+            _ => {
+                report.unmerged += 1;
+                polygons.extend(face.iter().map(|t| t.to_vec()));
+            }
+        }
+    }
+    (polygons, report)
+}
+
+/// Merge the triangles of one face into a polygon, adding each triangle across an edge of the
+/// polygon which it shares, wound the other way. Returns `None` if some triangle cannot be
+/// added that way.
+fn merge_polygon(face: &[[u32; 3]]) -> Option<Vec<u32>> {
+    let mut polygon = face[0].to_vec();
+    let mut rest: Vec<[u32; 3]> = face[1..].to_vec();
+    while !rest.is_empty() {
+        let (t, k, w) = rest.iter().enumerate().find_map(|(t, &[a, b, c])| {
+            (0..polygon.len()).find_map(|k| {
+                let (u, v) = (polygon[k], polygon[(k + 1) % polygon.len()]);
+                let w = [(b, a, c), (c, b, a), (a, c, b)]
+                    .iter()
+                    .find(|&&(from, to, _)| (from, to) == (u, v))
+                    .map(|&(_, _, w)| w)?;
+                Some((t, k, w)).filter(|_| !polygon.contains(&w))
+            })
+        })?;
+        polygon.insert(k + 1, w);
+        rest.swap_remove(t);
+    }
+    Some(polygon)
+}
+
+impl PolyMesh {
+    fn new(data: &MeshData, faces: Vec<Vec<u32>>, crease_angle: f32) -> PolyMesh {
+        let (positions, position_of) = data.weld_positions();
+        let corner_attribs = |i: u32| {
+            let i = i as usize;
+            let t = data
+                .texcoords
+                .get(i)
+                .cloned()
+                .unwrap_or(glm::vec2(0.0, 0.0));
+            let c = data
+                .colors
+                .get(i)
+                .cloned()
+                .unwrap_or(glm::vec4(0.0, 0.0, 0.0, 0.0));
+            [t.x, t.y, c.x, c.y, c.z, c.w]
+        };
+        let mut mesh = PolyMesh {
+            positions,
+            attribs: faces
+                .iter()
+                .map(|f| f.iter().map(|&i| corner_attribs(i)).collect())
+                .collect(),
+            faces: faces
+                .iter()
+                .map(|f| f.iter().map(|&i| position_of[i as usize]).collect())
+                .collect(),
+            creases: HashSet::new(),
+        };
+
+        let normals: Vec<glm::Vec3> = (0..mesh.faces.len()).map(|f| mesh.normal(f)).collect();
+        let min_cos = crease_angle.cos();
+        for (e, faces) in mesh.edge_faces() {
+            if let [f, g] = faces[..] {
+                if glm::dot(normals[f], normals[g]) < min_cos {
+                    mesh.creases.insert(e);
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Unit normal of face `f` (Newell's method, so that polygons need not be planar).
+    fn normal(&self, f: usize) -> glm::Vec3 {
+        let face = &self.faces[f];
+        let mut n = glm::vec3(0.0, 0.0, 0.0);
+        for (k, &i) in face.iter().enumerate() {
+            let (p, q) = (
+                self.positions[i],
+                self.positions[face[(k + 1) % face.len()]],
+            );
+            n = n + glm::vec3(
+                (p.y - q.y) * (p.z + q.z),
+                (p.z - q.z) * (p.x + q.x),
+                (p.x - q.x) * (p.y + q.y),
+            );
+        }
+        if glm::length(n) > 0.0 {
+            glm::normalize(n)
+        } else {
+            n
+        }
+    }
+
+    fn edge_faces(&self) -> HashMap<Edge, Vec<usize>> {
+        let mut edges: HashMap<Edge, Vec<usize>> = HashMap::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for k in 0..face.len() {
+                edges
+                    .entry(edge(face[k], face[(k + 1) % face.len()]))
+                    .or_default()
+                    .push(f);
+            }
+        }
+        edges
+    }
+
+    fn is_sharp(&self, e: Edge, faces: &[usize]) -> bool {
+        faces.len() != 2 || self.creases.contains(&e)
+    }
+
+    /// Neighbours of each vertex, and how each vertex moves.
+    fn vertex_rules(
+        &self,
+        edges: &HashMap<Edge, Vec<usize>>,
+    ) -> (Vec<Vec<usize>>, Vec<VertexRule>) {
+        let mut neighbours = vec![Vec::new(); self.positions.len()];
+        let mut sharp = vec![Vec::new(); self.positions.len()];
+        for (&(a, b), faces) in edges {
+            neighbours[a].push(b);
+            neighbours[b].push(a);
+            if self.is_sharp((a, b), faces) {
+                sharp[a].push(b);
+                sharp[b].push(a);
+            }
+        }
+        let rules = sharp
+            .iter()
+            .map(|s| match s[..] {
+                [a, b] => VertexRule::Crease(a, b),
+                [_, _, _, ..] => VertexRule::Corner,
+                _ => VertexRule::Smooth,
+            })
+            .collect();
+        (neighbours, rules)
+    }
+
+    /// Halves of each crease, split at the new vertices in `midpoints`.
+    fn split_creases(&self, midpoints: &HashMap<Edge, usize>) -> HashSet<Edge> {
+        self.creases
+            .iter()
+            .flat_map(|&(a, b)| {
+                let m = midpoints[&(a, b)];
+                vec![edge(a, m), edge(m, b)]
+            })
+            .collect()
+    }
+
+    fn loop_step(&self) -> PolyMesh {
+        let edges = self.edge_faces();
+        let (neighbours, rules) = self.vertex_rules(&edges);
+        let p = &self.positions;
+
+        let mut positions: Vec<glm::Vec3> = rules
+            .iter()
+            .enumerate()
+            .map(|(v, rule)| match *rule {
+                VertexRule::Corner => p[v],
+                VertexRule::Crease(a, b) => p[v] * 0.75 + (p[a] + p[b]) * 0.125,
+                VertexRule::Smooth => {
+                    let n = neighbours[v].len();
+                    let beta = if n == 3 {
+                        3.0 / 16.0
+                    } else {
+                        3.0 / (8.0 * n as f32)
+                    };
+                    let sum = neighbours[v]
+                        .iter()
+                        .fold(glm::vec3(0.0, 0.0, 0.0), |sum, &u| sum + p[u]);
+                    p[v] * (1.0 - n as f32 * beta) + sum * beta
+                }
+            })
+            .collect();
+
+        let mut midpoints: HashMap<Edge, usize> = HashMap::new();
+        for (&(a, b), faces) in &edges {
+            let point = if self.is_sharp((a, b), faces) {
+                (p[a] + p[b]) * 0.5
+            } else {
+                let opposite = |f: usize| {
+                    let c = self.faces[f].iter().find(|&&c| c != a && c != b);
+                    p[*c.unwrap_or(&a)]
+                };
+                (p[a] + p[b]) * 0.375 + (opposite(faces[0]) + opposite(faces[1])) * 0.125
+            };
+            midpoints.insert((a, b), positions.len());
+            positions.push(point);
+        }
+
+        let mut faces = Vec::with_capacity(self.faces.len() * 4);
+        let mut attribs = Vec::with_capacity(self.faces.len() * 4);
+        for (face, corners) in self.faces.iter().zip(&self.attribs) {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            let [ab, bc, ca] = [(a, b), (b, c), (c, a)].map(|(u, v)| midpoints[&edge(u, v)]);
+            let [ta, tb, tc] = [corners[0], corners[1], corners[2]];
+            let [tab, tbc, tca] = [[ta, tb], [tb, tc], [tc, ta]].map(|t| average(&t));
+            faces.extend(vec![
+                vec![a, ab, ca],
+                vec![ab, b, bc],
+                vec![ca, bc, c],
+                vec![ab, bc, ca],
+            ]);
+            attribs.extend(vec![
+                vec![ta, tab, tca],
+                vec![tab, tb, tbc],
+                vec![tca, tbc, tc],
+                vec![tab, tbc, tca],
+            ]);
+        }
+
+        PolyMesh {
+            creases: self.split_creases(&midpoints),
+            positions,
+            faces,
+            attribs,
+        }
+    }
+
+    fn catmull_clark_step(&self) -> PolyMesh {
+        let edges = self.edge_faces();
+        let (neighbours, rules) = self.vertex_rules(&edges);
+        let p = &self.positions;
+
+        let face_points: Vec<glm::Vec3> = self
+            .faces
+            .iter()
+            .map(|face| {
+                face.iter()
+                    .fold(glm::vec3(0.0, 0.0, 0.0), |sum, &i| sum + p[i])
+                    / face.len() as f32
+            })
+            .collect();
+        let mut vertex_faces = vec![Vec::new(); p.len()];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &i in face {
+                vertex_faces[i].push(f);
+            }
+        }
+
+        let mut positions: Vec<glm::Vec3> = rules
+            .iter()
+            .enumerate()
+            .map(|(v, rule)| match *rule {
+                VertexRule::Corner => p[v],
+                VertexRule::Crease(a, b) => p[v] * 0.75 + (p[a] + p[b]) * 0.125,
+                VertexRule::Smooth if vertex_faces[v].is_empty() => p[v],
+                VertexRule::Smooth => {
+                    let n = neighbours[v].len() as f32;
+                    let f = vertex_faces[v]
+                        .iter()
+                        .fold(glm::vec3(0.0, 0.0, 0.0), |sum, &f| sum + face_points[f])
+                        / vertex_faces[v].len() as f32;
+                    let r = neighbours[v]
+                        .iter()
+                        .fold(glm::vec3(0.0, 0.0, 0.0), |sum, &u| {
+                            sum + (p[v] + p[u]) * 0.5
+                        })
+                        / n;
+                    (f + r * 2.0 + p[v] * (n - 3.0)) / n
+                }
+            })
+            .collect();
+
+        let mut midpoints: HashMap<Edge, usize> = HashMap::new();
+        for (&(a, b), faces) in &edges {
+            let point = if self.is_sharp((a, b), faces) {
+                (p[a] + p[b]) * 0.5
+            } else {
+                (p[a] + p[b] + face_points[faces[0]] + face_points[faces[1]]) * 0.25
+            };
+            midpoints.insert((a, b), positions.len());
+            positions.push(point);
+        }
+
+        let mut faces = Vec::new();
+        let mut attribs = Vec::new();
+        for (f, (face, corners)) in self.faces.iter().zip(&self.attribs).enumerate() {
+            let center = positions.len();
+            positions.push(face_points[f]);
+            let center_attribs = average(corners);
+            let m = face.len();
+            for k in 0..m {
+                let (prev, next) = ((k + m - 1) % m, (k + 1) % m);
+                faces.push(vec![
+                    face[k],
+                    midpoints[&edge(face[k], face[next])],
+                    center,
+                    midpoints[&edge(face[prev], face[k])],
+                ]);
+                attribs.push(vec![
+                    corners[k],
+                    average(&[corners[k], corners[next]]),
+                    center_attribs,
+                    average(&[corners[prev], corners[k]]),
+                ]);
+            }
+        }
+
+        PolyMesh {
+            creases: self.split_creases(&midpoints),
+            positions,
+            faces,
+            attribs,
+        }
+    }
+
+    /// Triangulate the faces into mesh data with the attributes `original` has, sharing
+    /// vertices between corners with the same position and attributes.
+    fn into_mesh_data(self, original: &MeshData, crease_angle: f32) -> MeshData {
+        let uvs = !original.texcoords.is_empty();
+        let colors = !original.colors.is_empty();
+        let mut data = MeshData::default();
+        let mut vertices: HashMap<(usize, [u32; 6]), u32> = HashMap::new();
+        for (face, corners) in self.faces.iter().zip(&self.attribs) {
+            let mut indices = Vec::with_capacity(face.len());
+            for (&p, a) in face.iter().zip(corners) {
+                let key = (p, a.map(f32::to_bits));
+                let index = *vertices.entry(key).or_insert_with(|| {
+                    data.positions.push(self.positions[p]);
+                    if uvs {
+                        data.texcoords.push(glm::vec2(a[0], a[1]));
+                    }
+                    if colors {
+                        data.colors.push(glm::vec4(a[2], a[3], a[4], a[5]));
+                    }
+                    data.positions.len() as u32 - 1
+                });
+                indices.push(index);
+            }
+            for k in 1..indices.len() - 1 {
+                data.indices
+                    .extend_from_slice(&[indices[0], indices[k], indices[k + 1]]);
+            }
+        }
+        if !original.normals.is_empty() {
+            data.compute_normals(Shading::Smooth { crease_angle });
+        }
+        data
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use glm;
+
+use crate::shape::MeshData;
+
+/// Problems found by `MeshData::validate`.
+///
+/// Triangles and vertices are listed by index. Border loops and duplicate vertices are not
+/// problems in themselves, since open surfaces and attribute seams have them, but they can
+/// point to holes that should not be there.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Indices left over after the last whole triangle.
+    pub trailing_indices: usize,
+    /// Attributes with neither none nor one value per vertex.
+    pub mismatched_attributes: Vec<&'static str>,
+    /// Triangles with an index past the last vertex.
+    pub out_of_range: Vec<usize>,
+    /// Triangles with a repeated vertex, or with no area.
+    pub degenerate: Vec<usize>,
+    /// Vertices with a position, normal or texture coordinate which is infinite or NaN.
+    pub non_finite: Vec<usize>,
+    /// Edges along which both triangles are wound the same way, so one of them faces the
+    /// other way to the rest of the surface.
+    pub inconsistent_edges: usize,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Loops of edges with a triangle on only one side, around holes or open borders.
+    pub border_loops: usize,
+    /// Vertices identical in every attribute to an earlier one.
+    pub duplicate_vertices: usize,
+}
+
+impl ValidationReport {
+    /// Whether the mesh has none of the problems which make it draw or pick incorrectly.
+    pub fn is_valid(&self) -> bool {
+        self.trailing_indices == 0
+            && self.mismatched_attributes.is_empty()
+            && self.out_of_range.is_empty()
+            && self.degenerate.is_empty()
+            && self.non_finite.is_empty()
+            && self.inconsistent_edges == 0
+            && self.non_manifold_edges == 0
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = [
+            (self.trailing_indices, "trailing indices"),
+            (
+                self.out_of_range.len(),
+                "triangles with out-of-range indices",
+            ),
+            (self.degenerate.len(), "degenerate triangles"),
+            (self.non_finite.len(), "vertices with non-finite values"),
+            (self.inconsistent_edges, "edges with inconsistent winding"),
+            (self.non_manifold_edges, "non-manifold edges"),
+            (self.border_loops, "border loops"),
+            (self.duplicate_vertices, "duplicate vertices"),
+        ];
+        let mut problems: Vec<String> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, name)| format!("{} {}", count, name))
+            .collect();
+        for name in &self.mismatched_attributes {
+            problems.push(format!("{} do not match the vertices", name));
+        }
+        if problems.is_empty() {
+            write!(f, "no problems")
+        } else {
+            write!(f, "{}", problems.join(", "))
+        }
+    }
+}
+
+impl MeshData {
+    /// Check the indices and vertex data for problems.
+    ///
+    /// Connectivity is checked between vertices at the same position, allowing for rounding,
+    /// so that vertices split on attribute seams do not count as borders.
+    pub fn validate(&self) -> ValidationReport {
+        let count = self.positions.len();
+        let mut report = ValidationReport {
+            trailing_indices: self.indices.len() % 3,
+            ..ValidationReport::default()
+        };
+        let attributes = [
+            ("normals", self.normals.len()),
+            ("texture coordinates", self.texcoords.len()),
+            ("colours", self.colors.len()),
+        ];
+        report.mismatched_attributes = attributes
+            .iter()
+            .filter(|&&(_, len)| len != 0 && len != count)
+            .map(|&(name, _)| name)
+            .collect();
+
+        for i in 0..count {
+            let p = self.positions[i];
+            let n = self.normals.get(i).cloned().unwrap_or(p);
+            let t = self
+                .texcoords
+                .get(i)
+                .cloned()
+                .unwrap_or(glm::vec2(0.0, 0.0));
+            let values = [p.x, p.y, p.z, n.x, n.y, n.z, t.x, t.y];
+            if !values.iter().all(|x| x.is_finite()) {
+                report.non_finite.push(i);
+            }
+        }
+        let mut seen = HashSet::new();
+        report.duplicate_vertices = (0..count)
+            .filter(|&i| !seen.insert(self.vertex_key(i)))
+            .count();
+
+        let (_, position_of) = self.weld_positions();
+        let mut edges: HashMap<(usize, usize), Vec<bool>> = HashMap::new();
+        for (t, triangle) in self.triangles().enumerate() {
+            if triangle.iter().any(|&i| i as usize >= count) {
+                report.out_of_range.push(t);
+                continue;
+            }
+            let [a, b, c] = triangle.map(|i| position_of[i as usize]);
+            if a == b || b == c || a == c || self.triangle_area(triangle) == 0.0 {
+                report.degenerate.push(t);
+                continue;
+            }
+            for &(from, to) in &[(a, b), (b, c), (c, a)] {
+                edges
+                    .entry((from.min(to), from.max(to)))
+                    .or_default()
+                    .push(from < to);
+            }
+        }
+
+        let mut border = Vec::new();
+        for (&edge, directions) in &edges {
+            match directions.len() {
+                1 => border.push(edge),
+                2 if directions[0] == directions[1] => report.inconsistent_edges += 1,
+                2 => {}
+                _ => report.non_manifold_edges += 1,
+            }
+        }
+        report.border_loops = count_loops(&border);
+        report
+    }
+
+    /// Merge vertices which are identical in every attribute, and drop vertices which no
+    /// triangle uses. Triangles with out-of-range indices are dropped too. Returns the number
+    /// of vertices removed.
+    pub fn weld_vertices(&mut self) -> usize {
+        if self.is_point_cloud() {
+            return 0;
+        }
+        let count = self.positions.len();
+        let indices: Vec<u32> = self
+            .triangles()
+            .filter(|t| t.iter().all(|&i| (i as usize) < count))
+            .flat_map(|t| t.to_vec())
+            .collect();
+
+        let mut keys: HashMap<Vec<u32>, u32> = HashMap::new();
+        let mut welded = MeshData::default();
+        for old in indices {
+            let old = old as usize;
+            let next = welded.positions.len() as u32;
+            let index = *keys.entry(self.vertex_key(old)).or_insert_with(|| {
+                welded.positions.push(self.positions[old]);
+                if let Some(&n) = self.normals.get(old) {
+                    welded.normals.push(n);
+                }
+                if let Some(&t) = self.texcoords.get(old) {
+                    welded.texcoords.push(t);
+                }
+                if let Some(&c) = self.colors.get(old) {
+                    welded.colors.push(c);
+                }
+                next
+            });
+            welded.indices.push(index);
+        }
+        *self = welded;
+        count - self.positions.len()
+    }
+
+    /// Remove triangles with out-of-range indices, a repeated vertex, or no area, and any
+    /// indices after the last whole triangle. Returns the number of triangles removed.
+    pub fn remove_degenerate_triangles(&mut self) -> usize {
+        let count = self.positions.len();
+        let before = self.indices.len() / 3;
+        let kept: Vec<u32> = self
+            .triangles()
+            .filter(|&[a, b, c]| {
+                [a, b, c].iter().all(|&i| (i as usize) < count)
+                    && a != b
+                    && b != c
+                    && a != c
+                    && self.triangle_area([a, b, c]) > 0.0
+            })
+            .flat_map(|t| t.to_vec())
+            .collect();
+        self.indices = kept;
+        before - self.indices.len() / 3
+    }
+
+    /// Flip triangles so that each connected piece of the mesh is wound consistently, facing
+    /// outwards if it is closed, to match the counter-clockwise front faces `ui::View` culls
+    /// with. Open pieces face the way their normals point, if the mesh has normals, and the
+    /// way most of their triangles already face otherwise. Returns the number of triangles
+    /// flipped.
+    ///
+    /// Triangles are connected through edges with exactly two triangles, between vertices at
+    /// the same position. Triangles with out-of-range indices are left as they are.
+    pub fn fix_winding(&mut self) -> usize {
+        let count = self.positions.len();
+        let (_, position_of) = self.weld_positions();
+        let triangles: Vec<[u32; 3]> = self.triangles().collect();
+        let valid = |t: &[u32; 3]| t.iter().all(|&i| (i as usize) < count);
+
+        let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+        for (t, triangle) in triangles.iter().enumerate().filter(|(_, t)| valid(t)) {
+            let [a, b, c] = triangle.map(|i| position_of[i as usize]);
+            for &(from, to) in &[(a, b), (b, c), (c, a)] {
+                if from != to {
+                    edges
+                        .entry((from.min(to), from.max(to)))
+                        .or_default()
+                        .push((t, from < to));
+                }
+            }
+        }
+        let mut neighbours: Vec<Vec<(usize, bool)>> = vec![Vec::new(); triangles.len()];
+        let mut closed = vec![true; triangles.len()];
+        for sides in edges.values() {
+            match sides[..] {
+                // the same direction along the edge means opposite windings
+                [(t, d), (u, e)] => {
+                    neighbours[t].push((u, d == e));
+                    neighbours[u].push((t, d == e));
+                }
+                _ => sides.iter().for_each(|&(t, _)| closed[t] = false),
+            }
+        }
+
+        let mut flip: Vec<Option<bool>> = vec![None; triangles.len()];
+        let mut flipped = 0;
+        for seed in 0..triangles.len() {
+            if flip[seed].is_some() || !valid(&triangles[seed]) {
+                continue;
+            }
+            // orient the piece around `seed` consistently with it
+            let mut piece = vec![seed];
+            let mut queue = VecDeque::from(vec![seed]);
+            flip[seed] = Some(false);
+            while let Some(t) = queue.pop_front() {
+                for &(u, opposite) in &neighbours[t] {
+                    if flip[u].is_none() {
+                        flip[u] = Some(flip[t] != Some(opposite));
+                        piece.push(u);
+                        queue.push_back(u);
+                    }
+                }
+            }
+
+            // then choose which way the whole piece faces
+            let oriented = |t: usize| {
+                let [a, b, c] = triangles[t];
+                if flip[t] == Some(true) {
+                    [a, c, b]
+                } else {
+                    [a, b, c]
+                }
+            };
+            let facing: f32 = if piece.iter().all(|&t| closed[t]) {
+                piece
+                    .iter()
+                    .map(|&t| {
+                        let [a, b, c] = oriented(t).map(|i| self.positions[i as usize]);
+                        glm::dot(a, glm::cross(b, c))
+                    })
+                    .sum()
+            } else if self.normals.len() == count {
+                piece
+                    .iter()
+                    .map(|&t| {
+                        let corners = oriented(t);
+                        let [a, b, c] = corners.map(|i| self.positions[i as usize]);
+                        let normal = corners.iter().fold(glm::vec3(0.0, 0.0, 0.0), |n, &i| {
+                            n + self.normals[i as usize]
+                        });
+                        glm::dot(glm::cross(b - a, c - a), normal)
+                    })
+                    .sum()
+            } else {
+                let flips = piece.iter().filter(|&&t| flip[t] == Some(true)).count();
+                piece.len() as f32 - 2.0 * flips as f32
+            };
+            if facing < 0.0 {
+                for &t in &piece {
+                    flip[t] = flip[t].map(|f| !f);
+                }
+            }
+            for &t in &piece {
+                if flip[t] == Some(true) {
+                    self.indices.swap(3 * t + 1, 3 * t + 2);
+                    flipped += 1;
+                }
+            }
+        }
+        flipped
+    }
+
+    /// Area of the triangle with vertices `triangle`.
+    fn triangle_area(&self, triangle: [u32; 3]) -> f32 {
+        let [a, b, c] = triangle.map(|i| self.positions[i as usize]);
+        glm::length(glm::cross(b - a, c - a)) / 2.0
+    }
+
+    /// Bit pattern of every attribute of vertex `i`, for finding identical vertices.
+    fn vertex_key(&self, i: usize) -> Vec<u32> {
+        let p = self.positions[i];
+        let mut key = vec![p.x, p.y, p.z];
+        if let Some(n) = self.normals.get(i) {
+            key.extend(&[n.x, n.y, n.z]);
+        }
+        if let Some(t) = self.texcoords.get(i) {
+            key.extend(&[t.x, t.y]);
+        }
+        if let Some(c) = self.colors.get(i) {
+            key.extend(&[c.x, c.y, c.z, c.w]);
+        }
+        key.iter().map(|x| (x + 0.0).to_bits()).collect()
+    }
+}
+
+/// Number of connected loops formed by `edges`.
+fn count_loops(edges: &[(usize, usize)]) -> usize {
+    let mut parent: HashMap<usize, usize> = HashMap::new();
+    fn find(parent: &mut HashMap<usize, usize>, v: usize) -> usize {
+        let p = *parent.entry(v).or_insert(v);
+        if p == v {
+            return v;
+        }
+        let root = find(parent, p);
+        parent.insert(v, root);
+        root
+    }
+    let mut loops = 0;
+    for &(a, b) in edges {
+        let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+        if ra == rb {
+            // an edge between vertices already connected closes a loop
+            loops += 1;
+        } else {
+            parent.insert(ra, rb);
+        }
+    }
+    loops
+}
@@ -1,12 +1,16 @@
 use gl;
 use glm::vec3;
-use sdl2::keyboard::Keycode;
-use std::path::Path;
+use sdl2::keyboard::{self, Keycode};
+use sdl2::mouse::MouseButton;
+use std::path::{Path, PathBuf};
 
+use crate::animation::{AnimationClip, Animator};
 use crate::mesh;
+use crate::picking::{IdPicker, Pick};
 use crate::rendergl;
 use crate::resources::{self, ResourceLoader};
 use crate::shape::{self, Drawable};
+use crate::util::{Aabb, BoundingVolume};
 
 use crate::camera::*;
 
@@ -68,20 +72,86 @@ fn make_mesh(loader: &resources::ResourceLoader) -> Result<mesh::MeshObject, sha
     mesh::DepthMesh::new(&depth_data, h, w).build_shape(&loader)
 }
 
+/// Callback receiving the result of picking when the scene is clicked.
+type ClickHandler = Box<dyn FnMut(Option<&Pick>)>;
+
+/// How clicks find the object under the cursor.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum PickMethod {
+    /// Cast a ray against meshes on the CPU.
+    RayCast,
+    /// Render IDs on the GPU and read them back over the next frames.
+    IdBuffer,
+}
+
+/// Input scheme currently driving the camera.
+enum CameraMode {
+    /// Dragging (or the arrow keys) orbits a target, middle-dragging pans, scrolling dollies.
+    Arcball(ArcballController),
+    /// First-person flight: WASD/QE move, the mouse looks around, scrolling changes speed.
+    Fly(FlyController),
+}
+
 /// Scene implementation.
 ///
 /// A scene contains a list of `Drawable` objects and a program to render them with.
 /// Currently, the camera is also part of the scene.
 ///
+/// Objects in the scene can be animated by attaching an `AnimationClip` with `Scene::animate`.
+///
+/// Clicking (pressing and releasing the left button without dragging) picks the object under the
+/// cursor, or under the centre of the view while flying, and reports it to the handler set with
+/// `Scene::set_click_handler`. `G` switches between picking by ray casting and by GPU ID buffer.
+///
+/// `Tab` toggles between orbiting a target and flying through the scene, and `T` switches
+/// orbiting between turntable and free arcball rotation. `O` toggles between perspective and
+/// orthographic projection, and `Ctrl` + `O` cycles through every kind of `Projection`, both
+/// keeping the framing of whatever is being orbited. `Home` frames the whole scene, which is
+/// also done on startup.
+///
+/// `Ctrl` + a number key bookmarks the camera, and the number key alone glides back to the
+/// bookmark. Bookmarks are saved to `camera_bookmarks.txt` in the working directory.
+///
+/// `K` adds the current camera to the end of the camera path and `Ctrl` + `K` clears it. `P`
+/// plays the path back (or stops it), and `Ctrl` + `P` switches its curve between Catmull-Rom
+/// and Bezier. The path is saved to `camera_path.txt` in the working directory.
+///
 /// The scene will eventually also need to contain things such as lights and a scenegraph.
 pub struct Scene {
     shapes: Vec<Box<Drawable>>,
+    animators: Vec<(usize, Animator)>,
     camera: Camera,
+    camera_mode: CameraMode,
+    viewport: (i32, i32),
+    press: Option<(i32, i32)>,
+    click_handler: Option<ClickHandler>,
+    pick_method: PickMethod,
+    picker: IdPicker,
+    bookmarks: Bookmarks,
+    bookmarks_path: PathBuf,
+    /// Camera move in progress, and the point to orbit once it has finished.
+    transition: Option<(CameraTransition, glm::Vec3)>,
+    camera_path: CameraPath,
+    camera_path_file: PathBuf,
+    path_player: Option<PathPlayer>,
     _loader: ResourceLoader,
 }
 
 impl Scene {
     const ROT_DELTA: f32 = 0.01;
+    /// Nearest the camera focuses on what is in front of it when it has no target, so that a
+    /// camera at the origin still has something to orbit.
+    const MIN_FOCUS_DISTANCE: f32 = 1.0;
+    /// Furthest, in pixels, the cursor may move between press and release for a click.
+    const CLICK_SLOP: i32 = 3;
+    const BOOKMARKS_FILE: &'static str = "camera_bookmarks.txt";
+    /// Seconds taken to glide to a bookmark.
+    const TRANSITION_TIME: f32 = 0.6;
+    const CAMERA_PATH_FILE: &'static str = "camera_path.txt";
+    /// Animation clip played by the ball, under the assets directory.
+    const BALL_CLIP: &'static str = "animations/bounce.clip";
+    /// World units per second travelled when playing the camera path.
+    const PATH_SPEED: f32 = 1.0;
 
     pub fn new(assets_dir: &str) -> Result<Scene, Error> {
         let loader =
@@ -91,7 +161,7 @@ impl Scene {
             })?;
         println!("{}", loader);
 
-        let camera = CameraBuilder::new()
+        let mut camera = CameraBuilder::new()
             .eye(&vec3(1.5, 1.0, 1.5))
             .look(&vec3(-1.5, -1.0, -1.5))
             .up(&vec3(-1.0, 1.0, -1.0))
@@ -102,26 +172,354 @@ impl Scene {
         let mesh = mesh::MeshObject::from_obj(&loader, "models/untitled.obj", "shaders/mesh")?;
         //let mesh = make_mesh(&loader)?;
         let skybox = shape::Skybox::new(&loader)?;
+        let ball = shape::ShaderShape::sphere(&loader, 30, 30)?;
 
         let mut shapes: Vec<Box<Drawable>> = Vec::new();
         //shapes.push(Box::new(sphere));
         shapes.push(Box::new(mesh));
         shapes.push(Box::new(skybox));
+        let ball_index = shapes.len();
+        shapes.push(Box::new(ball));
         for shape in &mut shapes {
             shape.init()?;
         }
 
-        Ok(Scene {
+        let arcball = ArcballController::new(&mut camera, vec3(0.0, 0.0, 0.0));
+        let picker = IdPicker::new(&loader)?;
+
+        let bookmarks_path = PathBuf::from(Scene::BOOKMARKS_FILE);
+        let bookmarks = Bookmarks::load(&bookmarks_path).unwrap_or_else(|e| {
+            println!("Ignoring camera bookmarks: {}", e);
+            Bookmarks::new()
+        });
+
+        let camera_path_file = PathBuf::from(Scene::CAMERA_PATH_FILE);
+        let camera_path = CameraPath::load(&camera_path_file).unwrap_or_else(|e| {
+            println!("Ignoring camera path: {}", e);
+            CameraPath::new(PathCurve::CatmullRom)
+        });
+
+        let ball_clip = loader
+            .load_clip(Path::new(Scene::BALL_CLIP))
+            .map_err(|e| println!("Ignoring animation clip {}: {}", Scene::BALL_CLIP, e))
+            .ok();
+
+        let mut scene = Scene {
             shapes,
+            animators: Vec::new(),
             camera,
+            camera_mode: CameraMode::Arcball(arcball),
+            viewport: (1, 1),
+            press: None,
+            click_handler: None,
+            pick_method: PickMethod::RayCast,
+            picker,
+            bookmarks,
+            bookmarks_path,
+            transition: None,
+            camera_path,
+            camera_path_file,
+            path_player: None,
             _loader: loader,
-        })
+        };
+        if let Some(clip) = ball_clip {
+            scene.animate(ball_index, clip);
+        }
+        scene.frame_all();
+        Ok(scene)
     }
 
-    pub fn tick(&mut self) {
+    /// Advance the scene by `dt` seconds.
+    pub fn tick(&mut self, dt: f32) {
         for shape in &mut self.shapes {
             shape.tick();
         }
+        for (index, animator) in &mut self.animators {
+            animator.advance(dt);
+            self.shapes[*index].apply_pose(&animator.pose());
+        }
+        if let Some((ref mut transition, target)) = self.transition {
+            if transition.update(&mut self.camera, dt) {
+                self.transition = None;
+                self.retarget(target);
+            }
+        } else if let Some(ref mut player) = self.path_player {
+            if player.update(&self.camera_path, &mut self.camera, dt) {
+                self.stop_path();
+            }
+        } else if let CameraMode::Fly(ref mut fly) = self.camera_mode {
+            fly.update(&mut self.camera, dt);
+        }
+        if let Some(pick) = self.picker.poll() {
+            self.report_click(pick);
+        }
+    }
+
+    /// Whether the scene wants the cursor hidden and relative mouse motion reported, i.e. while
+    /// flying.
+    pub fn captures_mouse(&self) -> bool {
+        match self.camera_mode {
+            CameraMode::Fly(_) => true,
+            CameraMode::Arcball(_) => false,
+        }
+    }
+
+    fn toggle_camera_mode(&mut self) {
+        self.camera_mode = match self.camera_mode {
+            CameraMode::Arcball(_) => CameraMode::Fly(FlyController::from_camera(&self.camera)),
+            CameraMode::Fly(_) => {
+                let target = self.target_ahead();
+                CameraMode::Arcball(ArcballController::new(&mut self.camera, target))
+            }
+        };
+    }
+
+    /// Distance in front of the camera of what it is looking at: the orbit target, or when
+    /// flying, as far as the origin is (but at least `MIN_FOCUS_DISTANCE`).
+    fn focus_distance(&self) -> f32 {
+        match self.camera_mode {
+            CameraMode::Arcball(ref arcball) => arcball.distance(&self.camera),
+            CameraMode::Fly(_) => {
+                glm::length(self.camera.params().eye).max(Scene::MIN_FOCUS_DISTANCE)
+            }
+        }
+    }
+
+    /// Point in front of the camera to orbit when it has no target: as far away as the origin
+    /// is, but at least `MIN_FOCUS_DISTANCE`.
+    fn target_ahead(&self) -> glm::Vec3 {
+        let params = self.camera.params();
+        let distance = glm::length(params.eye).max(Scene::MIN_FOCUS_DISTANCE);
+        params.eye + glm::normalize(params.look) * distance
+    }
+
+    /// Re-aim the camera controller after the camera has been moved directly, orbiting `target`
+    /// from now on.
+    fn retarget(&mut self, target: glm::Vec3) {
+        self.camera_mode = match self.camera_mode {
+            CameraMode::Arcball(ref old) => {
+                let mut arcball = ArcballController::new(&mut self.camera, target);
+                arcball.style = old.style;
+                arcball.min_distance = old.min_distance;
+                arcball.max_distance = old.max_distance;
+                CameraMode::Arcball(arcball)
+            }
+            CameraMode::Fly(ref old) => {
+                let mut fly = FlyController::from_camera(&self.camera);
+                fly.speed = old.speed;
+                CameraMode::Fly(fly)
+            }
+        };
+    }
+
+    /// Switch the camera's projection, keeping the framing at the orbit target (or, when flying,
+    /// at the origin's distance). A lens shift moves the orbit target with the camera.
+    pub fn set_projection(&mut self, projection: Projection) {
+        let focus_distance = self.focus_distance();
+        let shift = self.camera.switch_projection(projection, focus_distance);
+        if let CameraMode::Arcball(ref arcball) = self.camera_mode {
+            let target = arcball.target() + shift;
+            self.retarget(target);
+        }
+        println!("Projection: {:?}", self.camera.projection());
+    }
+
+    /// Clear the frame, with depth testing set up for the camera's projection, so that cameras
+    /// with reverse-Z work however they were set up.
+    pub fn clear(&self) {
+        let projection = self.camera.projection();
+        unsafe {
+            gl::DepthFunc(projection.depth_func());
+            gl::ClearDepth(projection.clear_depth());
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    /// World-space bounds of everything in the scene which has bounds.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let bounds = self
+            .shapes
+            .iter()
+            .filter_map(|shape| shape.bounds())
+            .fold(Aabb::empty(), |aabb, b| aabb.union(&b));
+        if bounds.is_empty() {
+            None
+        } else {
+            Some(bounds)
+        }
+    }
+
+    /// Move the camera back or forth, without turning it, to fit the whole scene in view, and
+    /// orbit around the scene's centre.
+    pub fn frame_all(&mut self) {
+        let bounds = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let distance = self.camera.frame_bounds(&BoundingVolume::Box(bounds));
+        self.retarget(bounds.center());
+        // allow dollying in close and out far relative to the size of the scene
+        if let CameraMode::Arcball(ref mut arcball) = self.camera_mode {
+            arcball.min_distance = distance * 0.01;
+            arcball.max_distance = distance * 20.0;
+        }
+    }
+
+    /// Bookmark the camera as `name`, and save all bookmarks to disk.
+    pub fn save_bookmark(&mut self, name: &str) {
+        let bookmark = Bookmark::from_camera(&self.camera, self.focus_distance());
+        self.bookmarks.insert(name, bookmark);
+        if let Err(e) = self.bookmarks.save(&self.bookmarks_path) {
+            println!("Failed to save camera bookmarks: {}", e);
+        }
+    }
+
+    /// Glide the camera to the bookmark `name`. Returns whether there is such a bookmark.
+    ///
+    /// The bookmark's field of view, clip planes and projection take effect straight away; its position and
+    /// orientation are reached over `TRANSITION_TIME`.
+    pub fn restore_bookmark(&mut self, name: &str) -> bool {
+        let bookmark = match self.bookmarks.get(name) {
+            Some(bookmark) => *bookmark,
+            None => return false,
+        };
+        self.finish_transition();
+        self.camera.set_fov(bookmark.fov);
+        self.camera.set_clip_planes(bookmark.near, bookmark.far);
+        self.camera.set_projection(bookmark.projection);
+        let transition = CameraTransition::look(
+            &self.camera,
+            bookmark.eye,
+            bookmark.look,
+            bookmark.up,
+            Scene::TRANSITION_TIME,
+        );
+        self.transition = Some((transition, bookmark.target()));
+        true
+    }
+
+    /// Jump to the end of any camera transition in progress, so that input acts on where the
+    /// camera is headed.
+    fn finish_transition(&mut self) {
+        if let Some((mut transition, target)) = self.transition.take() {
+            transition.update(&mut self.camera, Scene::TRANSITION_TIME);
+            self.retarget(target);
+        }
+    }
+
+    /// Add the camera's current placement to the end of the camera path, and save the path.
+    pub fn capture_path_key(&mut self) {
+        self.camera_path.push(PathKey::from_camera(&self.camera));
+        self.save_path();
+    }
+
+    fn save_path(&self) {
+        if let Err(e) = self.camera_path.save(&self.camera_path_file) {
+            println!("Failed to save camera path: {}", e);
+        }
+    }
+
+    /// Start flying the camera along the camera path, if it has more than one key.
+    pub fn play_path(&mut self) {
+        if self.camera_path.keys().len() < 2 {
+            return;
+        }
+        self.path_player = Some(PathPlayer::with_speed(&self.camera_path, Scene::PATH_SPEED));
+    }
+
+    /// Stop playing the camera path, leaving the camera where it is.
+    pub fn stop_path(&mut self) {
+        if self.path_player.take().is_some() {
+            let target = self.target_ahead();
+            self.retarget(target);
+        }
+    }
+
+    /// Hand the camera back to the user: finish any transition and stop any path playback.
+    fn interrupt_camera(&mut self) {
+        self.finish_transition();
+        self.stop_path();
+    }
+
+    /// Bookmark name for a number key.
+    fn bookmark_name(keycode: &Keycode) -> Option<&'static str> {
+        match keycode {
+            Keycode::Num1 => Some("1"),
+            Keycode::Num2 => Some("2"),
+            Keycode::Num3 => Some("3"),
+            Keycode::Num4 => Some("4"),
+            Keycode::Num5 => Some("5"),
+            Keycode::Num6 => Some("6"),
+            Keycode::Num7 => Some("7"),
+            Keycode::Num8 => Some("8"),
+            Keycode::Num9 => Some("9"),
+            _ => None,
+        }
+    }
+
+    fn fly_movement(keycode: &Keycode) -> Option<Movement> {
+        match keycode {
+            Keycode::W => Some(Movement::Forward),
+            Keycode::S => Some(Movement::Back),
+            Keycode::A => Some(Movement::Left),
+            Keycode::D => Some(Movement::Right),
+            Keycode::E => Some(Movement::Up),
+            Keycode::Q => Some(Movement::Down),
+            _ => None,
+        }
+    }
+
+    /// Play `clip` on the `index`th object in the scene, replacing any clip already attached
+    /// to it.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not a valid object index.
+    pub fn animate(&mut self, index: usize, clip: AnimationClip) {
+        assert!(index < self.shapes.len(), "no scene object {}", index);
+        self.animators.retain(|(i, _)| *i != index);
+        self.animators.push((index, Animator::new(clip)));
+    }
+
+    /// Closest object under pixel `(x, y)`, if any.
+    pub fn pick(&self, x: i32, y: i32) -> Option<Pick> {
+        let ray = self.camera.unproject(x, y, self.viewport)?;
+        self.shapes
+            .iter()
+            .enumerate()
+            .filter_map(|(object, shape)| shape.ray_cast(&ray).map(|hit| Pick { object, hit }))
+            .min_by(|a, b| a.hit.distance.total_cmp(&b.hit.distance))
+    }
+
+    /// Call `handler` with the result of picking whenever the scene is clicked, replacing any
+    /// previous handler.
+    pub fn set_click_handler<F: FnMut(Option<&Pick>) + 'static>(&mut self, handler: F) {
+        self.click_handler = Some(Box::new(handler));
+    }
+
+    fn on_click(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        let (x, y) = match self.camera_mode {
+            CameraMode::Fly(_) => (self.viewport.0 / 2, self.viewport.1 / 2),
+            CameraMode::Arcball(_) => (x, y),
+        };
+        match self.pick_method {
+            PickMethod::RayCast => {
+                let pick = self.pick(x, y);
+                self.report_click(pick);
+            }
+            // reported from `tick` once the readback completes
+            PickMethod::IdBuffer => {
+                self.picker
+                    .request(&self.shapes, &self.camera, x, y, self.viewport)?
+            }
+        }
+        Ok(())
+    }
+
+    fn report_click(&mut self, pick: Option<Pick>) {
+        if let Some(ref mut handler) = self.click_handler {
+            handler(pick.as_ref());
+        }
     }
 
     /// Render the objects in the scene.
@@ -134,6 +532,7 @@ impl Scene {
     }
 
     pub fn on_resize(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        self.viewport = (x, y);
         self.camera.set_aspect((x as f32) / (y as f32));
         unsafe {
             gl::Viewport(0, 0, x, y);
@@ -141,29 +540,74 @@ impl Scene {
         Ok(())
     }
 
-    pub fn on_keydown(&mut self, keycode: &Keycode) -> Result<(), Error> {
+    pub fn on_keydown(&mut self, keycode: &Keycode, keymod: keyboard::Mod) -> Result<(), Error> {
+        let was_playing = self.path_player.is_some();
+        self.interrupt_camera();
+        let ctrl = keymod.intersects(keyboard::LCTRLMOD | keyboard::RCTRLMOD);
+        if let Some(name) = Scene::bookmark_name(keycode) {
+            if ctrl {
+                self.save_bookmark(name);
+            } else {
+                self.restore_bookmark(name);
+            }
+            return Ok(());
+        }
+        if let CameraMode::Fly(ref mut fly) = self.camera_mode {
+            if let Some(movement) = Scene::fly_movement(keycode) {
+                fly.set_moving(movement, true);
+                return Ok(());
+            }
+        }
+        if let CameraMode::Arcball(ref mut arcball) = self.camera_mode {
+            let camera = &mut self.camera;
+            match keycode {
+                Keycode::Left => arcball.orbit_by(camera, -Scene::ROT_DELTA, 0.0),
+                Keycode::Right => arcball.orbit_by(camera, Scene::ROT_DELTA, 0.0),
+                Keycode::Up => arcball.orbit_by(camera, 0.0, Scene::ROT_DELTA),
+                Keycode::Down => arcball.orbit_by(camera, 0.0, -Scene::ROT_DELTA),
+                Keycode::T => {
+                    arcball.style = match arcball.style {
+                        OrbitStyle::Turntable => OrbitStyle::Arcball,
+                        OrbitStyle::Arcball => OrbitStyle::Turntable,
+                    }
+                }
+                _ => {}
+            }
+        }
         match keycode {
-            Keycode::Left => {
-                self.camera
-                    .orbit(-Scene::ROT_DELTA, &glm::vec3(0.0, 1.0, 0.0));
-            }
-            Keycode::Right => {
-                self.camera
-                    .orbit(Scene::ROT_DELTA, &glm::vec3(0.0, 1.0, 0.0));
-            }
-            Keycode::Up => {
-                let axis = {
-                    let params = self.camera.params();
-                    glm::cross(params.up, params.look)
+            Keycode::Tab => self.toggle_camera_mode(),
+            Keycode::Home => self.frame_all(),
+            Keycode::K if ctrl => {
+                self.camera_path.clear();
+                self.save_path();
+            }
+            Keycode::K => self.capture_path_key(),
+            Keycode::P if ctrl => {
+                let curve = match self.camera_path.curve() {
+                    PathCurve::CatmullRom => PathCurve::Bezier,
+                    PathCurve::Bezier => PathCurve::CatmullRom,
                 };
-                self.camera.orbit(Scene::ROT_DELTA, &axis);
+                self.camera_path.set_curve(curve);
+                self.save_path();
+            }
+            Keycode::P if !was_playing => self.play_path(),
+            Keycode::G => {
+                self.pick_method = match self.pick_method {
+                    PickMethod::RayCast => PickMethod::IdBuffer,
+                    PickMethod::IdBuffer => PickMethod::RayCast,
+                }
+            }
+            Keycode::O if ctrl => {
+                let projection = self.camera.projection().next_kind();
+                self.set_projection(projection);
             }
-            Keycode::Down => {
-                let axis = {
-                    let params = self.camera.params();
-                    glm::cross(params.up, params.look)
+            Keycode::O => {
+                let projection = if self.camera.projection().is_orthographic() {
+                    Projection::Perspective
+                } else {
+                    Projection::Orthographic { height: 1.0 }
                 };
-                self.camera.orbit(-Scene::ROT_DELTA, &axis);
+                self.set_projection(projection);
             }
             Keycode::F => unsafe {
                 gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
@@ -176,9 +620,63 @@ impl Scene {
         Ok(())
     }
 
+    pub fn on_keyup(&mut self, keycode: &Keycode) -> Result<(), Error> {
+        if let CameraMode::Fly(ref mut fly) = self.camera_mode {
+            if let Some(movement) = Scene::fly_movement(keycode) {
+                fly.set_moving(movement, false);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn on_mouse_down(&mut self, button: MouseButton, x: i32, y: i32) -> Result<(), Error> {
+        self.interrupt_camera();
+        if button == MouseButton::Left {
+            self.press = Some((x, y));
+        }
+        if let CameraMode::Arcball(ref mut arcball) = self.camera_mode {
+            match button {
+                MouseButton::Left => arcball.begin_drag(DragMode::Rotate, x, y, self.viewport),
+                MouseButton::Middle => arcball.begin_drag(DragMode::Pan, x, y, self.viewport),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn on_mouse_up(&mut self, button: MouseButton, x: i32, y: i32) -> Result<(), Error> {
+        if button == MouseButton::Left {
+            if let Some((px, py)) = self.press.take() {
+                if (x - px).abs() <= Scene::CLICK_SLOP && (y - py).abs() <= Scene::CLICK_SLOP {
+                    self.on_click(x, y)?;
+                }
+            }
+        }
+        if let CameraMode::Arcball(ref mut arcball) = self.camera_mode {
+            if let MouseButton::Left | MouseButton::Middle = button {
+                arcball.end_drag();
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle mouse motion, given the cursor position and its movement in pixels since the last
+    /// event.
+    pub fn on_mouse_motion(&mut self, x: i32, y: i32, xrel: i32, yrel: i32) -> Result<(), Error> {
+        match self.camera_mode {
+            CameraMode::Arcball(ref mut arcball) => arcball.drag_to(&mut self.camera, x, y),
+            CameraMode::Fly(ref mut fly) => fly.look(xrel, yrel),
+        }
+        Ok(())
+    }
+
     pub fn on_scroll(&mut self, delta: i32) -> Result<(), Error> {
+        self.interrupt_camera();
         let dir = if delta > 0 { 1.0 } else { -1.0 };
-        self.camera.zoom(dir * 0.05);
+        match self.camera_mode {
+            CameraMode::Arcball(ref arcball) => arcball.dolly(&mut self.camera, 1.0 - dir * 0.1),
+            CameraMode::Fly(ref mut fly) => fly.scale_speed(1.0 + dir * 0.1),
+        }
         Ok(())
     }
 }
@@ -4,7 +4,7 @@ use std;
 
 /// Owns handles to SDL and GL contexts. Processes events and renders OpenGL scene.
 pub struct View {
-    _sdl_ctx: sdl2::Sdl,
+    sdl_ctx: sdl2::Sdl,
     _gl_ctx: sdl2::video::GLContext,
     window_ctx: sdl2::video::Window,
     event_pump: sdl2::EventPump,
@@ -49,7 +49,7 @@ impl View {
 
         let event_pump = sdl.event_pump()?;
         Ok(View {
-            _sdl_ctx: sdl,
+            sdl_ctx: sdl,
             _gl_ctx: gl_ctx,
             event_pump,
             window_ctx,
@@ -63,4 +63,12 @@ impl View {
     pub fn gl_swap_window(&mut self) {
         self.window_ctx.gl_swap_window();
     }
+
+    /// Hide the cursor and report only relative mouse motion, e.g. for mouse-look.
+    pub fn set_relative_mouse_mode(&self, enabled: bool) {
+        let mouse = self.sdl_ctx.mouse();
+        if mouse.relative_mouse_mode() != enabled {
+            mouse.set_relative_mouse_mode(enabled);
+        }
+    }
 }
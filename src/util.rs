@@ -2,6 +2,12 @@
 use glm;
 use std::f32;
 
+mod bounds;
+mod quat;
+
+pub use self::bounds::{Aabb, BoundingVolume};
+pub use self::quat::Quat;
+
 /// A point on a 3D shape primitive.
 pub enum SurfacePoint {
     /// Spherical point `r, theta, phi`.
@@ -10,6 +16,38 @@ pub enum SurfacePoint {
     Cylinder { r: f32, theta: f32, y: f32 },
     /// A point on a 2D disk coplanar with `y`. (e.g. a cylinder cap)
     Disk { r: f32, theta: f32, y: f32 },
+    /// A point on the face of a cube (half-width `R`) facing along `normal`, which must be a unit
+    /// axis vector. `s, t` are coordinates across the face, see `cube_face_axes`.
+    Cube { normal: glm::Vec3, s: f32, t: f32 },
+    /// A point `x, z` on the plane `y = 0`, facing up.
+    Plane { x: f32, z: f32 },
+    /// A point `theta, y` on the side of a cone with its apex at `y = R` and a base of radius `R`
+    /// at `y = -R`.
+    Cone { theta: f32, y: f32 },
+    /// A point on a torus around the y-axis: `theta` around a ring of radius `major`, and `phi`
+    /// around the tube of radius `minor` (0 on the outside, increasing upwards).
+    Torus {
+        major: f32,
+        minor: f32,
+        theta: f32,
+        phi: f32,
+    },
+    /// A point on a capsule: a cylinder of radius `r` between `y = -half_height` and
+    /// `y = half_height` capped by hemispheres. `s` is how far along the profile the point is,
+    /// from 0 at the top pole to 1 at the bottom pole.
+    Capsule {
+        r: f32,
+        half_height: f32,
+        theta: f32,
+        s: f32,
+    },
+    /// A point on an arbitrary surface, with everything already worked out (see
+    /// `shape::ParametricSurface`).
+    Parametric {
+        position: glm::Vec3,
+        normal: glm::Vec3,
+        texcoord: glm::Vec2,
+    },
 }
 
 impl SurfacePoint {
@@ -26,6 +64,35 @@ impl SurfacePoint {
             SurfacePoint::Cylinder { r, theta, y } | SurfacePoint::Disk { r, theta, y } => {
                 glm::vec3(polar_x(r, theta), y, polar_y(r, theta))
             }
+            SurfacePoint::Cube { normal, s, t } => {
+                let (u, v) = cube_face_axes(normal);
+                normal * SurfacePoint::R + u * s + v * t
+            }
+            SurfacePoint::Plane { x, z } => glm::vec3(x, 0.0, z),
+            SurfacePoint::Cone { theta, y } => {
+                // the radius shrinks linearly from `R` at the base to 0 at the apex
+                let r = (SurfacePoint::R - y) / 2.0;
+                glm::vec3(polar_x(r, theta), y, polar_y(r, theta))
+            }
+            SurfacePoint::Torus {
+                major,
+                minor,
+                theta,
+                ..
+            } => {
+                let ring = glm::vec3(polar_x(major, theta), 0.0, polar_y(major, theta));
+                ring + self.normal() * minor
+            }
+            SurfacePoint::Capsule {
+                r,
+                half_height,
+                theta,
+                s,
+            } => {
+                let (center_y, normal) = capsule_profile(r, half_height, theta, s);
+                glm::vec3(0.0, center_y, 0.0) + normal * r
+            }
+            SurfacePoint::Parametric { position, .. } => position,
         }
     }
 
@@ -40,6 +107,23 @@ impl SurfacePoint {
                 glm::vec3(n.x, 0.0, n.y)
             }
             SurfacePoint::Disk { y, .. } => glm::vec3(0.0, y.signum(), 0.0),
+            SurfacePoint::Cube { normal, .. } => normal,
+            SurfacePoint::Plane { .. } => glm::vec3(0.0, 1.0, 0.0),
+            SurfacePoint::Cone { theta, .. } => {
+                // the side rises by 2R over a run of R
+                glm::normalize(glm::vec3(glm::cos(theta), 0.5, glm::sin(theta)))
+            }
+            SurfacePoint::Torus { theta, phi, .. } => {
+                let ring = glm::cos(phi);
+                glm::vec3(polar_x(ring, theta), glm::sin(phi), polar_y(ring, theta))
+            }
+            SurfacePoint::Capsule {
+                r,
+                half_height,
+                theta,
+                s,
+            } => capsule_profile(r, half_height, theta, s).1,
+            SurfacePoint::Parametric { normal, .. } => normal,
         }
     }
 
@@ -61,10 +145,66 @@ impl SurfacePoint {
                 let v = 1.0 + y.signum() * (polar_y(r, theta) - 0.5);
                 glm::vec2(u, v)
             }
+            SurfacePoint::Cube { s, t, .. } => glm::vec2(s + SurfacePoint::R, t + SurfacePoint::R),
+            SurfacePoint::Plane { x, z } => glm::vec2(x + SurfacePoint::R, SurfacePoint::R - z),
+            SurfacePoint::Cone { theta, y } => {
+                let u = -theta / (2.0 * f32::consts::PI);
+                let v = -y - 0.5;
+                glm::vec2(u, v)
+            }
+            SurfacePoint::Torus { theta, phi, .. } => {
+                let u = -theta / (2.0 * f32::consts::PI);
+                let v = -phi / (2.0 * f32::consts::PI);
+                glm::vec2(u, v)
+            }
+            SurfacePoint::Capsule { theta, s, .. } => {
+                let u = -theta / (2.0 * f32::consts::PI);
+                glm::vec2(u, s)
+            }
+            SurfacePoint::Parametric { texcoord, .. } => texcoord,
         }
     }
 }
 
+/// Axes `(u, v)` across the cube face facing along the unit axis vector `normal`, such that
+/// `u x v = normal`. Seen from outside the cube, `u` points right and `v` up; the top and bottom
+/// faces have `u` along x.
+pub fn cube_face_axes(normal: glm::Vec3) -> (glm::Vec3, glm::Vec3) {
+    if normal.x != 0.0 {
+        let sign = normal.x.signum();
+        (glm::vec3(0.0, 0.0, -sign), glm::vec3(0.0, 1.0, 0.0))
+    } else if normal.y != 0.0 {
+        let sign = normal.y.signum();
+        (glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 0.0, -sign))
+    } else {
+        let sign = normal.z.signum();
+        (glm::vec3(sign, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0))
+    }
+}
+
+/// Height of the centre of curvature, and the normal, of the point a fraction `s` of the way
+/// down the profile of a capsule, see `SurfacePoint::Capsule`.
+fn capsule_profile(r: f32, half_height: f32, theta: f32, s: f32) -> (f32, glm::Vec3) {
+    let quarter = f32::consts::FRAC_PI_2 * r;
+    let d = s * (2.0 * quarter + 2.0 * half_height);
+    let (center_y, phi) = if d <= quarter {
+        (half_height, d / r)
+    } else if d <= quarter + 2.0 * half_height {
+        (half_height - (d - quarter), f32::consts::FRAC_PI_2)
+    } else {
+        (
+            -half_height,
+            f32::consts::FRAC_PI_2 + (d - quarter - 2.0 * half_height) / r,
+        )
+    };
+    let normal = glm::vec3(
+        spherical_x(1.0, theta, phi),
+        spherical_y(1.0, theta, phi),
+        spherical_z(1.0, theta, phi),
+    );
+    (center_y, normal)
+}
+
 pub fn linear_index(row: usize, col: usize, num_cols: usize) -> usize {
     row * num_cols + col
 }
@@ -0,0 +1,129 @@
+use glm;
+use std::f32;
+
+/// Axis-aligned bounding box.
+///
+/// A box containing nothing (`Aabb::empty`) has `min` greater than `max`, so that growing it by
+/// any point gives a box around just that point.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// A box containing nothing.
+    pub fn empty() -> Aabb {
+        Aabb::new(glm::to_vec3(f32::INFINITY), glm::to_vec3(f32::NEG_INFINITY))
+    }
+
+    /// Smallest box containing all of `points`.
+    pub fn from_points<'a, I: IntoIterator<Item = &'a glm::Vec3>>(points: I) -> Aabb {
+        points
+            .into_iter()
+            .fold(Aabb::empty(), |aabb, p| aabb.grow(*p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// Smallest box containing both this box and `point`.
+    pub fn grow(&self, point: glm::Vec3) -> Aabb {
+        Aabb::new(glm::min(self.min, point), glm::max(self.max, point))
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(glm::min(self.min, other.min), glm::max(self.max, other.max))
+    }
+
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Edge lengths along each axis.
+    pub fn size(&self) -> glm::Vec3 {
+        self.max - self.min
+    }
+
+    /// Index (0, 1 or 2 for x, y or z) of the axis along which the box is longest.
+    pub fn longest_axis(&self) -> usize {
+        let size = self.size();
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// The eight corners of the box.
+    pub fn corners(&self) -> [glm::Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            glm::vec3(a.x, a.y, a.z),
+            glm::vec3(b.x, a.y, a.z),
+            glm::vec3(a.x, b.y, a.z),
+            glm::vec3(b.x, b.y, a.z),
+            glm::vec3(a.x, a.y, b.z),
+            glm::vec3(b.x, a.y, b.z),
+            glm::vec3(a.x, b.y, b.z),
+            glm::vec3(b.x, b.y, b.z),
+        ]
+    }
+
+    /// Box around this box after it has been transformed by `transform`.
+    pub fn transform(&self, transform: &glm::Mat4) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        let corners = self.corners();
+        let transformed: Vec<glm::Vec3> = corners
+            .iter()
+            .map(|c| (*transform * c.extend(1.0)).truncate(3))
+            .collect();
+        Aabb::from_points(&transformed)
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Aabb {
+        Aabb::empty()
+    }
+}
+
+/// Region of space to fit in view, see `Camera::frame_bounds`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BoundingVolume {
+    Sphere { center: glm::Vec3, radius: f32 },
+    Box(Aabb),
+}
+
+impl BoundingVolume {
+    pub fn center(&self) -> glm::Vec3 {
+        match *self {
+            BoundingVolume::Sphere { center, .. } => center,
+            BoundingVolume::Box(ref aabb) => aabb.center(),
+        }
+    }
+
+    /// Radius of a sphere around `center` enclosing the volume.
+    pub fn radius(&self) -> f32 {
+        match *self {
+            BoundingVolume::Sphere { radius, .. } => radius,
+            BoundingVolume::Box(ref aabb) => glm::length(aabb.size()) / 2.0,
+        }
+    }
+}
+
+impl From<Aabb> for BoundingVolume {
+    fn from(aabb: Aabb) -> BoundingVolume {
+        BoundingVolume::Box(aabb)
+    }
+}
@@ -0,0 +1,217 @@
+use glm;
+use std::ops::Mul;
+
+/// Unit quaternion representing a rotation in 3D.
+///
+/// `glm` does not provide quaternions, so this is a small implementation covering what is needed
+/// for animating and orienting objects: axis-angle construction, composition, rotating vectors,
+/// spherical linear interpolation and conversion to rotation matrices.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Quat {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quat {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Quat {
+        Quat { w, x, y, z }
+    }
+
+    /// The identity rotation.
+    pub fn identity() -> Quat {
+        Quat::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Rotation of `angle` radians around `axis`. The axis does not need to be normalised.
+    pub fn from_axis_angle(axis: glm::Vec3, angle: f32) -> Quat {
+        let axis = glm::normalize(axis);
+        let (s, c) = (angle * 0.5).sin_cos();
+        Quat::new(c, axis.x * s, axis.y * s, axis.z * s)
+    }
+
+    /// Rotation described by an orthonormal rotation matrix.
+    pub fn from_mat3(m: &glm::Mat3) -> Quat {
+        // Shepperd's method: divide by the largest of the four candidates for stability
+        let (m00, m11, m22) = (m[0][0], m[1][1], m[2][2]);
+        let trace = m00 + m11 + m22;
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat::new(
+                s / 4.0,
+                (m[1][2] - m[2][1]) / s,
+                (m[2][0] - m[0][2]) / s,
+                (m[0][1] - m[1][0]) / s,
+            )
+        } else if m00 > m11 && m00 > m22 {
+            let s = (1.0 + m00 - m11 - m22).sqrt() * 2.0;
+            Quat::new(
+                (m[1][2] - m[2][1]) / s,
+                s / 4.0,
+                (m[1][0] + m[0][1]) / s,
+                (m[2][0] + m[0][2]) / s,
+            )
+        } else if m11 > m22 {
+            let s = (1.0 + m11 - m00 - m22).sqrt() * 2.0;
+            Quat::new(
+                (m[2][0] - m[0][2]) / s,
+                (m[1][0] + m[0][1]) / s,
+                s / 4.0,
+                (m[2][1] + m[1][2]) / s,
+            )
+        } else {
+            let s = (1.0 + m22 - m00 - m11).sqrt() * 2.0;
+            Quat::new(
+                (m[0][1] - m[1][0]) / s,
+                (m[2][0] + m[0][2]) / s,
+                (m[2][1] + m[1][2]) / s,
+                s / 4.0,
+            )
+        };
+        q.normalize()
+    }
+
+    /// Orientation of a camera looking along `forward` with `up` as close to upright as possible,
+    /// following the OpenGL convention that an unrotated camera looks down -z with +y up.
+    ///
+    /// Neither vector needs to be normalised. If they are parallel, some other up vector is
+    /// picked rather than failing.
+    pub fn look_rotation(forward: glm::Vec3, up: glm::Vec3) -> Quat {
+        let back = -glm::normalize(forward);
+        let mut right = glm::cross(up, back);
+        if glm::length(right) < 1e-6 {
+            // looking straight along `up`: any perpendicular will do
+            let other = if back.x.abs() < 0.9 {
+                glm::vec3(1.0, 0.0, 0.0)
+            } else {
+                glm::vec3(0.0, 1.0, 0.0)
+            };
+            right = glm::cross(other, back);
+        }
+        let right = glm::normalize(right);
+        let up = glm::cross(back, right);
+        Quat::from_mat3(&glm::Mat3::new(right, up, back))
+    }
+
+    /// Vector part `(x, y, z)` of the quaternion.
+    pub fn xyz(&self) -> glm::Vec3 {
+        glm::vec3(self.x, self.y, self.z)
+    }
+
+    pub fn dot(&self, other: &Quat) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Returns this quaternion scaled to unit length.
+    pub fn normalize(&self) -> Quat {
+        let len = self.length();
+        if len <= f32::EPSILON {
+            return Quat::identity();
+        }
+        Quat::new(self.w / len, self.x / len, self.y / len, self.z / len)
+    }
+
+    /// The conjugate, which for unit quaternions is the inverse rotation.
+    pub fn conjugate(&self) -> Quat {
+        Quat::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Rotate a vector by this (unit) quaternion.
+    pub fn rotate(&self, v: glm::Vec3) -> glm::Vec3 {
+        // v' = v + 2w(q x v) + 2q x (q x v)
+        let q = self.xyz();
+        let t = glm::cross(q, v) * 2.0;
+        v + t * self.w + glm::cross(q, t)
+    }
+
+    /// Spherical linear interpolation towards `other`, taking the shortest path.
+    ///
+    /// `t` outside of `[0, 1]` extrapolates along the same great arc.
+    pub fn slerp(&self, other: &Quat, t: f32) -> Quat {
+        let mut cos_theta = self.dot(other);
+        let mut end = *other;
+        // q and -q represent the same rotation; pick the one on our hemisphere
+        if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            end = Quat::new(-end.w, -end.x, -end.y, -end.z);
+        }
+
+        let (a, b) = if cos_theta > 0.9995 {
+            // nearly parallel: fall back to linear interpolation to avoid dividing by ~0
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+
+        Quat::new(
+            a * self.w + b * end.w,
+            a * self.x + b * end.x,
+            a * self.y + b * end.y,
+            a * self.z + b * end.z,
+        )
+        .normalize()
+    }
+
+    /// Rotation matrix equivalent to this quaternion.
+    pub fn to_mat3(&self) -> glm::Mat3 {
+        let Quat { w, x, y, z } = self.normalize();
+        glm::Mat3::new(
+            glm::vec3(
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y + w * z),
+                2.0 * (x * z - w * y),
+            ),
+            glm::vec3(
+                2.0 * (x * y - w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z + w * x),
+            ),
+            glm::vec3(
+                2.0 * (x * z + w * y),
+                2.0 * (y * z - w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ),
+        )
+    }
+
+    /// Homogeneous rotation matrix equivalent to this quaternion.
+    pub fn to_mat4(&self) -> glm::Mat4 {
+        let m = self.to_mat3();
+        glm::Mat4::new(
+            m[0].extend(0.0),
+            m[1].extend(0.0),
+            m[2].extend(0.0),
+            glm::vec4(0.0, 0.0, 0.0, 1.0),
+        )
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Quat {
+        Quat::identity()
+    }
+}
+
+/// Hamilton product: `a * b` applies `b` first, then `a`.
+impl Mul<Quat> for Quat {
+    type Output = Quat;
+
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}